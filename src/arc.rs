@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+
+use crate::audit::{self, Compliance};
+use crate::dmarc::{DmarcResult, PolicyOverrideReason};
+use crate::domain::normalize;
+use crate::Feedback;
+
+/// The result of validating the ARC chain of messages, as defined in RFC 8617 Section 4.4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArcResult {
+    None,
+    Pass,
+    Fail,
+}
+
+/// An ARC verdict given by a reporter in the comment of an override reason.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArcVerdict {
+    pub result: ArcResult,
+    /// The domains that sealed the messages by instance, if the reporter lists them.
+    pub sealers: BTreeMap<u32, String>,
+}
+
+/// Reads the instance of a sealer domain key such as `as[2].d` or `as.2.d`.
+fn sealer_instance(key: &str) -> Option<u32> {
+    let instance = key.strip_prefix("as")?.strip_suffix(".d")?;
+    let instance = instance
+        .strip_prefix('[')
+        .and_then(|instance| instance.strip_suffix(']'))
+        .or_else(|| instance.strip_prefix('.'))?;
+    instance.parse().ok()
+}
+
+impl ArcVerdict {
+    /// Parses the ARC result and sealers from a free-form comment such as
+    /// `arc=pass as[1].d=example.net`.
+    ///
+    /// Returns `None` if the comment has no ARC result.
+    pub fn parse(comment: &str) -> Option<Self> {
+        let mut result = None;
+        let mut sealers = BTreeMap::new();
+        for token in comment.split(|c: char| c.is_whitespace() || c == ';' || c == ',') {
+            let Some((key, value)) = token.split_once('=') else {
+                // Some reporters write sealers as `as.1.example.net`.
+                let sealer = token
+                    .strip_prefix("as.")
+                    .and_then(|rest| rest.split_once('.'))
+                    .and_then(|(instance, domain)| Some((instance.parse().ok()?, domain)));
+                if let Some((instance, domain)) = sealer {
+                    sealers.insert(instance, normalize(domain));
+                }
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+            match key.as_str() {
+                "arc" => {
+                    result = match value.to_lowercase().as_str() {
+                        "pass" => Some(ArcResult::Pass),
+                        "fail" => Some(ArcResult::Fail),
+                        "none" => Some(ArcResult::None),
+                        _ => None,
+                    }
+                }
+                // A sealer without instance is treated as the first one.
+                "sealer" | "arc.d" => {
+                    sealers.entry(1).or_insert_with(|| normalize(value));
+                }
+                key => {
+                    if let Some(instance) = sealer_instance(key) {
+                        sealers.insert(instance, normalize(value));
+                    }
+                }
+            }
+        }
+        Some(Self {
+            result: result?,
+            sealers,
+        })
+    }
+
+    /// The sealer of the highest instance, which handed the messages to the reporter.
+    pub fn last_sealer(&self) -> Option<&str> {
+        self.sealers.values().next_back().map(String::as_str)
+    }
+}
+
+/// Finds the ARC verdict among the override reasons of a record.
+pub fn verdict(reasons: &[PolicyOverrideReason]) -> Option<ArcVerdict> {
+    reasons
+        .iter()
+        .filter_map(|reason| reason.comment.as_deref())
+        .find_map(ArcVerdict::parse)
+}
+
+/// The ARC verdicts reported for the messages handed over by one sealer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SealerSummary {
+    /// The sealer domain, or `None` if reporters did not name it.
+    pub sealer: Option<String>,
+    /// Whether the sealer is one of the trusted sealers.
+    pub trusted: bool,
+    /// The number of messages with an ARC verdict.
+    pub messages: u64,
+    /// The number of messages whose ARC chain passed.
+    pub passed: u64,
+    /// The number of messages failing DMARC whose ARC chain passed.
+    pub dmarc_failed: u64,
+    /// The number of messages failing DMARC that were treated more leniently than the policy
+    /// demands because of a passing ARC chain.
+    pub rescued: u64,
+}
+
+/// Summarizes the ARC verdicts of reports by the sealer that handed the messages over.
+///
+/// Sealers listed in `trusted`, such as the mailing lists and forwarders of the domain owner, are
+/// marked as trusted.
+pub fn summarize(feedbacks: &[Feedback], trusted: &[String]) -> Vec<SealerSummary> {
+    let trusted: Vec<String> = trusted.iter().map(|sealer| normalize(sealer)).collect();
+    let mut summaries: BTreeMap<Option<String>, SealerSummary> = BTreeMap::new();
+    for feedback in feedbacks {
+        for record in &feedback.records {
+            let evaluated = &record.row.policy_evaluated;
+            let Some(verdict) = verdict(&evaluated.reasons) else {
+                continue;
+            };
+            let sealer = verdict.last_sealer().map(String::from);
+            let summary = summaries
+                .entry(sealer.clone())
+                .or_insert_with(|| SealerSummary {
+                    trusted: sealer
+                        .as_ref()
+                        .is_some_and(|sealer| trusted.contains(sealer)),
+                    sealer,
+                    ..Default::default()
+                });
+            let count = u64::from(record.row.count);
+            summary.messages += count;
+            if verdict.result != ArcResult::Pass {
+                continue;
+            }
+            summary.passed += count;
+            if evaluated.dkim == DmarcResult::Fail && evaluated.spf == DmarcResult::Fail {
+                summary.dmarc_failed += count;
+                if audit::compliance(record, feedback) == Compliance::Overridden {
+                    summary.rescued += count;
+                }
+            }
+        }
+    }
+    summaries.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dmarc::{Disposition, DmarcResult, PolicyOverride, PolicyOverrideReason};
+    use crate::fixtures;
+
+    use super::{summarize, ArcResult, ArcVerdict};
+
+    #[test]
+    fn parse_verdict() {
+        let verdict =
+            ArcVerdict::parse("arc=pass as[1].d=example.org as[2].d=Lists.example.net").unwrap();
+        assert_eq!(verdict.result, ArcResult::Pass);
+        assert_eq!(verdict.last_sealer(), Some("lists.example.net"));
+
+        let verdict = ArcVerdict::parse("arc=fail; as.1.d=example.org; spf=pass").unwrap();
+        assert_eq!(verdict.result, ArcResult::Fail);
+        assert_eq!(verdict.last_sealer(), Some("example.org"));
+
+        let verdict = ArcVerdict::parse("arc=pass as.1.google.com").unwrap();
+        assert_eq!(verdict.last_sealer(), Some("google.com"));
+
+        let verdict = ArcVerdict::parse("ARC=pass").unwrap();
+        assert_eq!(verdict.last_sealer(), None);
+        assert!(ArcVerdict::parse("forwarded by example.org").is_none());
+    }
+
+    #[test]
+    fn count_rescued_messages() {
+        let mut feedback = fixtures::report();
+        feedback.policy_published.p = Disposition::Reject;
+        feedback.policy_published.sp = Disposition::Reject;
+        let mut record = feedback.records[0].clone();
+        record.row.count = 5;
+        record.row.policy_evaluated.disposition = Disposition::None;
+        record.row.policy_evaluated.dkim = DmarcResult::Fail;
+        record.row.policy_evaluated.spf = DmarcResult::Fail;
+        record.row.policy_evaluated.reasons = vec![PolicyOverrideReason {
+            typ: PolicyOverride::LocalPolicy,
+            comment: Some("arc=pass as[1].d=lists.example.net".into()),
+        }];
+        let mut rejected = record.clone();
+        rejected.row.count = 2;
+        rejected.row.policy_evaluated.disposition = Disposition::Reject;
+        feedback.records = vec![record.clone(), rejected];
+
+        let summaries = summarize(&[feedback.clone()], &["Lists.example.net".into()]);
+        assert_eq!(summaries.len(), 1);
+        let summary = &summaries[0];
+        assert_eq!(summary.sealer.as_deref(), Some("lists.example.net"));
+        assert!(summary.trusted);
+        assert_eq!(summary.messages, 7);
+        assert_eq!(summary.dmarc_failed, 7);
+        assert_eq!(summary.rescued, 5);
+
+        // Quarantining instead of rejecting is honoring a policy applied to only some messages.
+        feedback.policy_published.pct = 50;
+        record.row.policy_evaluated.disposition = Disposition::Quarantine;
+        feedback.records = vec![record];
+        let summaries = summarize(&[feedback], &[]);
+        assert!(!summaries[0].trusted);
+        assert_eq!(summaries[0].rescued, 0);
+    }
+}
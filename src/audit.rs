@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::dmarc::{Disposition, DmarcResult, Feedback, Record};
+
+/// How the disposition applied by a reporter relates to the published policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Compliance {
+    /// The disposition is the one the policy requests.
+    Honored,
+    /// The disposition is less strict than requested, with an override reason.
+    Overridden,
+    /// The disposition is less strict than requested, without a reason.
+    Ignored,
+    /// The disposition is stricter than requested.
+    Stricter,
+}
+
+impl Compliance {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compliance::Honored => "honored",
+            Compliance::Overridden => "overridden",
+            Compliance::Ignored => "ignored",
+            Compliance::Stricter => "stricter",
+        }
+    }
+}
+
+impl fmt::Display for Compliance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Compares the disposition applied to a record with the one its published policy requests.
+///
+/// Failing messages are subject to `p` or `sp`. If `pct` is below 100, the next less strict
+/// disposition is accepted as well, as described in RFC 7489 Section 6.6.4. Passing messages are
+/// expected to be delivered.
+pub fn compliance(record: &Record, feedback: &Feedback) -> Compliance {
+    let policy = &feedback.policy_published;
+    let evaluated = &record.row.policy_evaluated;
+    let failed = evaluated.dkim == DmarcResult::Fail && evaluated.spf == DmarcResult::Fail;
+    let requested = if failed {
+        policy.requested(&record.identifiers.header_from)
+    } else {
+        Disposition::None
+    };
+    let lowest = if failed && policy.pct < 100 {
+        requested.relaxed()
+    } else {
+        requested
+    };
+    match evaluated.disposition {
+        disposition if disposition > requested => Compliance::Stricter,
+        disposition if disposition >= lowest => Compliance::Honored,
+        _ if evaluated.reasons.is_empty() => Compliance::Ignored,
+        _ => Compliance::Overridden,
+    }
+}
+
+/// How a reporter applied the published policies.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReporterAudit {
+    pub reporter: String,
+    /// The number of messages failing DMARC.
+    pub failed: u64,
+    /// The number of messages by compliance with the policy.
+    pub messages: BTreeMap<Compliance, u64>,
+    /// The policy domains whose policy was ignored.
+    pub ignored_domains: BTreeMap<String, u64>,
+}
+
+impl ReporterAudit {
+    pub fn count(&self, compliance: Compliance) -> u64 {
+        self.messages.get(&compliance).copied().unwrap_or_default()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.messages.values().sum()
+    }
+
+    /// Whether the reporter applied the policies to all messages, allowing for documented overrides.
+    pub fn is_honoring(&self) -> bool {
+        self.count(Compliance::Ignored) == 0 && self.count(Compliance::Stricter) == 0
+    }
+}
+
+/// Audits the dispositions applied by each reporting organization.
+pub fn audit(feedbacks: &[Feedback]) -> Vec<ReporterAudit> {
+    let mut audits: BTreeMap<String, ReporterAudit> = BTreeMap::new();
+    for feedback in feedbacks {
+        let reporter = &feedback.report_metadata.org_name;
+        let audit = audits
+            .entry(reporter.clone())
+            .or_insert_with(|| ReporterAudit {
+                reporter: reporter.clone(),
+                ..Default::default()
+            });
+        for record in &feedback.records {
+            let count = u64::from(record.row.count);
+            let evaluated = &record.row.policy_evaluated;
+            if evaluated.dkim == DmarcResult::Fail && evaluated.spf == DmarcResult::Fail {
+                audit.failed += count;
+            }
+            let compliance = compliance(record, feedback);
+            *audit.messages.entry(compliance).or_default() += count;
+            if compliance == Compliance::Ignored {
+                *audit
+                    .ignored_domains
+                    .entry(feedback.policy_published.domain.to_lowercase())
+                    .or_default() += count;
+            }
+        }
+    }
+    audits.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dmarc::{Disposition, DmarcResult, PolicyOverride, PolicyOverrideReason};
+    use crate::{fixtures, Feedback};
+
+    use super::{audit, compliance, Compliance};
+
+    fn failing_feedback(p: Disposition, pct: u8) -> Feedback {
+        let mut feedback = fixtures::report();
+        feedback.policy_published.p = p;
+        feedback.policy_published.sp = p;
+        feedback.policy_published.pct = pct;
+        feedback.records.truncate(1);
+        let evaluated = &mut feedback.records[0].row.policy_evaluated;
+        evaluated.dkim = DmarcResult::Fail;
+        evaluated.spf = DmarcResult::Fail;
+        evaluated.reasons.clear();
+        feedback
+    }
+
+    fn with_disposition(mut feedback: Feedback, disposition: Disposition) -> Compliance {
+        feedback.records[0].row.policy_evaluated.disposition = disposition;
+        compliance(&feedback.records[0], &feedback)
+    }
+
+    #[test]
+    fn compare_dispositions() {
+        let reject = failing_feedback(Disposition::Reject, 100);
+        assert_eq!(
+            with_disposition(reject.clone(), Disposition::Reject),
+            Compliance::Honored
+        );
+        assert_eq!(
+            with_disposition(reject.clone(), Disposition::None),
+            Compliance::Ignored
+        );
+        let sampled = failing_feedback(Disposition::Reject, 50);
+        assert_eq!(
+            with_disposition(sampled.clone(), Disposition::Quarantine),
+            Compliance::Honored
+        );
+        assert_eq!(
+            with_disposition(sampled, Disposition::None),
+            Compliance::Ignored
+        );
+        let none = failing_feedback(Disposition::None, 100);
+        assert_eq!(
+            with_disposition(none, Disposition::Quarantine),
+            Compliance::Stricter
+        );
+
+        let mut overridden = reject;
+        overridden.records[0].row.policy_evaluated.reasons = vec![PolicyOverrideReason {
+            typ: PolicyOverride::LocalPolicy,
+            comment: None,
+        }];
+        assert_eq!(
+            with_disposition(overridden, Disposition::None),
+            Compliance::Overridden
+        );
+    }
+
+    #[test]
+    fn audit_reporters() {
+        let mut feedback = failing_feedback(Disposition::Reject, 100);
+        feedback.records[0].row.count = 4;
+        feedback.records[0].row.policy_evaluated.disposition = Disposition::None;
+        let audits = audit(&[feedback]);
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].failed, 4);
+        assert_eq!(audits[0].count(Compliance::Ignored), 4);
+        assert!(!audits[0].is_honoring());
+        assert_eq!(audits[0].ignored_domains.values().sum::<u64>(), 4);
+    }
+}
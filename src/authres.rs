@@ -0,0 +1,214 @@
+use std::net::IpAddr;
+
+/// A single method result of an Authentication-Results header, such as `dkim=pass header.d=example.com`.
+#[derive(Debug, PartialEq)]
+pub struct MethodResult {
+    /// The authentication method, such as `dkim` or `spf`, lowercased.
+    pub method: String,
+    /// The result of the method, such as `pass`, lowercased.
+    pub result: String,
+    /// The properties of the result as `ptype.property` and value, such as `header.d` and `example.com`.
+    pub properties: Vec<(String, String)>,
+}
+
+impl MethodResult {
+    /// The value of the first property with the given name.
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(property, _)| property.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A parsed Authentication-Results header according to RFC 8601.
+#[derive(Debug, PartialEq)]
+pub struct AuthenticationResults {
+    /// The identifier of the server that performed the authentication.
+    pub authserv_id: String,
+    pub results: Vec<MethodResult>,
+}
+
+/// Removes comments in parentheses, which may be nested, outside of quoted strings.
+fn strip_comments(header: &str) -> String {
+    let mut stripped = String::with_capacity(header.len());
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in header.chars() {
+        if escaped {
+            escaped = false;
+            if depth == 0 {
+                stripped.push(c);
+            }
+            continue;
+        }
+        match c {
+            '\\' => {
+                escaped = true;
+                if depth == 0 {
+                    stripped.push(c);
+                }
+            }
+            '"' if depth == 0 => {
+                quoted = !quoted;
+                stripped.push(c);
+            }
+            '(' if !quoted => depth += 1,
+            ')' if !quoted && depth > 0 => {
+                depth -= 1;
+                // Comments separate tokens like whitespace.
+                if depth == 0 {
+                    stripped.push(' ');
+                }
+            }
+            _ if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(value) => value.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => value.into(),
+    }
+}
+
+/// Splits a result into tokens separated by whitespace, keeping whitespace within quoted strings.
+fn tokens(resinfo: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in resinfo.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                token.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    // Tolerate whitespace around the equals sign, as in "dkim = pass".
+    let mut joined: Vec<String> = vec![];
+    for token in tokens {
+        match joined.last_mut() {
+            Some(last) if last.ends_with('=') || token.starts_with('=') => last.push_str(&token),
+            _ => joined.push(token),
+        }
+    }
+    joined
+}
+
+fn parse_method_result(resinfo: &str) -> Option<MethodResult> {
+    let mut tokens = tokens(resinfo).into_iter();
+    let (method, result) = tokens.next()?.split_once('=').map(|(m, r)| {
+        // Strip the optional method version, as in "dkim/1".
+        let method = m.split('/').next().unwrap_or(m).trim().to_lowercase();
+        (method, r.trim().to_lowercase())
+    })?;
+    let properties = tokens
+        .filter_map(|token| {
+            let (name, value) = token.split_once('=')?;
+            Some((name.trim().to_lowercase(), unquote(value.trim())))
+        })
+        .collect();
+    Some(MethodResult {
+        method,
+        result,
+        properties,
+    })
+}
+
+impl AuthenticationResults {
+    /// Parses the value of an Authentication-Results header.
+    ///
+    /// Malformed method results are skipped. Returns `None` if the header has no authserv-id.
+    pub fn parse(header: &str) -> Option<Self> {
+        let header = strip_comments(header);
+        let mut parts = header.split(';');
+        let authserv_id = parts
+            .next()?
+            .split_whitespace()
+            .next()?
+            .trim()
+            .to_lowercase();
+        let results = parts
+            .filter_map(parse_method_result)
+            .filter(|result| result.method != "none")
+            .collect();
+        Some(Self {
+            authserv_id,
+            results,
+        })
+    }
+
+    /// The results of the given method.
+    pub fn method<'a>(&'a self, method: &'a str) -> impl Iterator<Item = &'a MethodResult> {
+        self.results.iter().filter(move |r| r.method == method)
+    }
+
+    /// The IP address of the connecting client, if recorded by an iprev or SPF result.
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.results.iter().find_map(|result| {
+            ["policy.iprev", "smtp.remote-ip", "smtp.client-ip"]
+                .iter()
+                .find_map(|name| result.property(name)?.parse().ok())
+        })
+    }
+}
+
+/// The domain part of an email address, or the value itself if it is not an address.
+pub fn domain_of(address: &str) -> &str {
+    let address = address.trim().trim_start_matches('<').trim_end_matches('>');
+    address
+        .rsplit_once('@')
+        .map_or(address, |(_, domain)| domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{domain_of, AuthenticationResults};
+
+    #[test]
+    fn parse_authentication_results() {
+        let header = "mx.example.net (version 1);
+            dkim=pass (2048-bit key) header.d=example.com header.s=sel1 header.b=\"abc def\";
+            spf = softfail (domain of bounce@example.org does not designate 192.0.2.1) smtp.mailfrom=bounce@example.org;
+            iprev=pass policy.iprev=192.0.2.1;
+            dmarc=fail (p=reject dis=none) header.from=example.com";
+        let ar = AuthenticationResults::parse(header).unwrap();
+        assert_eq!(ar.authserv_id, "mx.example.net");
+        let dkim = ar.method("dkim").next().unwrap();
+        assert_eq!(dkim.result, "pass");
+        assert_eq!(dkim.property("header.d"), Some("example.com"));
+        assert_eq!(dkim.property("header.s"), Some("sel1"));
+        assert_eq!(dkim.property("header.b"), Some("abc def"));
+        let spf = ar.method("spf").next().unwrap();
+        assert_eq!(spf.result, "softfail");
+        assert_eq!(
+            domain_of(spf.property("smtp.mailfrom").unwrap()),
+            "example.org"
+        );
+        assert_eq!(ar.client_ip(), Some("192.0.2.1".parse().unwrap()));
+        assert_eq!(
+            ar.method("dmarc").next().unwrap().property("header.from"),
+            Some("example.com")
+        );
+    }
+
+    #[test]
+    fn parse_no_results() {
+        let ar = AuthenticationResults::parse("mx.example.net; none").unwrap();
+        assert!(ar.results.is_empty());
+    }
+}
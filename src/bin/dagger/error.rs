@@ -0,0 +1,59 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+/// Errors of the command line tool, in addition to those of reading and analyzing reports.
+#[derive(Debug)]
+pub enum Error {
+    Report(dagger::Error),
+    WriteOutput(io::Error),
+    WriteFile(PathBuf, io::Error),
+    /// A policy argument not given as `DOMAIN:RECORD`.
+    InvalidPolicy(String),
+    Smtp(io::Error),
+    SmtpRejected(String),
+    RunSendmail(PathBuf, io::Error),
+    SendmailFailed(PathBuf, ExitStatus),
+    Serve(Box<dyn std::error::Error + Send + Sync>),
+    InvalidUrl(String),
+    Http(String, io::Error),
+    HttpStatus(String, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Report(e) => e.fmt(f),
+            Error::WriteOutput(e) => write!(f, "Could not write output: {e}"),
+            Error::WriteFile(path, e) => {
+                write!(f, "Could not write file '{}': {}", path.display(), e)
+            }
+            Error::InvalidPolicy(policy) => {
+                write!(f, "Invalid policy '{policy}': expected DOMAIN:RECORD")
+            }
+            Error::Smtp(e) => write!(f, "SMTP connection failed: {e}"),
+            Error::SmtpRejected(reply) => write!(f, "SMTP server rejected email: {reply}"),
+            Error::RunSendmail(path, e) => {
+                write!(f, "Could not run sendmail '{}': {}", path.display(), e)
+            }
+            Error::SendmailFailed(path, status) => {
+                write!(f, "Sendmail '{}' failed with {}", path.display(), status)
+            }
+            Error::Serve(e) => write!(f, "Could not start HTTP server: {e}"),
+            Error::InvalidUrl(url) => write!(f, "Unsupported URL '{url}'"),
+            Error::Http(url, e) => write!(f, "HTTP request to '{url}' failed: {e}"),
+            Error::HttpStatus(url, response) => {
+                write!(f, "HTTP request to '{url}' was rejected: {response}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<dagger::Error> for Error {
+    fn from(error: dagger::Error) -> Self {
+        Error::Report(error)
+    }
+}
@@ -0,0 +1,12 @@
+//! Test data built from the report fixtures in `tests/fixtures`.
+
+use dagger::{from_xml_str, Feedback, Limits};
+
+/// The report of `full.xml`.
+pub fn report() -> Feedback {
+    from_xml_str(
+        include_str!("../../../tests/fixtures/full.xml"),
+        &Limits::default(),
+    )
+    .unwrap()
+}
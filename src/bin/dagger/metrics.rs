@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use dagger::domain::normalize;
+use dagger::Feedback;
+
+/// Escapes a label value for the Prometheus text exposition format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let labels: Vec<String> = pairs
+        .iter()
+        .map(|(name, value)| format!(r#"{name}="{}""#, escape(value)))
+        .collect();
+    labels.join(",")
+}
+
+/// Renders metrics about the reports in the Prometheus text exposition format.
+///
+/// Messages are counted by RFC5322.From domain, reporter, disposition and the DKIM and SPF results
+/// of the policy evaluation. The output is suitable for a `/metrics` endpoint and for the textfile
+/// collector of the node exporter.
+pub fn prometheus(feedbacks: &[Feedback]) -> String {
+    let mut messages: BTreeMap<[String; 5], u64> = BTreeMap::new();
+    let mut reports: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut last_report: BTreeMap<&str, i64> = BTreeMap::new();
+    for feedback in feedbacks {
+        let metadata = &feedback.report_metadata;
+        let reporter = metadata.org_name.as_str();
+        *reports.entry(reporter).or_default() += 1;
+        let end = metadata.date_range.end.timestamp();
+        let last = last_report.entry(reporter).or_insert(end);
+        *last = (*last).max(end);
+        for record in &feedback.records {
+            let evaluated = &record.row.policy_evaluated;
+            let key = [
+                normalize(&record.identifiers.header_from),
+                reporter.to_string(),
+                evaluated.disposition.as_str().to_string(),
+                evaluated.dkim.as_str().to_string(),
+                evaluated.spf.as_str().to_string(),
+            ];
+            *messages.entry(key).or_default() += u64::from(record.row.count);
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str("# HELP dmarc_messages_total Messages reported in DMARC aggregate reports.\n");
+    out.push_str("# TYPE dmarc_messages_total counter\n");
+    for ([domain, reporter, disposition, dkim, spf], count) in &messages {
+        let labels = labels(&[
+            ("domain", domain),
+            ("reporter", reporter),
+            ("disposition", disposition),
+            ("dkim", dkim),
+            ("spf", spf),
+        ]);
+        writeln!(out, "dmarc_messages_total{{{labels}}} {count}").unwrap();
+    }
+    out.push_str("# HELP dmarc_reports_total DMARC aggregate reports received.\n");
+    out.push_str("# TYPE dmarc_reports_total counter\n");
+    for (reporter, count) in &reports {
+        let labels = labels(&[("reporter", reporter)]);
+        writeln!(out, "dmarc_reports_total{{{labels}}} {count}").unwrap();
+    }
+    out.push_str(
+        "# HELP dmarc_last_report_timestamp_seconds End of the latest report period of a reporter.\n",
+    );
+    out.push_str("# TYPE dmarc_last_report_timestamp_seconds gauge\n");
+    for (reporter, end) in &last_report {
+        let labels = labels(&[("reporter", reporter)]);
+        writeln!(out, "dmarc_last_report_timestamp_seconds{{{labels}}} {end}").unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+
+    use super::{escape, prometheus};
+
+    #[test]
+    fn render_metrics() {
+        let mut feedback = fixtures::report();
+        feedback.report_metadata.org_name = "example.org".into();
+        feedback.records.truncate(1);
+        feedback.records[0].row.count = 3;
+        feedback.records[0].identifiers.header_from = "Example.com".into();
+        let mut duplicate = feedback.records[0].clone();
+        duplicate.row.count = 2;
+        feedback.records.push(duplicate);
+        let evaluated = &feedback.records[0].row.policy_evaluated;
+        let end = feedback.report_metadata.date_range.end.timestamp();
+
+        let metrics = prometheus(&[feedback.clone(), feedback.clone()]);
+        let expected = format!(
+            r#"dmarc_messages_total{{domain="example.com",reporter="example.org",disposition="{}",dkim="{}",spf="{}"}} 10"#,
+            evaluated.disposition.as_str(),
+            evaluated.dkim.as_str(),
+            evaluated.spf.as_str(),
+        );
+        assert!(metrics.lines().any(|line| line == expected), "{metrics}");
+        assert!(metrics
+            .lines()
+            .any(|line| line == r#"dmarc_reports_total{reporter="example.org"} 2"#));
+        assert!(metrics.lines().any(|line| line
+            == format!(r#"dmarc_last_report_timestamp_seconds{{reporter="example.org"}} {end}"#)));
+        assert_eq!(escape("a\"b\\c\n"), r#"a\"b\\c\n"#);
+    }
+}
@@ -0,0 +1,351 @@
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use dagger::authres::domain_of;
+use dagger::dns::TxtLookup;
+use dagger::domain::{normalize, organizational_domain};
+use dagger::generate::to_gzip;
+use dagger::record::ReportUri;
+use dagger::xml::report_file_name;
+use dagger::Feedback;
+
+use crate::error::Error;
+
+/// Verifies that an external destination accepts reports for a policy domain, as described in
+/// RFC 7489 Section 7.1.
+///
+/// Destinations within the organizational domain of the policy domain need no verification. Others
+/// must publish a DMARC record at `<policy domain>._report._dmarc.<destination domain>`.
+pub fn verify_destination(
+    policy_domain: &str,
+    address: &str,
+    dns: &dyn TxtLookup,
+) -> Result<bool, Error> {
+    let destination = normalize(domain_of(address));
+    if organizational_domain(&destination) == organizational_domain(policy_domain) {
+        return Ok(true);
+    }
+    let name = format!("{}._report._dmarc.{destination}", normalize(policy_domain));
+    Ok(dns
+        .lookup_txt(&name)?
+        .iter()
+        .any(|txt| txt.trim_start().starts_with("v=DMARC1")))
+}
+
+/// Wraps a report into an email as described in RFC 7489 Section 7.2.1.1.
+///
+/// The report is attached as GZIP compressed XML, named after the report.
+pub fn report_email(feedback: &Feedback, from: &str, to: &str, date: DateTime<Utc>) -> Vec<u8> {
+    let metadata = &feedback.report_metadata;
+    let domain = &feedback.policy_published.domain;
+    let file_name = format!("{}.xml.gz", report_file_name(feedback));
+    let boundary = "=_dagger_report";
+    let mut email = String::new();
+    let mut header = |name: &str, value: &str| {
+        email.push_str(&format!("{name}: {value}\r\n"));
+    };
+    header("From", from);
+    header("To", to);
+    header("Date", &date.to_rfc2822());
+    header(
+        "Subject",
+        &format!(
+            "Report Domain: {domain} Submitter: {} Report-ID: <{}>",
+            metadata.org_name, metadata.report_id
+        ),
+    );
+    header(
+        "Message-ID",
+        &format!("<{}@{}>", metadata.report_id, metadata.org_name),
+    );
+    header("MIME-Version", "1.0");
+    header(
+        "Content-Type",
+        &format!("multipart/mixed; boundary=\"{boundary}\""),
+    );
+    email.push_str("\r\n");
+    email.push_str(&format!(
+        "--{boundary}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\
+         \r\n\
+         This is an aggregate report from {} for {domain}.\r\n\
+         \r\n\
+         --{boundary}\r\n\
+         Content-Type: application/gzip; name=\"{file_name}\"\r\n\
+         Content-Disposition: attachment; filename=\"{file_name}\"\r\n\
+         Content-Transfer-Encoding: base64\r\n\
+         \r\n",
+        metadata.org_name
+    ));
+    let encoded = STANDARD.encode(to_gzip(feedback));
+    for line in encoded.as_bytes().chunks(76) {
+        email.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        email.push_str("\r\n");
+    }
+    email.push_str(&format!("--{boundary}--\r\n"));
+    email.into_bytes()
+}
+
+/// A way of delivering emails.
+pub trait Transport {
+    fn send(&mut self, from: &str, to: &str, email: &[u8]) -> Result<(), Error>;
+}
+
+/// Delivers emails to an SMTP server, usually a local relay, without TLS or authentication.
+pub struct Smtp {
+    /// The address of the server, such as `localhost:25`.
+    pub server: String,
+    /// The name this host identifies itself with.
+    pub helo: String,
+}
+
+/// Reads a possibly multiline reply, failing if its code is not the expected one.
+fn expect_reply(reader: &mut impl BufRead, expected: u16) -> Result<(), Error> {
+    let mut reply = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(Error::Smtp)? == 0 {
+            return Err(Error::Smtp(io::ErrorKind::UnexpectedEof.into()));
+        }
+        reply.push_str(line.trim_end());
+        // A hyphen after the code marks a continued reply.
+        if line.as_bytes().get(3) != Some(&b'-') {
+            break;
+        }
+        reply.push(' ');
+    }
+    match reply.get(..3).and_then(|code| code.parse::<u16>().ok()) {
+        Some(code) if code == expected => Ok(()),
+        _ => Err(Error::SmtpRejected(reply)),
+    }
+}
+
+impl Transport for Smtp {
+    fn send(&mut self, from: &str, to: &str, email: &[u8]) -> Result<(), Error> {
+        let stream = TcpStream::connect(&self.server).map_err(Error::Smtp)?;
+        stream
+            .set_read_timeout(Some(Duration::from_secs(60)))
+            .map_err(Error::Smtp)?;
+        let mut writer = stream.try_clone().map_err(Error::Smtp)?;
+        let mut reader = BufReader::new(stream);
+        let mut command = |reader: &mut BufReader<TcpStream>, line: &str, expected| {
+            writer
+                .write_all(format!("{line}\r\n").as_bytes())
+                .map_err(Error::Smtp)?;
+            expect_reply(reader, expected)
+        };
+
+        expect_reply(&mut reader, 220)?;
+        command(&mut reader, &format!("EHLO {}", self.helo), 250)?;
+        command(&mut reader, &format!("MAIL FROM:<{from}>"), 250)?;
+        command(&mut reader, &format!("RCPT TO:<{to}>"), 250)?;
+        command(&mut reader, "DATA", 354)?;
+        let mut data = Vec::with_capacity(email.len() + 64);
+        for line in email.split_inclusive(|b| *b == b'\n') {
+            // Lines starting with a dot are escaped by another dot.
+            if line.starts_with(b".") {
+                data.push(b'.');
+            }
+            data.extend_from_slice(line);
+        }
+        if !data.ends_with(b"\r\n") {
+            data.extend_from_slice(b"\r\n");
+        }
+        data.extend_from_slice(b".");
+        command(&mut reader, &String::from_utf8_lossy(&data), 250)?;
+        command(&mut reader, "QUIT", 221)
+    }
+}
+
+/// Delivers emails by piping them to a sendmail compatible binary.
+pub struct Sendmail {
+    pub path: PathBuf,
+}
+
+impl Transport for Sendmail {
+    fn send(&mut self, from: &str, to: &str, email: &[u8]) -> Result<(), Error> {
+        let mut child = Command::new(&self.path)
+            .args(["-i", "-f", from, "--", to])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::RunSendmail(self.path.clone(), e))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin
+            .write_all(email)
+            .map_err(|e| Error::RunSendmail(self.path.clone(), e))?;
+        drop(stdin);
+        let status = child
+            .wait()
+            .map_err(|e| Error::RunSendmail(self.path.clone(), e))?;
+        if !status.success() {
+            return Err(Error::SendmailFailed(self.path.clone(), status));
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of sending a report to one of its destinations.
+#[derive(Debug, PartialEq)]
+pub enum Delivery {
+    Sent,
+    /// The external destination did not confirm that it accepts reports for the domain.
+    Unverified,
+    /// The report email of the given size exceeds the size limit of the destination.
+    TooLarge(usize),
+}
+
+/// Sends a report to each of the `rua` destinations of its policy domain.
+pub fn send_report(
+    feedback: &Feedback,
+    rua: &[String],
+    from: &str,
+    date: DateTime<Utc>,
+    transport: &mut dyn Transport,
+    dns: &dyn TxtLookup,
+) -> Vec<(String, Result<Delivery, Error>)> {
+    rua.iter()
+        .map(|uri| {
+            let delivery = ReportUri::parse(uri).map_err(Error::from).and_then(|uri| {
+                if !verify_destination(&feedback.policy_published.domain, &uri.address, dns)? {
+                    return Ok(Delivery::Unverified);
+                }
+                let email = report_email(feedback, from, &uri.address, date);
+                if uri.max_size.is_some_and(|max| email.len() as u64 > max) {
+                    return Ok(Delivery::TooLarge(email.len()));
+                }
+                transport.send(from, &uri.address, &email)?;
+                Ok(Delivery::Sent)
+            });
+            (uri.clone(), delivery)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use chrono::DateTime;
+    use dagger::generate::{generate, read_csv, Policies, Reporter};
+    use dagger::record::DmarcRecord;
+    use dagger::{from_email, Diagnostics, Feedback, Limits};
+
+    use super::{send_report, verify_destination, Delivery, Smtp};
+
+    fn feedback() -> Feedback {
+        let csv = "\
+timestamp,source_ip,header_from,envelope_from,envelope_to,dkim_domain,dkim_selector,dkim_result,spf_domain,spf_scope,spf_result,disposition
+1700006400,192.0.2.1,example.com,example.com,,example.com,s1,pass,example.com,mfrom,pass,
+";
+        let mut policies = Policies::default();
+        policies.insert(
+            "example.com",
+            DmarcRecord::parse("v=DMARC1; p=none").unwrap(),
+        );
+        let reporter = Reporter {
+            org_name: "mx.example.net".into(),
+            email: "dmarc@example.net".into(),
+            extra_contact_info: None,
+        };
+        let outcomes = read_csv(csv.as_bytes()).unwrap();
+        generate(&outcomes, &policies, &reporter)
+            .feedbacks
+            .remove(0)
+    }
+
+    /// Accepts a single SMTP transaction and returns the received commands and message.
+    fn smtp_stand_in(listener: TcpListener) -> (Vec<String>, String) {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut commands = vec![];
+        let mut message = String::new();
+        writer.write_all(b"220 stand-in ESMTP\r\n").unwrap();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end().to_string();
+            let reply: &[u8] = match line.split(' ').next().unwrap() {
+                "EHLO" => b"250-stand-in\r\n250 8BITMIME\r\n",
+                "DATA" => {
+                    writer.write_all(b"354 go ahead\r\n").unwrap();
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line == ".\r\n" {
+                            break;
+                        }
+                        message.push_str(&line);
+                    }
+                    b"250 queued\r\n"
+                }
+                "QUIT" => {
+                    writer.write_all(b"221 bye\r\n").unwrap();
+                    commands.push(line);
+                    return (commands, message);
+                }
+                _ => b"250 ok\r\n",
+            };
+            commands.push(line);
+            writer.write_all(reply).unwrap();
+        }
+    }
+
+    #[test]
+    fn verify_external_destination() {
+        let dns = HashMap::from([(
+            "example.com._report._dmarc.reports.example.org".to_string(),
+            vec!["v=DMARC1".to_string()],
+        )]);
+        assert!(verify_destination("example.com", "a@mail.example.com", &dns).unwrap());
+        assert!(verify_destination("example.com", "a@reports.example.org", &dns).unwrap());
+        assert!(!verify_destination("example.com", "a@example.net", &dns).unwrap());
+    }
+
+    #[test]
+    fn send_report_over_smtp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut smtp = Smtp {
+            server: listener.local_addr().unwrap().to_string(),
+            helo: "mx.example.net".into(),
+        };
+        let server = thread::spawn(move || smtp_stand_in(listener));
+        let dns = HashMap::new();
+        let feedback = feedback();
+        let rua = vec![
+            "mailto:dmarc@example.net".to_string(),
+            "mailto:dmarc@example.com!1k".to_string(),
+            "mailto:dmarc@example.com".to_string(),
+        ];
+        let date = DateTime::from_timestamp(1700100000, 0).unwrap();
+        let deliveries = send_report(&feedback, &rua, "dmarc@example.net", date, &mut smtp, &dns);
+        let (commands, message) = server.join().unwrap();
+
+        assert!(matches!(deliveries[0].1, Ok(Delivery::Unverified)));
+        assert!(matches!(deliveries[1].1, Ok(Delivery::TooLarge(_))));
+        assert!(matches!(deliveries[2].1, Ok(Delivery::Sent)));
+        assert_eq!(commands[1], "MAIL FROM:<dmarc@example.net>");
+        assert_eq!(commands[2], "RCPT TO:<dmarc@example.com>");
+        assert!(message.contains(&format!(
+            "Subject: Report Domain: example.com Submitter: mx.example.net Report-ID: <{}>\r\n",
+            feedback.report_metadata.report_id
+        )));
+        let reports = from_email(
+            message.as_bytes(),
+            &Limits::default(),
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+        assert_eq!(reports, vec![feedback]);
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use chrono::{DateTime, Utc};
+use dagger::dmarc::{Disposition, DmarcResult, PolicyPublished, Record};
+use dagger::domain::normalize;
+use dagger::filter::Filter;
+use dagger::Feedback;
+use serde::Serialize;
+use tiny_http::{Header, Server};
+
+use crate::error::Error;
+use crate::metrics;
+
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// A report without its records.
+#[derive(Debug, Serialize)]
+struct ReportSummary<'a> {
+    org_name: &'a str,
+    email: &'a str,
+    report_id: &'a str,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+    policy_published: &'a PolicyPublished,
+    records: usize,
+    messages: u64,
+    passed: u64,
+}
+
+/// A record with the report it belongs to.
+#[derive(Debug, Serialize)]
+struct ReportRecord<'a> {
+    org_name: &'a str,
+    report_id: &'a str,
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+    policy_domain: &'a str,
+    #[serde(flatten)]
+    record: &'a Record,
+}
+
+fn is_pass(record: &Record) -> bool {
+    let evaluated = &record.row.policy_evaluated;
+    evaluated.dkim == DmarcResult::Pass || evaluated.spf == DmarcResult::Pass
+}
+
+/// The property records are aggregated by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GroupBy {
+    Domain,
+    Source,
+    Reporter,
+    Disposition,
+    Day,
+}
+
+impl GroupBy {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "domain" => Some(GroupBy::Domain),
+            "source" => Some(GroupBy::Source),
+            "reporter" => Some(GroupBy::Reporter),
+            "disposition" => Some(GroupBy::Disposition),
+            "day" => Some(GroupBy::Day),
+            _ => None,
+        }
+    }
+
+    fn key(self, feedback: &Feedback, record: &Record) -> String {
+        match self {
+            GroupBy::Domain => normalize(&record.identifiers.header_from),
+            GroupBy::Source => record.row.source_ip.to_string(),
+            GroupBy::Reporter => feedback.report_metadata.org_name.clone(),
+            GroupBy::Disposition => record.row.policy_evaluated.disposition.as_str().into(),
+            GroupBy::Day => feedback
+                .report_metadata
+                .date_range
+                .begin
+                .date_naive()
+                .to_string(),
+        }
+    }
+}
+
+/// The messages of a group of records.
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct Aggregate {
+    key: String,
+    messages: u64,
+    /// The number of messages passing DMARC.
+    passed: u64,
+    quarantined: u64,
+    rejected: u64,
+}
+
+fn aggregate(feedbacks: &[Feedback], group_by: GroupBy) -> Vec<Aggregate> {
+    let mut aggregates: BTreeMap<String, Aggregate> = BTreeMap::new();
+    for feedback in feedbacks {
+        for record in &feedback.records {
+            let key = group_by.key(feedback, record);
+            let aggregate = aggregates.entry(key.clone()).or_insert_with(|| Aggregate {
+                key,
+                ..Default::default()
+            });
+            let count = u64::from(record.row.count);
+            let evaluated = &record.row.policy_evaluated;
+            aggregate.messages += count;
+            if is_pass(record) {
+                aggregate.passed += count;
+            }
+            match evaluated.disposition {
+                Disposition::None => {}
+                Disposition::Quarantine => aggregate.quarantined += count,
+                Disposition::Reject => aggregate.rejected += count,
+            }
+        }
+    }
+    aggregates.into_values().collect()
+}
+
+/// Decodes a percent-encoded component of a query string.
+fn decode_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A response to an HTTP request.
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn json<T: Serialize>(value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => Self {
+                status: 200,
+                content_type: "application/json",
+                body,
+            },
+            Err(e) => Self::error(500, &e.to_string()),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::json!({ "error": message })
+                .to_string()
+                .into_bytes(),
+        }
+    }
+}
+
+/// Serves reports through a JSON API, a web dashboard and Prometheus metrics.
+pub struct Dashboard {
+    feedbacks: Vec<Feedback>,
+    /// The expected `Authorization` header if basic authentication is required.
+    authorization: Option<String>,
+}
+
+impl Dashboard {
+    /// Creates a dashboard of the given reports, requiring basic authentication with the given
+    /// `user:password` credentials if any.
+    pub fn new(feedbacks: Vec<Feedback>, credentials: Option<&str>) -> Self {
+        Self {
+            feedbacks,
+            authorization: credentials
+                .map(|credentials| format!("Basic {}", BASE64_STANDARD.encode(credentials))),
+        }
+    }
+
+    fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        let Some(expected) = &self.authorization else {
+            return true;
+        };
+        let Some(given) = authorization else {
+            return false;
+        };
+        // Compare in constant time to not reveal how much of the credentials is correct.
+        given.len() == expected.len()
+            && given
+                .bytes()
+                .zip(expected.bytes())
+                .fold(0, |difference, (a, b)| difference | (a ^ b))
+                == 0
+    }
+
+    /// Answers a request for the URL, which consists of the path and query string.
+    pub fn handle(&self, method: &str, url: &str, authorization: Option<&str>) -> Response {
+        if !self.is_authorized(authorization) {
+            return Response::error(401, "authentication required");
+        }
+        if method != "GET" {
+            return Response::error(405, "only GET is supported");
+        }
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let mut filter = Filter::default();
+        let mut group_by = None;
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let (key, value) = (decode_component(key), decode_component(value));
+            if key == "by" {
+                match GroupBy::parse(&value) {
+                    Some(by) => group_by = Some(by),
+                    None => return Response::error(400, &format!("cannot group by '{value}'")),
+                }
+                continue;
+            }
+            match filter.set(&key, &value) {
+                Ok(true) => {}
+                Ok(false) => return Response::error(400, &format!("unknown parameter '{key}'")),
+                Err(e) => return Response::error(400, &e.to_string()),
+            }
+        }
+        let feedbacks = filter.apply(&self.feedbacks);
+        match path {
+            "/" | "/index.html" => Response {
+                status: 200,
+                content_type: "text/html; charset=utf-8",
+                body: DASHBOARD.as_bytes().to_vec(),
+            },
+            "/api/reports" => {
+                let reports: Vec<ReportSummary> = feedbacks
+                    .iter()
+                    .map(|feedback| {
+                        let metadata = &feedback.report_metadata;
+                        let count = |record: &Record| u64::from(record.row.count);
+                        ReportSummary {
+                            org_name: &metadata.org_name,
+                            email: &metadata.email,
+                            report_id: &metadata.report_id,
+                            begin: metadata.date_range.begin,
+                            end: metadata.date_range.end,
+                            policy_published: &feedback.policy_published,
+                            records: feedback.records.len(),
+                            messages: feedback.records.iter().map(count).sum(),
+                            passed: feedback
+                                .records
+                                .iter()
+                                .filter(|record| is_pass(record))
+                                .map(count)
+                                .sum(),
+                        }
+                    })
+                    .collect();
+                Response::json(&reports)
+            }
+            "/api/records" => {
+                let records: Vec<ReportRecord> = feedbacks
+                    .iter()
+                    .flat_map(|feedback| {
+                        let metadata = &feedback.report_metadata;
+                        feedback.records.iter().map(|record| ReportRecord {
+                            org_name: &metadata.org_name,
+                            report_id: &metadata.report_id,
+                            begin: metadata.date_range.begin,
+                            end: metadata.date_range.end,
+                            policy_domain: &feedback.policy_published.domain,
+                            record,
+                        })
+                    })
+                    .collect();
+                Response::json(&records)
+            }
+            "/metrics" => Response {
+                status: 200,
+                content_type: "text/plain; version=0.0.4; charset=utf-8",
+                body: metrics::prometheus(&feedbacks).into_bytes(),
+            },
+            "/api/aggregates" => {
+                Response::json(&aggregate(&feedbacks, group_by.unwrap_or(GroupBy::Domain)))
+            }
+            _ => Response::error(404, "not found"),
+        }
+    }
+}
+
+/// Serves the dashboard on the given address until the process is terminated.
+pub fn serve(address: SocketAddr, dashboard: &Dashboard) -> Result<(), Error> {
+    let server = Server::http(address).map_err(Error::Serve)?;
+    for request in server.incoming_requests() {
+        let authorization = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Authorization"))
+            .map(|header| header.value.to_string());
+        let response = dashboard.handle(
+            request.method().as_str(),
+            request.url(),
+            authorization.as_deref(),
+        );
+        let mut reply = tiny_http::Response::from_data(response.body)
+            .with_status_code(response.status)
+            .with_header(
+                Header::from_bytes("Content-Type", response.content_type).expect("valid header"),
+            );
+        if response.status == 401 {
+            reply.add_header(
+                Header::from_bytes("WWW-Authenticate", r#"Basic realm="dagger""#)
+                    .expect("valid header"),
+            );
+        }
+        // A client that went away does not affect other clients.
+        let _ = request.respond(reply);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+
+    use super::{decode_component, Dashboard};
+
+    #[test]
+    fn decode_query() {
+        assert_eq!(decode_component("a%2Eb+c%zz"), "a.b c%zz");
+    }
+
+    #[test]
+    fn answer_requests() {
+        let feedback = fixtures::report();
+        let reporter = feedback.report_metadata.org_name.clone();
+        let records = feedback.records.len();
+        let dashboard = Dashboard::new(vec![feedback], Some("admin:secret"));
+        let authorization = Some("Basic YWRtaW46c2VjcmV0");
+
+        assert_eq!(dashboard.handle("GET", "/api/reports", None).status, 401);
+        assert_eq!(
+            dashboard
+                .handle("GET", "/api/reports", Some("Basic YWRtaW46d3Jvbmc="))
+                .status,
+            401
+        );
+        let response = dashboard.handle("GET", "/", authorization);
+        assert_eq!(response.status, 200);
+        assert!(response.content_type.starts_with("text/html"));
+
+        let response = dashboard.handle("GET", "/api/records", authorization);
+        let json: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), records);
+        assert_eq!(json[0]["org_name"], reporter.as_str());
+        assert!(json[0]["row"]["source_ip"].is_string());
+
+        let response = dashboard.handle(
+            "GET",
+            &format!("/api/aggregates?by=reporter&reporter={reporter}"),
+            authorization,
+        );
+        let json: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(json[0]["key"], reporter.as_str());
+
+        let response = dashboard.handle(
+            "GET",
+            "/api/reports?since=2000-01-01&until=2000-01-02",
+            authorization,
+        );
+        assert_eq!(response.body, b"[]");
+        assert_eq!(
+            dashboard
+                .handle("GET", "/api/records?disposition=discard", authorization)
+                .status,
+            400
+        );
+        assert_eq!(
+            dashboard
+                .handle("GET", "/api/nothing", authorization)
+                .status,
+            404
+        );
+    }
+}
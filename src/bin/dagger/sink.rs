@@ -0,0 +1,135 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use dagger::dmarc::Record;
+use dagger::{Feedback, ReportSink};
+
+use crate::error::Error;
+use crate::http::Url;
+use crate::metrics;
+use crate::opensearch;
+use crate::timeseries;
+use crate::tui;
+use crate::ui;
+
+/// Prints the details of each report.
+pub struct ListSink<W: Write>(pub W);
+
+impl<W: Write> ReportSink<Error> for ListSink<W> {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), Error> {
+        for feedback in feedbacks {
+            writeln!(self.0, "{}", ui::ReportDetails(feedback)).map_err(Error::WriteOutput)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints the records of all reports in a single table.
+pub struct AggregateSink<W: Write>(pub W);
+
+impl<W: Write> ReportSink<Error> for AggregateSink<W> {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), Error> {
+        let Some(begin) = feedbacks
+            .iter()
+            .map(|f| f.report_metadata.date_range.begin)
+            .min()
+        else {
+            return Ok(());
+        };
+        let end = feedbacks
+            .iter()
+            .map(|f| f.report_metadata.date_range.end)
+            .max()
+            .unwrap();
+        let records: Vec<&Record> = feedbacks.iter().flat_map(|f| &f.records).collect();
+        let table = ui::build_records_table(&records);
+        writeln!(
+            self.0,
+            " Aggregate Report Details\n--------------------------\nTimeframe: {begin} to {end}\n\n{table}"
+        )
+        .map_err(Error::WriteOutput)
+    }
+}
+
+/// Writes Prometheus metrics of all reports to a file for the textfile collector.
+///
+/// The file is replaced atomically so that the collector never reads a partial file.
+pub struct MetricsFileSink(pub PathBuf);
+
+impl ReportSink<Error> for MetricsFileSink {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), Error> {
+        let mut temporary = self.0.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        fs::write(&temporary, metrics::prometheus(feedbacks))
+            .map_err(|e| Error::WriteFile(temporary.clone(), e))?;
+        fs::rename(&temporary, &self.0).map_err(|e| Error::WriteFile(self.0.clone(), e))
+    }
+}
+
+/// Writes the record counts of all reports in the InfluxDB line protocol to a file.
+pub struct InfluxFileSink(pub PathBuf);
+
+impl ReportSink<Error> for InfluxFileSink {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), Error> {
+        fs::write(&self.0, timeseries::line_protocol(feedbacks))
+            .map_err(|e| Error::WriteFile(self.0.clone(), e))
+    }
+}
+
+/// Writes the record counts of all reports to an InfluxDB write endpoint.
+pub struct InfluxSink {
+    pub url: Url,
+    /// The API token required by InfluxDB 2.
+    pub token: Option<String>,
+}
+
+impl ReportSink<Error> for InfluxSink {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), Error> {
+        timeseries::write_influx(&self.url, self.token.as_deref(), feedbacks)
+    }
+}
+
+/// Sends all reports as metrics and logs to an OTLP/HTTP endpoint.
+pub struct OtlpSink(pub Url);
+
+impl ReportSink<Error> for OtlpSink {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), Error> {
+        timeseries::export_otlp(&self.0, feedbacks)
+    }
+}
+
+/// Writes the records of all reports as bulk API requests for OpenSearch or Elasticsearch to a file.
+pub struct BulkFileSink {
+    pub path: PathBuf,
+    pub index: String,
+}
+
+impl ReportSink<Error> for BulkFileSink {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), Error> {
+        fs::write(&self.path, opensearch::bulk_ndjson(feedbacks, &self.index))
+            .map_err(|e| Error::WriteFile(self.path.clone(), e))
+    }
+}
+
+/// Indexes the records of all reports in OpenSearch or Elasticsearch.
+pub struct OpenSearchSink {
+    pub url: Url,
+    pub index: String,
+}
+
+impl ReportSink<Error> for OpenSearchSink {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), Error> {
+        opensearch::export(&self.url, &self.index, feedbacks)
+    }
+}
+
+/// Browses all reports in an interactive terminal UI.
+pub struct TuiSink;
+
+impl ReportSink<Error> for TuiSink {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), Error> {
+        tui::run(feedbacks)
+    }
+}
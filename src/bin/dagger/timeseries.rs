@@ -0,0 +1,343 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+use dagger::dmarc::{DmarcResult, Record};
+use dagger::domain::normalize;
+use dagger::Feedback;
+use serde_json::{json, Value};
+
+use crate::error::Error;
+use crate::http::{self, Url};
+
+/// The InfluxDB measurement records are written to.
+pub const MEASUREMENT: &str = "dmarc";
+
+/// The number of points or log records sent in a single request.
+pub const BATCH_SIZE: usize = 5000;
+
+/// The tags describing the messages of a record, by name.
+fn tags(feedback: &Feedback, record: &Record) -> BTreeMap<&'static str, String> {
+    let identifiers = &record.identifiers;
+    let evaluated = &record.row.policy_evaluated;
+    let mut tags = BTreeMap::from([
+        ("reporter", feedback.report_metadata.org_name.clone()),
+        (
+            "policy_domain",
+            normalize(&feedback.policy_published.domain),
+        ),
+        ("header_from", normalize(&identifiers.header_from)),
+        ("source_ip", record.row.source_ip.to_string()),
+        ("disposition", evaluated.disposition.as_str().to_string()),
+        ("dkim", evaluated.dkim.as_str().to_string()),
+        ("spf", evaluated.spf.as_str().to_string()),
+    ]);
+    if let Some(envelope_from) = &identifiers.envelope_from {
+        tags.insert("envelope_from", normalize(envelope_from));
+    }
+    if let Some(envelope_to) = &identifiers.envelope_to {
+        tags.insert("envelope_to", normalize(envelope_to));
+    }
+    // Empty tag values are not allowed by InfluxDB.
+    tags.retain(|_, value| !value.is_empty());
+    tags
+}
+
+/// The number of messages with the same tags in the period of a report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    /// The beginning of the report period, used as timestamp.
+    pub begin: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub tags: BTreeMap<&'static str, String>,
+    pub messages: u64,
+}
+
+/// Converts records to points, summing the messages of records with the same tags and period.
+pub fn points(feedbacks: &[Feedback]) -> Vec<Point> {
+    let mut points: BTreeMap<_, Point> = BTreeMap::new();
+    for feedback in feedbacks {
+        let range = &feedback.report_metadata.date_range;
+        for record in &feedback.records {
+            let tags = tags(feedback, record);
+            let point = points
+                .entry((range.begin, range.end, tags.clone()))
+                .or_insert_with(|| Point {
+                    begin: range.begin,
+                    end: range.end,
+                    tags,
+                    messages: 0,
+                });
+            point.messages += u64::from(record.row.count);
+        }
+    }
+    points.into_values().collect()
+}
+
+/// Escapes commas, equal signs and spaces in tag keys and values of the line protocol.
+///
+/// Line breaks cannot be escaped, so they are replaced by spaces.
+fn escape_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        let c = match c {
+            '\n' | '\r' => ' ',
+            c => c,
+        };
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn nanoseconds(time: DateTime<Utc>) -> i64 {
+    time.timestamp_nanos_opt().unwrap_or_default()
+}
+
+/// Renders the record counts of reports in the InfluxDB line protocol with nanosecond timestamps.
+pub fn line_protocol(feedbacks: &[Feedback]) -> String {
+    point_lines(&points(feedbacks))
+}
+
+fn point_lines(points: &[Point]) -> String {
+    let mut lines = String::new();
+    for point in points {
+        lines.push_str(MEASUREMENT);
+        for (key, value) in &point.tags {
+            write!(lines, ",{key}={}", escape_tag(value)).unwrap();
+        }
+        writeln!(
+            lines,
+            " messages={}i {}",
+            point.messages,
+            nanoseconds(point.begin)
+        )
+        .unwrap();
+    }
+    lines
+}
+
+/// Writes the record counts of reports to an InfluxDB write endpoint.
+///
+/// The URL is the complete write endpoint, such as
+/// `http://localhost:8086/api/v2/write?org=example&bucket=dmarc` for InfluxDB 2 or
+/// `http://localhost:8086/write?db=dmarc` for InfluxDB 1. The token is sent as required by
+/// InfluxDB 2. Points are sent in batches of [`BATCH_SIZE`].
+pub fn write_influx(url: &Url, token: Option<&str>, feedbacks: &[Feedback]) -> Result<(), Error> {
+    let authorization = token.map(|token| format!("Token {token}"));
+    let mut headers = vec![("Content-Type", "text/plain; charset=utf-8")];
+    if let Some(authorization) = &authorization {
+        headers.push(("Authorization", authorization));
+    }
+    for batch in points(feedbacks).chunks(BATCH_SIZE) {
+        http::post(url, &headers, point_lines(batch).as_bytes())?;
+    }
+    Ok(())
+}
+
+fn attributes<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Value {
+    pairs
+        .into_iter()
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect()
+}
+
+fn resource() -> Value {
+    json!({ "attributes": attributes([("service.name", env!("CARGO_PKG_NAME"))]) })
+}
+
+fn scope() -> Value {
+    json!({ "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") })
+}
+
+/// Encodes record counts as an OTLP metrics request in the JSON encoding.
+///
+/// Messages are reported as the delta sum `dmarc.messages` starting at the beginning of the report
+/// period.
+pub fn otlp_metrics(points: &[Point]) -> Value {
+    let data_points: Vec<Value> = points
+        .iter()
+        .map(|point| {
+            let tags = point.tags.iter().map(|(key, value)| (*key, value.as_str()));
+            json!({
+                "attributes": attributes(tags),
+                "startTimeUnixNano": nanoseconds(point.begin).to_string(),
+                "timeUnixNano": nanoseconds(point.end).to_string(),
+                "asInt": point.messages.to_string(),
+            })
+        })
+        .collect();
+    json!({
+        "resourceMetrics": [{
+            "resource": resource(),
+            "scopeMetrics": [{
+                "scope": scope(),
+                "metrics": [{
+                    "name": "dmarc.messages",
+                    "description": "Messages reported in DMARC aggregate reports",
+                    "unit": "{message}",
+                    "sum": {
+                        // AGGREGATION_TEMPORALITY_DELTA
+                        "aggregationTemporality": 1,
+                        "isMonotonic": true,
+                        "dataPoints": data_points,
+                    },
+                }],
+            }],
+        }],
+    })
+}
+
+/// Encodes each record of the reports as an OTLP log record in the JSON encoding.
+///
+/// Records failing DMARC are logged as warnings.
+pub fn log_records(feedbacks: &[Feedback]) -> Vec<Value> {
+    let mut log_records = vec![];
+    for feedback in feedbacks {
+        let metadata = &feedback.report_metadata;
+        for record in &feedback.records {
+            let evaluated = &record.row.policy_evaluated;
+            let failed = evaluated.dkim == DmarcResult::Fail && evaluated.spf == DmarcResult::Fail;
+            let (severity, severity_number) = if failed { ("WARN", 13) } else { ("INFO", 9) };
+            let tags = tags(feedback, record);
+            let mut attributes = attributes(
+                tags.iter()
+                    .map(|(key, value)| (*key, value.as_str()))
+                    .chain([("report_id", metadata.report_id.as_str())]),
+            );
+            attributes.as_array_mut().unwrap().push(json!({
+                "key": "messages",
+                "value": { "intValue": record.row.count.to_string() },
+            }));
+            log_records.push(json!({
+                "timeUnixNano": nanoseconds(metadata.date_range.begin).to_string(),
+                "severityNumber": severity_number,
+                "severityText": severity,
+                "body": {
+                    "stringValue": format!(
+                        "{} messages from {} with From {}: disposition={} dkim={} spf={}",
+                        record.row.count,
+                        record.row.source_ip,
+                        record.identifiers.header_from,
+                        evaluated.disposition.as_str(),
+                        evaluated.dkim.as_str(),
+                        evaluated.spf.as_str(),
+                    ),
+                },
+                "attributes": attributes,
+            }));
+        }
+    }
+    log_records
+}
+
+/// Encodes log records as an OTLP logs request in the JSON encoding.
+pub fn otlp_logs(log_records: &[Value]) -> Value {
+    json!({
+        "resourceLogs": [{
+            "resource": resource(),
+            "scopeLogs": [{ "scope": scope(), "logRecords": log_records }],
+        }],
+    })
+}
+
+/// Sends the reports as OTLP metrics and logs to the `/v1/metrics` and `/v1/logs` paths of an
+/// OTLP/HTTP endpoint, such as `http://localhost:4318`.
+///
+/// Data points and log records are sent in batches of [`BATCH_SIZE`].
+pub fn export_otlp(endpoint: &Url, feedbacks: &[Feedback]) -> Result<(), Error> {
+    let headers = [("Content-Type", "application/json")];
+    let metrics = endpoint.join("/v1/metrics");
+    for batch in points(feedbacks).chunks(BATCH_SIZE) {
+        let request = otlp_metrics(batch).to_string();
+        http::post(&metrics, &headers, request.as_bytes())?;
+    }
+    let logs = endpoint.join("/v1/logs");
+    for batch in log_records(feedbacks).chunks(BATCH_SIZE) {
+        let request = otlp_logs(batch).to_string();
+        http::post(&logs, &headers, request.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use dagger::dmarc::DmarcResult;
+    use dagger::Feedback;
+
+    use crate::fixtures;
+
+    use super::{escape_tag, line_protocol, log_records, otlp_logs, otlp_metrics, points};
+
+    fn feedback() -> Feedback {
+        let mut feedback = fixtures::report();
+        feedback.report_metadata.org_name = "Example Org, Inc.".into();
+        feedback.records.truncate(1);
+        let record = &mut feedback.records[0];
+        record.row.count = 2;
+        record.identifiers.header_from = "example.com".into();
+        record.identifiers.envelope_from = None;
+        record.identifiers.envelope_to = None;
+        record.row.policy_evaluated.dkim = DmarcResult::Fail;
+        record.row.policy_evaluated.spf = DmarcResult::Fail;
+        let duplicate = record.clone();
+        feedback.records.push(duplicate);
+        feedback
+    }
+
+    #[test]
+    fn render_line_protocol() {
+        let feedback = feedback();
+        let record = &feedback.records[0];
+        let begin = feedback
+            .report_metadata
+            .date_range
+            .begin
+            .timestamp_nanos_opt()
+            .unwrap();
+        let expected = format!(
+            "dmarc,disposition={},dkim=fail,header_from=example.com,policy_domain={},\
+             reporter=Example\\ Org\\,\\ Inc.,source_ip={},spf=fail messages=4i {begin}\n",
+            record.row.policy_evaluated.disposition.as_str(),
+            feedback.policy_published.domain,
+            record.row.source_ip,
+        );
+        assert_eq!(line_protocol(&[feedback]), expected);
+    }
+
+    #[test]
+    fn escape_tag_values() {
+        assert_eq!(escape_tag("a,b=c d\\e"), "a\\,b\\=c\\ d\\\\e");
+        assert_eq!(escape_tag("Example\r\nOrg"), "Example\\ \\ Org");
+    }
+
+    #[test]
+    fn encode_otlp() {
+        let feedback = feedback();
+        let begin = feedback.report_metadata.date_range.begin;
+
+        let metrics = otlp_metrics(&points(std::slice::from_ref(&feedback)));
+        let metric = &metrics["resourceMetrics"][0]["scopeMetrics"][0]["metrics"][0];
+        assert_eq!(metric["name"], "dmarc.messages");
+        let point = &metric["sum"]["dataPoints"][0];
+        assert_eq!(point["asInt"], "4");
+        assert_eq!(
+            point["startTimeUnixNano"],
+            begin.timestamp_nanos_opt().unwrap().to_string()
+        );
+
+        let logs = otlp_logs(&log_records(&[feedback]));
+        let records = logs["resourceLogs"][0]["scopeLogs"][0]["logRecords"]
+            .as_array()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["severityText"], "WARN");
+        assert!(records[0]["attributes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|attribute| attribute["key"] == "report_id"));
+    }
+}
@@ -0,0 +1,727 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+use dagger::dmarc::{DmarcResult, Record};
+use dagger::Feedback;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::error::Error;
+
+/// The property records are grouped by in the groups view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    FromDomain,
+    SourceIp,
+    Reporter,
+    Disposition,
+}
+
+impl GroupBy {
+    fn name(self) -> &'static str {
+        match self {
+            GroupBy::FromDomain => "From domain",
+            GroupBy::SourceIp => "IP address",
+            GroupBy::Reporter => "Reporter",
+            GroupBy::Disposition => "Disposition",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            GroupBy::FromDomain => GroupBy::SourceIp,
+            GroupBy::SourceIp => GroupBy::Reporter,
+            GroupBy::Reporter => GroupBy::Disposition,
+            GroupBy::Disposition => GroupBy::FromDomain,
+        }
+    }
+
+    fn key(self, feedback: &Feedback, record: &Record) -> String {
+        match self {
+            GroupBy::FromDomain => record.identifiers.header_from.to_lowercase(),
+            GroupBy::SourceIp => record.row.source_ip.to_string(),
+            GroupBy::Reporter => feedback.report_metadata.org_name.clone(),
+            GroupBy::Disposition => record.row.policy_evaluated.disposition.as_str().into(),
+        }
+    }
+}
+
+/// A record, identified by the index of its report and its index within the report.
+type RecordId = (usize, usize);
+
+/// What a table shows.
+#[derive(Debug, Clone, PartialEq)]
+enum View {
+    Reports,
+    Groups(GroupBy),
+    Records {
+        title: String,
+        records: Vec<RecordId>,
+    },
+}
+
+/// What selecting a row drills down into.
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Report(usize),
+    Group(String, Vec<RecordId>),
+    Record(RecordId),
+}
+
+#[derive(Debug, Clone)]
+struct TableRow {
+    cells: Vec<String>,
+    target: Target,
+}
+
+/// A view with its filter, sort order and selection.
+#[derive(Debug, Clone)]
+struct Screen {
+    view: View,
+    filter: String,
+    /// The sorted column and whether the order is descending.
+    sort: Option<(usize, bool)>,
+    selected: usize,
+    /// The first row shown, scrolled to keep the selection visible.
+    offset: usize,
+    /// The filtered and sorted rows, until the view, filter or sort order changes.
+    rows: Option<Vec<TableRow>>,
+}
+
+impl Screen {
+    fn new(view: View) -> Self {
+        Self {
+            view,
+            filter: String::new(),
+            sort: None,
+            selected: 0,
+            offset: 0,
+            rows: None,
+        }
+    }
+
+    /// Drops the cached rows, after the view, filter or sort order changed.
+    fn invalidate(&mut self) {
+        self.rows = None;
+    }
+}
+
+/// Compares cells numerically if both are numbers.
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    let number = |cell: &str| cell.trim_end_matches('%').parse::<f64>().ok();
+    match (number(a), number(b)) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+fn is_pass(record: &Record) -> bool {
+    let evaluated = &record.row.policy_evaluated;
+    evaluated.dkim == DmarcResult::Pass || evaluated.spf == DmarcResult::Pass
+}
+
+/// The state of the interactive report browser.
+pub struct App<'a> {
+    feedbacks: &'a [Feedback],
+    /// The views drilled into, with the current one last.
+    stack: Vec<Screen>,
+    /// Whether keys are typed into the filter.
+    editing: bool,
+    /// The number of rows visible at once, for paging.
+    page: usize,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    pub fn new(feedbacks: &'a [Feedback]) -> Self {
+        Self {
+            feedbacks,
+            stack: vec![Screen::new(View::Reports)],
+            editing: false,
+            page: 10,
+            quit: false,
+        }
+    }
+
+    fn screen(&self) -> &Screen {
+        self.stack.last().expect("there is always a screen")
+    }
+
+    fn screen_mut(&mut self) -> &mut Screen {
+        self.stack.last_mut().expect("there is always a screen")
+    }
+
+    fn record(&self, (report, record): RecordId) -> (&'a Feedback, &'a Record) {
+        let feedback = &self.feedbacks[report];
+        (feedback, &feedback.records[record])
+    }
+
+    fn all_records(&self) -> Vec<RecordId> {
+        self.feedbacks
+            .iter()
+            .enumerate()
+            .flat_map(|(i, feedback)| (0..feedback.records.len()).map(move |j| (i, j)))
+            .collect()
+    }
+
+    fn header(&self) -> Vec<&'static str> {
+        match &self.screen().view {
+            View::Reports => vec![
+                "Reporter",
+                "Report ID",
+                "Domain",
+                "Begin",
+                "End",
+                "Records",
+                "Messages",
+            ],
+            View::Groups(group_by) => vec![
+                group_by.name(),
+                "Records",
+                "Messages",
+                "DMARC pass",
+                "DMARC fail",
+                "Pass rate",
+            ],
+            View::Records { .. } => vec![
+                "Reporter",
+                "From domain",
+                "IP address",
+                "Count",
+                "Disposition",
+                "DKIM",
+                "SPF",
+            ],
+        }
+    }
+
+    fn unfiltered_rows(&self) -> Vec<TableRow> {
+        match &self.screen().view {
+            View::Reports => self
+                .feedbacks
+                .iter()
+                .enumerate()
+                .map(|(i, feedback)| {
+                    let metadata = &feedback.report_metadata;
+                    let messages: u64 = feedback
+                        .records
+                        .iter()
+                        .map(|record| u64::from(record.row.count))
+                        .sum();
+                    TableRow {
+                        cells: vec![
+                            metadata.org_name.clone(),
+                            metadata.report_id.clone(),
+                            feedback.policy_published.domain.clone(),
+                            metadata.date_range.begin.to_string(),
+                            metadata.date_range.end.to_string(),
+                            feedback.records.len().to_string(),
+                            messages.to_string(),
+                        ],
+                        target: Target::Report(i),
+                    }
+                })
+                .collect(),
+            View::Groups(group_by) => {
+                let mut groups: BTreeMap<String, Vec<RecordId>> = BTreeMap::new();
+                for id in self.all_records() {
+                    let (feedback, record) = self.record(id);
+                    groups
+                        .entry(group_by.key(feedback, record))
+                        .or_default()
+                        .push(id);
+                }
+                groups
+                    .into_iter()
+                    .map(|(key, ids)| {
+                        let (mut passed, mut failed) = (0, 0);
+                        for id in &ids {
+                            let (_, record) = self.record(*id);
+                            let count = u64::from(record.row.count);
+                            if is_pass(record) {
+                                passed += count;
+                            } else {
+                                failed += count;
+                            }
+                        }
+                        let rate = match passed + failed {
+                            0 => 0.0,
+                            total => passed as f64 / total as f64 * 100.0,
+                        };
+                        TableRow {
+                            cells: vec![
+                                key.clone(),
+                                ids.len().to_string(),
+                                (passed + failed).to_string(),
+                                passed.to_string(),
+                                failed.to_string(),
+                                format!("{rate:.1}%"),
+                            ],
+                            target: Target::Group(key, ids),
+                        }
+                    })
+                    .collect()
+            }
+            View::Records { records, .. } => records
+                .iter()
+                .map(|id| {
+                    let (feedback, record) = self.record(*id);
+                    let evaluated = &record.row.policy_evaluated;
+                    TableRow {
+                        cells: vec![
+                            feedback.report_metadata.org_name.clone(),
+                            record.identifiers.header_from.clone(),
+                            record.row.source_ip.to_string(),
+                            record.row.count.to_string(),
+                            evaluated.disposition.as_str().into(),
+                            evaluated.dkim.as_str().into(),
+                            evaluated.spf.as_str().into(),
+                        ],
+                        target: Target::Record(*id),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// The rows of the current view, filtered and sorted, computed once until they change.
+    fn rows(&mut self) -> &[TableRow] {
+        if self.screen().rows.is_none() {
+            let rows = self.filtered_rows();
+            self.screen_mut().rows = Some(rows);
+        }
+        self.screen().rows.as_deref().unwrap_or_default()
+    }
+
+    fn filtered_rows(&self) -> Vec<TableRow> {
+        let screen = self.screen();
+        let filter = screen.filter.to_lowercase();
+        let mut rows: Vec<TableRow> = self
+            .unfiltered_rows()
+            .into_iter()
+            .filter(|row| {
+                filter.is_empty()
+                    || row
+                        .cells
+                        .iter()
+                        .any(|cell| cell.to_lowercase().contains(&filter))
+            })
+            .collect();
+        if let Some((column, descending)) = screen.sort {
+            rows.sort_by(|a, b| {
+                let ordering = compare_cells(&a.cells[column], &b.cells[column]);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+        rows
+    }
+
+    fn title(&self) -> String {
+        let title = match &self.screen().view {
+            View::Reports => "Reports".into(),
+            View::Groups(group_by) => format!("Records by {}", group_by.name().to_lowercase()),
+            View::Records { title, .. } => title.clone(),
+        };
+        format!("{}{title}", "> ".repeat(self.stack.len() - 1))
+    }
+
+    fn detail(&self, target: &Target) -> Vec<Line<'static>> {
+        let report_lines = |feedback: &Feedback| {
+            let metadata = &feedback.report_metadata;
+            let policy = &feedback.policy_published;
+            vec![
+                Line::from(format!(
+                    "Report {} from {} <{}>",
+                    metadata.report_id, metadata.org_name, metadata.email
+                )),
+                Line::from(format!(
+                    "Coverage: {} to {}",
+                    metadata.date_range.begin, metadata.date_range.end
+                )),
+                Line::from(format!(
+                    "Policy: {} p={} sp={} pct={}",
+                    policy.domain,
+                    policy.p.as_str(),
+                    policy.sp.as_str(),
+                    policy.pct
+                )),
+            ]
+        };
+        match target {
+            Target::Report(i) => report_lines(&self.feedbacks[*i]),
+            Target::Group(key, ids) => vec![Line::from(format!(
+                "{key}: {} records from {} reports",
+                ids.len(),
+                ids.iter()
+                    .map(|(report, _)| report)
+                    .collect::<BTreeSet<_>>()
+                    .len()
+            ))],
+            Target::Record(id) => {
+                let (feedback, record) = self.record(*id);
+                let identifiers = &record.identifiers;
+                let evaluated = &record.row.policy_evaluated;
+                let mut lines = vec![
+                    Line::from(format!(
+                        "{} messages from {} with From {}, envelope {} -> {}",
+                        record.row.count,
+                        record.row.source_ip,
+                        identifiers.header_from,
+                        identifiers.envelope_from.as_deref().unwrap_or("?"),
+                        identifiers.envelope_to.as_deref().unwrap_or("?"),
+                    )),
+                    Line::from(format!(
+                        "Disposition: {}, DKIM: {}, SPF: {}",
+                        evaluated.disposition.as_str(),
+                        evaluated.dkim.as_str(),
+                        evaluated.spf.as_str()
+                    )),
+                ];
+                for reason in &evaluated.reasons {
+                    lines.push(Line::from(format!(
+                        "Override: {} {}",
+                        reason.typ.as_str(),
+                        reason.comment.as_deref().unwrap_or_default()
+                    )));
+                }
+                for dkim in &record.auth_results.dkim {
+                    lines.push(Line::from(format!(
+                        "DKIM {} (selector {}): {}",
+                        dkim.domain,
+                        dkim.selector.as_deref().unwrap_or("?"),
+                        dkim.result.as_str()
+                    )));
+                }
+                for spf in &record.auth_results.spf {
+                    lines.push(Line::from(format!(
+                        "SPF {}: {}",
+                        spf.domain,
+                        spf.result.as_str()
+                    )));
+                }
+                lines.extend(report_lines(feedback));
+                lines
+            }
+        }
+    }
+
+    fn drill_down(&mut self) {
+        let selected = self.screen().selected;
+        let Some(target) = self.rows().get(selected).map(|row| row.target.clone()) else {
+            return;
+        };
+        let view = match &target {
+            Target::Report(i) => {
+                let metadata = &self.feedbacks[*i].report_metadata;
+                View::Records {
+                    title: format!("Report {} from {}", metadata.report_id, metadata.org_name),
+                    records: (0..self.feedbacks[*i].records.len())
+                        .map(|j| (*i, j))
+                        .collect(),
+                }
+            }
+            Target::Group(key, ids) => View::Records {
+                title: key.clone(),
+                records: ids.clone(),
+            },
+            Target::Record((report, _)) => {
+                // Show the whole report of the record, unless it is already shown.
+                let mut screen = Screen::new(View::Reports);
+                screen.filter = self.feedbacks[*report].report_metadata.report_id.clone();
+                if self
+                    .stack
+                    .iter()
+                    .any(|s| s.view == screen.view && s.filter == screen.filter)
+                {
+                    return;
+                }
+                self.stack.push(screen);
+                return;
+            }
+        };
+        self.stack.push(Screen::new(view));
+    }
+
+    fn select(&mut self, selected: usize) {
+        let rows = self.rows().len();
+        self.screen_mut().selected = selected.min(rows.saturating_sub(1));
+    }
+
+    /// Handles a key press, returning whether the browser should quit.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if self.editing {
+            match key.code {
+                KeyCode::Enter => self.editing = false,
+                KeyCode::Esc => {
+                    self.editing = false;
+                    self.screen_mut().filter.clear();
+                }
+                KeyCode::Backspace => {
+                    self.screen_mut().filter.pop();
+                }
+                KeyCode::Char(c) => self.screen_mut().filter.push(c),
+                _ => {}
+            }
+            self.screen_mut().invalidate();
+            self.select(0);
+            return self.quit;
+        }
+        let selected = self.screen().selected;
+        let columns = self.header().len();
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') => self.select(selected + 1),
+            KeyCode::Up | KeyCode::Char('k') => self.select(selected.saturating_sub(1)),
+            KeyCode::PageDown => self.select(selected + self.page),
+            KeyCode::PageUp => self.select(selected.saturating_sub(self.page)),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(usize::MAX),
+            KeyCode::Enter => self.drill_down(),
+            KeyCode::Esc | KeyCode::Backspace => {
+                if self.stack.len() > 1 {
+                    self.stack.pop();
+                } else {
+                    let screen = self.screen_mut();
+                    screen.filter.clear();
+                    screen.invalidate();
+                }
+            }
+            KeyCode::Char('/') => self.editing = true,
+            KeyCode::Char('s') => {
+                let screen = self.screen_mut();
+                screen.sort = match screen.sort {
+                    None => Some((0, false)),
+                    Some((column, _)) if column + 1 < columns => Some((column + 1, false)),
+                    Some(_) => None,
+                };
+                screen.invalidate();
+            }
+            KeyCode::Char('r') => {
+                let screen = self.screen_mut();
+                if let Some((_, descending)) = &mut screen.sort {
+                    *descending = !*descending;
+                    screen.invalidate();
+                }
+            }
+            KeyCode::Char('b') => {
+                let screen = self.screen_mut();
+                if let View::Groups(group_by) = &mut screen.view {
+                    *group_by = group_by.next();
+                    screen.sort = None;
+                    screen.invalidate();
+                    self.select(0);
+                }
+            }
+            KeyCode::Char('1') => self.stack = vec![Screen::new(View::Reports)],
+            KeyCode::Char('2') => self.stack = vec![Screen::new(View::Groups(GroupBy::FromDomain))],
+            KeyCode::Char('3') => {
+                self.stack = vec![Screen::new(View::Records {
+                    title: "All records".into(),
+                    records: self.all_records(),
+                })]
+            }
+            _ => {}
+        }
+        self.quit
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame) {
+        let [table_area, detail_area, help_area] = Layout::vertical([
+            Constraint::Min(5),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        // The borders and header take three lines.
+        let height = usize::from(table_area.height.saturating_sub(3)).max(1);
+        self.rows();
+        let screen = self.screen_mut();
+        if screen.selected < screen.offset {
+            screen.offset = screen.selected;
+        } else if screen.selected >= screen.offset + height {
+            screen.offset = screen.selected + 1 - height;
+        }
+        let screen = self.screen();
+        let rows = screen.rows.as_deref().unwrap_or_default();
+        // Only the visible rows are rendered and measured.
+        let visible =
+            &rows[screen.offset.min(rows.len())..(screen.offset + height).min(rows.len())];
+        let header: Vec<String> = self
+            .header()
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| match screen.sort {
+                Some((column, false)) if column == i => format!("{name} ▲"),
+                Some((column, true)) if column == i => format!("{name} ▼"),
+                _ => name.to_string(),
+            })
+            .collect();
+        let mut widths: Vec<usize> = header.iter().map(|name| name.chars().count()).collect();
+        for row in visible {
+            for (width, cell) in widths.iter_mut().zip(&row.cells) {
+                *width = (*width).max(cell.chars().count()).min(40);
+            }
+        }
+        let title = match (&screen.filter, self.editing) {
+            (filter, true) => format!(" {} [/{filter}_] ", self.title()),
+            (filter, false) if !filter.is_empty() => format!(" {} [/{filter}] ", self.title()),
+            _ => format!(" {} ", self.title()),
+        };
+        let detail = rows
+            .get(screen.selected)
+            .map(|row| self.detail(&row.target))
+            .unwrap_or_default();
+        let table = Table::new(
+            visible.iter().map(|row| Row::new(row.cells.clone())),
+            widths.iter().map(|width| Constraint::Length(*width as u16)),
+        )
+        .header(Row::new(header).bold())
+        .block(Block::bordered().title(title))
+        .row_highlight_style(Style::new().reversed());
+        let mut state = TableState::default()
+            .with_selected(Some(screen.selected.saturating_sub(screen.offset)));
+        frame.render_stateful_widget(table, table_area, &mut state);
+        frame.render_widget(
+            Paragraph::new(detail)
+                .block(Block::bordered().title(" Details "))
+                .wrap(Wrap { trim: false }),
+            detail_area,
+        );
+        frame.render_widget(
+            Paragraph::new(
+                "q quit  ↑↓ move  enter drill down  esc back  / filter  s sort  r reverse  \
+                 b group by  1 reports  2 groups  3 records",
+            )
+            .dim(),
+            help_area,
+        );
+        self.page = height;
+    }
+}
+
+fn run_app(terminal: &mut DefaultTerminal, feedbacks: &[Feedback]) -> io::Result<()> {
+    let mut app = App::new(feedbacks);
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && app.handle_key(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Browses reports interactively in the terminal until the user quits.
+pub fn run(feedbacks: &[Feedback]) -> Result<(), Error> {
+    let mut terminal = ratatui::init();
+    let result = run_app(&mut terminal, feedbacks);
+    ratatui::restore();
+    result.map_err(Error::WriteOutput)
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use ratatui::Terminal;
+
+    use crate::fixtures;
+
+    use super::{App, View};
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+            app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn browse_reports() {
+        let feedback = fixtures::report();
+        let mut other = feedback.clone();
+        other.report_metadata.org_name = "other.example".into();
+        other.records.truncate(1);
+        let feedbacks = [feedback, other];
+        let mut app = App::new(&feedbacks);
+        assert_eq!(app.rows().len(), 2);
+
+        // Filter reports live and drill down into the remaining one.
+        press(&mut app, "/other\n");
+        assert_eq!(app.rows().len(), 1);
+        press(&mut app, "\n");
+        assert!(matches!(app.screen().view, View::Records { .. }));
+        assert_eq!(app.rows().len(), 1);
+        press(&mut app, "\x1b");
+        assert_eq!(app.screen().filter, "other");
+
+        // Sort groups by the number of records, descending.
+        press(&mut app, "2bbss");
+        let rows = app.rows();
+        assert!(rows.windows(2).all(|w| w[0].cells[1] <= w[1].cells[1]));
+        press(&mut app, "r");
+        let rows = app.rows();
+        assert!(rows.windows(2).all(|w| w[0].cells[1] >= w[1].cells[1]));
+        press(&mut app, "\n");
+        assert_eq!(app.stack.len(), 2);
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let text: String = buffer.content.iter().map(|cell| cell.symbol()).collect();
+        assert!(text.contains("Details"));
+        press(&mut app, "q");
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn render_visible_rows() {
+        let mut feedback = fixtures::report();
+        let record = feedback.records[0].clone();
+        feedback.records = (0..1000u32)
+            .map(|i| {
+                let mut record = record.clone();
+                record.row.source_ip = std::net::Ipv4Addr::from(0x0a00_0000 + i).into();
+                record
+            })
+            .collect();
+        let feedbacks = [feedback];
+        let mut app = App::new(&feedbacks);
+        press(&mut app, "3G");
+        assert_eq!(app.screen().selected, 999);
+        assert!(app.screen().rows.is_some());
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let text: String = terminal
+            .backend()
+            .buffer()
+            .content
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(text.contains("10.0.3.231"));
+        assert!(!text.contains("10.0.0.0 "));
+        assert_eq!(app.screen().offset, 1000 - app.page);
+
+        // Changing the filter recomputes the rows.
+        press(&mut app, "/10.0.0.1\n");
+        assert_eq!(app.rows().len(), 111);
+        press(&mut app, "s");
+        assert!(app.screen().rows.is_none());
+    }
+}
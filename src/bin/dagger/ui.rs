@@ -0,0 +1,541 @@
+use std::fmt::{self, Display};
+
+use dagger::arc::SealerSummary;
+use dagger::audit::{Compliance, ReporterAudit};
+use dagger::diagnostics::Failure;
+use dagger::dkim::{KeyStatus, Selector};
+use dagger::dmarc::{DmarcResult, Feedback, Record};
+use dagger::dns::Published;
+use dagger::lint::{Drift, Finding};
+use dagger::spf::{Evaluation, Explanation, SpfTree, LOOKUP_LIMIT, VOID_LOOKUP_LIMIT};
+use dagger::subdomain::Subdomain;
+use dagger::traffic::{Breakdown, Classifier, Traffic};
+use tabled::{
+    builder::Builder,
+    settings::{Color, Modify, Style},
+    Table,
+};
+
+/// The metadata, the published policy and the records of a report, as printed by the list output.
+pub struct ReportDetails<'a>(pub &'a Feedback);
+
+impl Display for ReportDetails<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, " DMARC Report Details")?;
+        writeln!(f, "----------------------")?;
+        if let Some(version) = &self.0.version {
+            writeln!(f, "Version: {}", version)?;
+        }
+        writeln!(f, "Provider: {}", self.0.report_metadata.org_name)?;
+        writeln!(f, "Coverage: {}", self.0.report_metadata.date_range)?;
+        writeln!(f, "Report ID: {}", self.0.report_metadata.report_id)?;
+        writeln!(f, "Email contact: {}", self.0.report_metadata.email)?;
+        if let Some(info) = &self.0.report_metadata.extra_contact_info {
+            writeln!(f, "Extra contact: {}", info)?;
+        }
+        writeln!(f, "Errors: {:?}", self.0.report_metadata.errors)?;
+        writeln!(f)?;
+
+        writeln!(f, " Policy Details")?;
+        writeln!(f, "----------------")?;
+        writeln!(f, "Policy: {:?}", self.0.policy_published.p)?;
+        writeln!(f, "Sub-domain policy: {:?}", self.0.policy_published.sp)?;
+        if let Some(adkim) = &self.0.policy_published.adkim {
+            writeln!(f, "DKIM alignment: {:?}", adkim)?;
+        }
+        if let Some(aspf) = &self.0.policy_published.aspf {
+            writeln!(f, "SPF alignment: {:?}", aspf)?;
+        }
+        writeln!(f, "Percentage: {}", self.0.policy_published.pct)?;
+        if !&self.0.policy_published.fo.is_empty() {
+            writeln!(f, "Failure options: {:?}", self.0.policy_published.fo)?;
+        }
+        writeln!(f)?;
+
+        let records: Vec<&Record> = self.0.records.iter().collect();
+        let table = build_records_table(&records);
+        writeln!(f, "{}", table)?;
+
+        Ok(())
+    }
+}
+
+fn get_dmarc_color(result: &DmarcResult) -> Color {
+    match result {
+        DmarcResult::Pass => Color::FG_BRIGHT_GREEN,
+        DmarcResult::Fail => Color::FG_BRIGHT_RED,
+    }
+}
+
+/// Builds a table with one row per record.
+pub fn build_records_table(records: &[&Record]) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record([
+        "From domain",
+        "Envelope",
+        "IP address",
+        "Count",
+        "Disposition",
+        "Override Reasons",
+        "DKIM",
+        "SPF",
+        "DKIM Auth Result",
+        "SPF Auth Result",
+    ]);
+
+    for r in records {
+        let header_from = &r.identifiers.header_from;
+        let envelope = format!(
+            "{}->{}",
+            r.identifiers
+                .envelope_from
+                .as_ref()
+                .unwrap_or(&"?".to_string()),
+            r.identifiers
+                .envelope_to
+                .as_ref()
+                .unwrap_or(&"?".to_string())
+        );
+        let source_ip = r.row.source_ip;
+        let count = r.row.count;
+        let disposition = format!("{:?}", r.row.policy_evaluated.disposition);
+        let override_reasons = &r
+            .row
+            .policy_evaluated
+            .reasons
+            .iter()
+            .map(|reason| reason.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let dkim_alignement = format!("{:?}", r.row.policy_evaluated.dkim);
+        let spf_alignement = format!("{:?}", r.row.policy_evaluated.spf);
+        let dkim_auth_results = r
+            .auth_results
+            .dkim
+            .iter()
+            .map(|res| res.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let spf_auth_results = r
+            .auth_results
+            .spf
+            .iter()
+            .map(|res| res.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        builder.push_record([
+            header_from,
+            &envelope,
+            &source_ip.to_string(),
+            &count.to_string(),
+            &disposition,
+            override_reasons,
+            &dkim_alignement,
+            &spf_alignement,
+            &dkim_auth_results,
+            &spf_auth_results,
+        ]);
+    }
+
+    let mut table = builder.build();
+    table.with(Style::psql());
+
+    // Highlight cells
+    for (i, r) in records.iter().enumerate() {
+        let dkim_alignement = get_dmarc_color(&r.row.policy_evaluated.dkim);
+        table.with(Modify::new((i + 1, 6)).with(dkim_alignement));
+        let spf_alignement = get_dmarc_color(&r.row.policy_evaluated.spf);
+        table.with(Modify::new((i + 1, 7)).with(spf_alignement));
+    }
+
+    table
+}
+
+/// Builds a table with one row per email which could not be processed.
+pub fn build_failures_table(failures: &[Failure]) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record(["#", "Origin", "Message-ID", "Subject", "Kind", "Error"]);
+    for failure in failures {
+        builder.push_record([
+            &failure.index.to_string(),
+            failure.origin.as_deref().unwrap_or("?"),
+            failure.message_id.as_deref().unwrap_or("?"),
+            failure.subject.as_deref().unwrap_or("?"),
+            failure.error.kind(),
+            &failure.error.to_string(),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+pub fn build_findings_table(findings: &[Finding]) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record(["Severity", "Tag", "Finding"]);
+    for finding in findings {
+        builder.push_record([
+            &finding.severity.to_string(),
+            finding.tag.as_deref().unwrap_or(""),
+            &finding.message,
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+pub fn build_drift_table(drifts: &[Drift]) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record([
+        "Tag",
+        "Published",
+        "Reported",
+        "Reports",
+        "Reporters",
+        "Last seen",
+    ]);
+    for drift in drifts {
+        builder.push_record([
+            drift.tag,
+            &drift.published,
+            &drift.reported,
+            &drift.reports.to_string(),
+            &drift
+                .reporters
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<&str>>()
+                .join(", "),
+            &drift.last_seen.to_string(),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+pub fn build_published_table(published: &[Published]) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record(["Kind", "Name", "Record"]);
+    for record in published {
+        builder.push_record([
+            record.kind,
+            &record.name,
+            record.record.as_deref().unwrap_or("not published"),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+/// Describes the outcome of an SPF evaluation in a few words.
+fn describe_evaluation(evaluation: &Evaluation) -> (String, String, String) {
+    let matched = match (&evaluation.matched, &evaluation.error) {
+        (Some(matched), _) => matched.directive.to_string(),
+        (None, Some(error)) => error.clone(),
+        (None, None) => "no match".into(),
+    };
+    let chain = evaluation
+        .matched
+        .as_ref()
+        .map(|matched| format!("{} ({})", matched.chain.join(" > "), matched.depth()))
+        .unwrap_or_default();
+    let lookups = format!("{}/{LOOKUP_LIMIT}", evaluation.lookups);
+    (matched, chain, lookups)
+}
+
+pub fn build_spf_table(explanations: &[Explanation]) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record([
+        "IP address",
+        "SPF domain",
+        "Count",
+        "Reported",
+        "Evaluated",
+        "Matched",
+        "Chain (depth)",
+        "Lookups",
+        "Fixes",
+    ]);
+    for explanation in explanations {
+        let (matched, chain, lookups) = describe_evaluation(&explanation.evaluation);
+        let fixes = explanation
+            .fixes
+            .iter()
+            .map(|(include, fix)| format!("include:{include} {fix}"))
+            .collect::<Vec<String>>()
+            .join("\n");
+        builder.push_record([
+            &explanation.source_ip.to_string(),
+            &explanation.domain,
+            &explanation.count.to_string(),
+            explanation.reported.as_str(),
+            explanation.evaluation.result.as_str(),
+            &matched,
+            &chain,
+            &lookups,
+            &fixes,
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+pub fn build_tree_table(tree: &SpfTree) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record(["Term", "Lookups", "Void", "Networks", "Note"]);
+    for (depth, node) in tree.root.walk() {
+        let lookups = match depth {
+            0 => format!("{}/{LOOKUP_LIMIT}", node.total_lookups()),
+            _ => node.total_lookups().to_string(),
+        };
+        let void_lookups = match depth {
+            0 => format!("{}/{VOID_LOOKUP_LIMIT}", node.total_void_lookups()),
+            _ => node.total_void_lookups().to_string(),
+        };
+        let networks = node
+            .networks
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join("\n");
+        builder.push_record([
+            format!("{}{}", "  ".repeat(depth), node.term),
+            lookups,
+            void_lookups,
+            networks,
+            node.note.clone().unwrap_or_default(),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+pub fn build_duplicates_table(tree: &SpfTree) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record(["Network", "Term", "Covered by", "Covering term"]);
+    for duplicate in &tree.duplicates {
+        builder.push_record([
+            duplicate.network.to_string(),
+            format!("{} ({})", duplicate.term, duplicate.domain),
+            duplicate.covered_by.to_string(),
+            format!(
+                "{} ({})",
+                duplicate.covering_term, duplicate.covering_domain
+            ),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+pub fn build_unauthorized_table(tree: &SpfTree) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record(["IP address", "Count"]);
+    for (ip, count) in &tree.unauthorized {
+        builder.push_record([ip.to_string(), count.to_string()]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+pub fn build_selectors_table(selectors: &[Selector]) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record([
+        "Domain",
+        "Selector",
+        "Messages",
+        "Pass rate",
+        "First seen",
+        "Last seen",
+        "Key",
+        "Problems",
+    ]);
+    for selector in selectors {
+        let (messages, pass_rate, first_seen, last_seen) = match &selector.usage {
+            Some(usage) => (
+                usage.messages.to_string(),
+                format!("{:.1}%", usage.pass_rate() * 100.0),
+                usage.first_seen.to_string(),
+                usage.last_seen.to_string(),
+            ),
+            None => Default::default(),
+        };
+        let key = match &selector.key {
+            Some(KeyStatus::Published(key)) if key.revoked => "revoked".into(),
+            Some(KeyStatus::Published(key)) => match key.bits {
+                Some(bits) => format!("{} {bits}", key.key_type),
+                None => key.key_type.clone(),
+            },
+            Some(KeyStatus::Invalid(_)) => "invalid".into(),
+            Some(KeyStatus::Missing) => "missing".into(),
+            Some(KeyStatus::LookupFailed(_)) => "lookup failed".into(),
+            None => String::new(),
+        };
+        builder.push_record([
+            selector.domain.clone(),
+            selector.selector.clone(),
+            messages,
+            pass_rate,
+            first_seen,
+            last_seen,
+            key,
+            selector.problems().join("\n"),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+pub fn build_breakdown_table(breakdowns: &[Breakdown]) -> Table {
+    let classes = [
+        Traffic::Direct,
+        Traffic::Forwarded,
+        Traffic::MailingList,
+        Traffic::LikelySpoof,
+    ];
+    let mut builder = Builder::new();
+    let mut header = vec!["From domain".to_string(), "Messages".to_string()];
+    header.extend(classes.iter().map(|class| {
+        let mut name = class.to_string();
+        name[..1].make_ascii_uppercase();
+        name
+    }));
+    header.push("Pass rate".into());
+    builder.push_record(header);
+    for breakdown in breakdowns {
+        let mut row = vec![breakdown.domain.clone(), breakdown.total().to_string()];
+        row.extend(classes.iter().map(|class| {
+            breakdown
+                .messages
+                .get(class)
+                .copied()
+                .unwrap_or_default()
+                .to_string()
+        }));
+        row.push(format!("{:.1}%", breakdown.pass_rate() * 100.0));
+        builder.push_record(row);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+/// Builds a table with the class of each record.
+pub fn build_classification_table(records: &[&Record], classifier: &Classifier) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record(["From domain", "IP address", "Count", "Traffic", "Reason"]);
+    for record in records {
+        let classification = classifier.classify(record);
+        builder.push_record([
+            record.identifiers.header_from.clone(),
+            record.row.source_ip.to_string(),
+            record.row.count.to_string(),
+            classification.traffic.to_string(),
+            classification.reason.to_string(),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+pub fn build_sealers_table(summaries: &[SealerSummary]) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record([
+        "Sealer",
+        "Trusted",
+        "Messages",
+        "ARC pass",
+        "DMARC fail",
+        "Rescued",
+    ]);
+    for summary in summaries {
+        builder.push_record([
+            summary.sealer.as_deref().unwrap_or("?"),
+            if summary.trusted { "yes" } else { "no" },
+            &summary.messages.to_string(),
+            &summary.passed.to_string(),
+            &summary.dmarc_failed.to_string(),
+            &summary.rescued.to_string(),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
+
+pub fn build_audit_table(audits: &[ReporterAudit]) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record([
+        "Reporter",
+        "Messages",
+        "DMARC fail",
+        "Honored",
+        "Overridden",
+        "Ignored",
+        "Stricter",
+        "Ignored for",
+    ]);
+    for audit in audits {
+        let ignored_domains = audit
+            .ignored_domains
+            .iter()
+            .map(|(domain, count)| format!("{domain} ({count})"))
+            .collect::<Vec<String>>()
+            .join("\n");
+        builder.push_record([
+            audit.reporter.clone(),
+            audit.total().to_string(),
+            audit.failed.to_string(),
+            audit.count(Compliance::Honored).to_string(),
+            audit.count(Compliance::Overridden).to_string(),
+            audit.count(Compliance::Ignored).to_string(),
+            audit.count(Compliance::Stricter).to_string(),
+            ignored_domains,
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+
+    // Highlight reporters not honoring the policies
+    for (i, audit) in audits.iter().enumerate() {
+        if !audit.is_honoring() {
+            table.with(Modify::new((i + 1, 0)).with(Color::FG_BRIGHT_RED));
+        }
+    }
+    table
+}
+
+pub fn build_subdomains_table(subdomains: &[Subdomain]) -> Table {
+    let mut builder = Builder::new();
+    builder.push_record([
+        "Policy domain",
+        "From domain",
+        "Policy",
+        "Messages",
+        "Pass rate",
+        "Last seen",
+    ]);
+    for subdomain in subdomains {
+        let tag = if subdomain.uses_sp { "sp" } else { "p" };
+        builder.push_record([
+            subdomain.policy_domain.clone(),
+            subdomain.header_from.clone(),
+            format!("{tag}={}", subdomain.requested.as_str()),
+            subdomain.messages.to_string(),
+            format!("{:.1}%", subdomain.pass_rate() * 100.0),
+            subdomain.last_seen.to_string(),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(Style::psql());
+    table
+}
@@ -0,0 +1,97 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+use crate::Error;
+
+/// Extracts the value of the encoding declaration from the XML prolog, if any.
+fn declared_encoding(xml: &[u8]) -> Option<&[u8]> {
+    let prolog = xml.strip_prefix(b"<?xml")?;
+    let end = prolog.windows(2).position(|w| w == b"?>")?;
+    let prolog = &prolog[..end];
+    let start = prolog.windows(8).position(|w| w == b"encoding")? + 8;
+    let value = prolog[start..].trim_ascii_start().strip_prefix(b"=")?;
+    let value = value.trim_ascii_start();
+    let quote = *value.first().filter(|q| **q == b'"' || **q == b'\'')?;
+    let value = &value[1..];
+    let end = value.iter().position(|b| *b == quote)?;
+    Some(&value[..end])
+}
+
+/// Detects the encoding of an XML document and the length of its byte order mark.
+///
+/// A byte order mark takes precedence over the encoding declaration of the XML prolog. Documents
+/// without either are assumed to be UTF-8.
+fn detect_encoding(xml: &[u8]) -> (&'static Encoding, usize) {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(xml) {
+        return (encoding, bom_length);
+    }
+    // UTF-16 without byte order mark, recognized by the start of the prolog.
+    if xml.starts_with(b"<\0?\0") {
+        return (UTF_16LE, 0);
+    }
+    if xml.starts_with(b"\0<\0?") {
+        return (UTF_16BE, 0);
+    }
+    let encoding = declared_encoding(xml)
+        .and_then(Encoding::for_label)
+        // The prolog of a document in a 16-bit encoding cannot be read as ASCII.
+        .filter(|encoding| *encoding != UTF_16LE && *encoding != UTF_16BE)
+        .unwrap_or(UTF_8);
+    (encoding, 0)
+}
+
+/// Decodes an XML report according to its byte order mark or encoding declaration.
+pub fn decode_xml(xml: &[u8]) -> Result<(String, &'static Encoding), Error> {
+    let (encoding, bom_length) = detect_encoding(xml);
+    let decoded = encoding
+        .decode_without_bom_handling_and_without_replacement(&xml[bom_length..])
+        .ok_or(Error::DecodeXml(encoding.name()))?;
+    Ok((decoded.into_owned(), encoding))
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+    use super::decode_xml;
+
+    #[test]
+    fn decode_utf8() {
+        let (xml, encoding) = decode_xml("<?xml version=\"1.0\"?><a>ä</a>".as_bytes()).unwrap();
+        assert_eq!(xml, "<?xml version=\"1.0\"?><a>ä</a>");
+        assert_eq!(encoding, UTF_8);
+
+        let (xml, encoding) = decode_xml(b"\xef\xbb\xbf<a/>").unwrap();
+        assert_eq!(xml, "<a/>");
+        assert_eq!(encoding, UTF_8);
+
+        assert!(decode_xml(b"<a>\xe4</a>").is_err());
+    }
+
+    #[test]
+    fn decode_latin1_declaration() {
+        let (xml, encoding) =
+            decode_xml(b"<?xml version='1.0' encoding = 'ISO-8859-1'?><a>\xe4</a>").unwrap();
+        assert_eq!(xml, "<?xml version='1.0' encoding = 'ISO-8859-1'?><a>ä</a>");
+        // ISO-8859-1 is decoded as its superset windows-1252, as mandated by the WHATWG encoding standard.
+        assert_eq!(encoding, WINDOWS_1252);
+    }
+
+    #[test]
+    fn decode_utf16() {
+        let utf16le: Vec<u8> = [0xff, 0xfe]
+            .into_iter()
+            .chain("<a>ä</a>".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let (xml, encoding) = decode_xml(&utf16le).unwrap();
+        assert_eq!(xml, "<a>ä</a>");
+        assert_eq!(encoding, UTF_16LE);
+
+        let utf16be: Vec<u8> = "<?xml version=\"1.0\" encoding=\"UTF-16\"?><a/>"
+            .encode_utf16()
+            .flat_map(u16::to_be_bytes)
+            .collect();
+        let (xml, encoding) = decode_xml(&utf16be).unwrap();
+        assert_eq!(xml, "<?xml version=\"1.0\" encoding=\"UTF-16\"?><a/>");
+        assert_eq!(encoding, UTF_16BE);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::dmarc::Feedback;
+
+/// Two reports sharing the same organization and report ID but differing in content.
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub org_name: String,
+    pub report_id: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Report '{}' from '{}' was received multiple times with differing content",
+            self.report_id, self.org_name
+        )
+    }
+}
+
+/// The identity under which reports are considered duplicates.
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    /// Reports are identified by the reporting organization and their report ID.
+    Id { org_name: String, report_id: String },
+    /// Reports without a usable ID are identified by their content only.
+    Fingerprint(u64),
+}
+
+/// Computes a hash over the content of a report, ignoring its report ID.
+fn fingerprint(feedback: &Feedback) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    feedback.version.map(f32::to_bits).hash(&mut hasher);
+    let metadata = &feedback.report_metadata;
+    metadata.org_name.hash(&mut hasher);
+    metadata.email.hash(&mut hasher);
+    metadata.extra_contact_info.hash(&mut hasher);
+    metadata.date_range.hash(&mut hasher);
+    metadata.errors.hash(&mut hasher);
+    feedback.policy_published.hash(&mut hasher);
+    feedback.records.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Removes duplicate reports while preserving the order of first occurrence.
+///
+/// Reports are keyed on their organization name and report ID. Reports with an empty ID are keyed on a
+/// fingerprint of their content instead. Reports sharing a key but differing in content are all kept,
+/// since some organizations reuse report IDs, and are returned as conflicts.
+pub fn dedup(feedbacks: Vec<Feedback>) -> (Vec<Feedback>, Vec<Conflict>) {
+    let mut seen: HashMap<Key, Vec<u64>> = HashMap::new();
+    let mut unique = vec![];
+    let mut conflicts = vec![];
+    for feedback in feedbacks {
+        let fingerprint = fingerprint(&feedback);
+        let metadata = &feedback.report_metadata;
+        let key = if metadata.report_id.trim().is_empty() {
+            Key::Fingerprint(fingerprint)
+        } else {
+            Key::Id {
+                org_name: metadata.org_name.clone(),
+                report_id: metadata.report_id.clone(),
+            }
+        };
+        let fingerprints = seen.entry(key).or_default();
+        if fingerprints.contains(&fingerprint) {
+            continue;
+        }
+        if !fingerprints.is_empty() {
+            conflicts.push(Conflict {
+                org_name: metadata.org_name.clone(),
+                report_id: metadata.report_id.clone(),
+            });
+        }
+        fingerprints.push(fingerprint);
+        unique.push(feedback);
+    }
+    (unique, conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dmarc::Feedback;
+    use crate::fixtures;
+
+    use super::{dedup, Conflict};
+
+    fn feedback(org_name: &str, report_id: &str, count: u32) -> Feedback {
+        let mut feedback = fixtures::report();
+        feedback.report_metadata.org_name = org_name.into();
+        feedback.report_metadata.report_id = report_id.into();
+        feedback.records.truncate(1);
+        feedback.records[0].row.count = count;
+        feedback
+    }
+
+    #[test]
+    fn dedup_non_adjacent_duplicates() {
+        let feedbacks = vec![
+            feedback("a.org", "1", 1),
+            feedback("a.org", "2", 1),
+            feedback("a.org", "1", 1),
+        ];
+        let (unique, conflicts) = dedup(feedbacks);
+        assert_eq!(
+            unique,
+            vec![feedback("a.org", "1", 1), feedback("a.org", "2", 1)]
+        );
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn dedup_same_id_from_different_orgs() {
+        let feedbacks = vec![feedback("a.org", "1", 1), feedback("b.org", "1", 1)];
+        let (unique, conflicts) = dedup(feedbacks);
+        assert_eq!(unique.len(), 2);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn dedup_conflicting_content() {
+        let feedbacks = vec![
+            feedback("a.org", "1", 1),
+            feedback("a.org", "1", 2),
+            feedback("a.org", "1", 2),
+        ];
+        let (unique, conflicts) = dedup(feedbacks);
+        assert_eq!(
+            unique,
+            vec![feedback("a.org", "1", 1), feedback("a.org", "1", 2)]
+        );
+        assert_eq!(
+            conflicts,
+            vec![Conflict {
+                org_name: "a.org".into(),
+                report_id: "1".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn dedup_empty_id_by_fingerprint() {
+        let feedbacks = vec![
+            feedback("a.org", "", 1),
+            feedback("a.org", "", 2),
+            feedback("a.org", "", 1),
+        ];
+        let (unique, conflicts) = dedup(feedbacks);
+        assert_eq!(
+            unique,
+            vec![feedback("a.org", "", 1), feedback("a.org", "", 2)]
+        );
+        assert!(conflicts.is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use encoding_rs::Encoding;
+use mailparse::{parse_headers, MailHeaderMap};
+
+use crate::Error;
+
+/// The encoding a report was decoded from.
+#[derive(Debug, PartialEq)]
+pub struct ReportEncoding {
+    pub org_name: String,
+    pub report_id: String,
+    pub encoding: &'static Encoding,
+}
+
+/// An email or report which could not be processed.
+#[derive(Debug)]
+pub struct Failure {
+    /// The position of the email in its source, starting at 0.
+    pub index: usize,
+    /// Where the email came from, if known.
+    pub origin: Option<String>,
+    pub message_id: Option<String>,
+    pub subject: Option<String>,
+    pub error: Error,
+}
+
+impl Failure {
+    /// Records a failure, taking the identifying headers from the email if possible.
+    pub fn new(index: usize, origin: Option<String>, email: Option<&[u8]>, error: Error) -> Self {
+        let headers = email.and_then(|email| parse_headers(email).ok().map(|(headers, _)| headers));
+        let header = |name| {
+            headers
+                .as_ref()
+                .and_then(|headers| headers.get_first_value(name))
+        };
+        Self {
+            index,
+            origin,
+            message_id: header("Message-ID"),
+            subject: header("Subject"),
+            error,
+        }
+    }
+}
+
+/// A part of an email or an archive entry which was skipped, while other parts yielded reports.
+#[derive(Debug)]
+pub struct SkippedPart {
+    /// The file name of the part or entry, or its MIME type if it has no name.
+    pub name: String,
+    pub error: Error,
+}
+
+/// Information gathered while extracting reports, for troubleshooting unusual input.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    /// The encodings of all extracted reports.
+    pub encodings: Vec<ReportEncoding>,
+    /// The number of emails and reports read from all sources.
+    pub messages: usize,
+    /// The emails and reports which could not be processed.
+    pub failures: Vec<Failure>,
+    /// The parts of otherwise processed emails and reports which could not be processed.
+    pub skipped_parts: Vec<SkippedPart>,
+    /// Errors writing failed emails and reports to the quarantine directory.
+    pub quarantine_errors: Vec<Error>,
+}
+
+/// Writes raw content which could not be processed to the quarantine directory.
+pub fn quarantine(dir: &Path, file_name: &str, content: &[u8]) -> Result<PathBuf, Error> {
+    let path = dir.join(file_name);
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&path, content))
+        .map_err(|e| Error::WriteQuarantine(path.clone(), e))?;
+    Ok(path)
+}
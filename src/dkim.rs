@@ -0,0 +1,430 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+
+use crate::dmarc::DkimResult;
+use crate::dns::{lookup_dkim, TxtLookup};
+use crate::domain::normalize;
+use crate::{Error, Feedback};
+
+/// Selectors that are commonly used by mail providers, probed to find keys that are not used.
+pub const COMMON_SELECTORS: &[&str] = &[
+    "default",
+    "dkim",
+    "google",
+    "k1",
+    "k2",
+    "mail",
+    "s1",
+    "s2",
+    "selector1",
+    "selector2",
+];
+
+/// RSA keys shorter than this are considered weak, as recommended by RFC 8301.
+pub const MIN_RSA_BITS: usize = 2048;
+
+/// How the signatures of a selector were evaluated by reporters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Usage {
+    /// The number of messages signed with the selector.
+    pub messages: u64,
+    /// The number of messages whose signature passed verification.
+    pub passed: u64,
+    /// The begin of the earliest report with the selector.
+    pub first_seen: DateTime<Utc>,
+    /// The end of the latest report with the selector.
+    pub last_seen: DateTime<Utc>,
+}
+
+impl Usage {
+    /// The share of messages whose signature passed, between 0 and 1.
+    pub fn pass_rate(&self) -> f64 {
+        match self.messages {
+            0 => 0.0,
+            messages => self.passed as f64 / messages as f64,
+        }
+    }
+}
+
+/// A public key published in a DKIM key record, as described in RFC 6376 Section 3.6.1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DkimKey {
+    /// The key type, such as `rsa` or `ed25519`.
+    pub key_type: String,
+    /// The length of the key in bits, if the key could be decoded.
+    pub bits: Option<usize>,
+    /// Whether the key is revoked by an empty `p=` tag.
+    pub revoked: bool,
+    /// Whether the domain is testing DKIM with the `t=y` flag.
+    pub testing: bool,
+}
+
+/// Reads a DER element, returning its tag, content and the position after it.
+fn der_element(data: &[u8], pos: usize) -> Option<(u8, &[u8], usize)> {
+    let tag = *data.get(pos)?;
+    let first = *data.get(pos + 1)? as usize;
+    let (len, start) = match first {
+        len if len < 0x80 => (len, pos + 2),
+        0x81..=0x84 => {
+            let octets = first & 0x7f;
+            let len = data
+                .get(pos + 2..pos + 2 + octets)?
+                .iter()
+                .fold(0, |len, b| len << 8 | *b as usize);
+            (len, pos + 2 + octets)
+        }
+        _ => return None,
+    };
+    let end = start.checked_add(len)?;
+    Some((tag, data.get(start..end)?, end))
+}
+
+/// The length of an RSA modulus in a SubjectPublicKeyInfo or RSAPublicKey structure.
+fn rsa_bits(key: &[u8]) -> Option<usize> {
+    const SEQUENCE: u8 = 0x30;
+    const INTEGER: u8 = 0x02;
+    const BIT_STRING: u8 = 0x03;
+    let (tag, outer, _) = der_element(key, 0)?;
+    if tag != SEQUENCE {
+        return None;
+    }
+    let (tag, first, next) = der_element(outer, 0)?;
+    let modulus = match tag {
+        // RSAPublicKey, which some publishers use instead of SubjectPublicKeyInfo.
+        INTEGER => first,
+        SEQUENCE => {
+            let (tag, bits, _) = der_element(outer, next)?;
+            if tag != BIT_STRING {
+                return None;
+            }
+            // The first octet is the number of unused bits.
+            let (_, rsa_key, _) = der_element(bits.get(1..)?, 0)?;
+            let (tag, modulus, _) = der_element(rsa_key, 0)?;
+            if tag != INTEGER {
+                return None;
+            }
+            modulus
+        }
+        _ => return None,
+    };
+    let start = modulus.iter().position(|b| *b != 0)?;
+    Some((modulus.len() - start) * 8 - modulus[start].leading_zeros() as usize)
+}
+
+/// Why the text of a DKIM key record could not be parsed.
+#[derive(Debug, PartialEq)]
+pub enum KeyError {
+    InvalidTag(String),
+    UnknownVersion(String),
+    MissingPublicKey,
+    InvalidPublicKey(base64::DecodeError),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::InvalidTag(tag) => write!(f, "invalid tag '{tag}'"),
+            KeyError::UnknownVersion(version) => write!(f, "unknown version '{version}'"),
+            KeyError::MissingPublicKey => write!(f, "missing public key"),
+            KeyError::InvalidPublicKey(e) => write!(f, "invalid public key: {e}"),
+        }
+    }
+}
+
+impl DkimKey {
+    /// Parses the text of a DKIM key record.
+    pub fn parse(txt: &str) -> Result<Self, Error> {
+        let mut tags = BTreeMap::new();
+        for tag in txt.split(';').map(str::trim).filter(|tag| !tag.is_empty()) {
+            let (name, value) = tag
+                .split_once('=')
+                .ok_or_else(|| Error::ParseDkimKey(KeyError::InvalidTag(tag.into())))?;
+            tags.insert(name.trim().to_lowercase(), value.trim());
+        }
+        if let Some(version) = tags.get("v").filter(|v| **v != "DKIM1") {
+            return Err(Error::ParseDkimKey(KeyError::UnknownVersion(
+                version.to_string(),
+            )));
+        }
+        let key_type = tags.get("k").unwrap_or(&"rsa").to_lowercase();
+        let public_key: String = tags
+            .get("p")
+            .ok_or(Error::ParseDkimKey(KeyError::MissingPublicKey))?
+            .split_whitespace()
+            .collect();
+        let testing = tags
+            .get("t")
+            .is_some_and(|flags| flags.split(':').any(|flag| flag.trim() == "y"));
+        if public_key.is_empty() {
+            return Ok(Self {
+                key_type,
+                bits: None,
+                revoked: true,
+                testing,
+            });
+        }
+        let public_key = STANDARD
+            .decode(public_key)
+            .map_err(|e| Error::ParseDkimKey(KeyError::InvalidPublicKey(e)))?;
+        let bits = match key_type.as_str() {
+            "rsa" => rsa_bits(&public_key),
+            "ed25519" => Some(public_key.len() * 8),
+            _ => None,
+        };
+        Ok(Self {
+            key_type,
+            bits,
+            revoked: false,
+            testing,
+        })
+    }
+
+    /// Whether the key is an RSA key shorter than recommended.
+    pub fn is_weak(&self) -> bool {
+        self.key_type == "rsa" && self.bits.is_some_and(|bits| bits < MIN_RSA_BITS)
+    }
+}
+
+/// The outcome of looking up the key of a selector.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyStatus {
+    Missing,
+    Invalid(String),
+    Published(DkimKey),
+    /// The key could not be looked up, such as when the nameserver did not respond.
+    LookupFailed(String),
+}
+
+/// A selector of a signing domain, seen in reports or found in DNS.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    pub domain: String,
+    pub selector: String,
+    /// How signatures with the selector were evaluated, if any were reported.
+    pub usage: Option<Usage>,
+    /// The published key, if it was looked up.
+    pub key: Option<KeyStatus>,
+}
+
+impl Selector {
+    /// Problems with the selector and, if it was looked up, its key.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        match &self.key {
+            Some(KeyStatus::Missing) => problems.push("key is not published".into()),
+            Some(KeyStatus::Invalid(e)) => problems.push(e.clone()),
+            Some(KeyStatus::LookupFailed(e)) => problems.push(format!("key lookup failed: {e}")),
+            Some(KeyStatus::Published(key)) => {
+                // A revoked key of an unused selector is the intended state after rotating keys.
+                match (key.revoked, &self.usage) {
+                    (true, Some(_)) => problems.push("key is revoked but still used".into()),
+                    (false, None) => problems.push("published but not seen in reports".into()),
+                    _ => {}
+                }
+                if key.is_weak() {
+                    problems.push(format!(
+                        "{}-bit RSA key is weak",
+                        key.bits.unwrap_or_default()
+                    ));
+                }
+                if key.testing {
+                    problems.push("domain is testing DKIM".into());
+                }
+            }
+            None => {}
+        }
+        problems
+    }
+}
+
+/// Lists the selectors of the DKIM results of reports with their usage.
+///
+/// Results without a selector are not listed.
+pub fn inventory(feedbacks: &[Feedback]) -> Vec<Selector> {
+    let mut usages: BTreeMap<(String, String), Usage> = BTreeMap::new();
+    for feedback in feedbacks {
+        let date_range = &feedback.report_metadata.date_range;
+        for record in &feedback.records {
+            for dkim in &record.auth_results.dkim {
+                let Some(selector) = &dkim.selector else {
+                    continue;
+                };
+                let key = (normalize(&dkim.domain), selector.to_lowercase());
+                let usage = usages.entry(key).or_insert(Usage {
+                    messages: 0,
+                    passed: 0,
+                    first_seen: date_range.begin,
+                    last_seen: date_range.end,
+                });
+                let count = u64::from(record.row.count);
+                usage.messages += count;
+                if dkim.result == DkimResult::Pass {
+                    usage.passed += count;
+                }
+                usage.first_seen = usage.first_seen.min(date_range.begin);
+                usage.last_seen = usage.last_seen.max(date_range.end);
+            }
+        }
+    }
+    usages
+        .into_iter()
+        .map(|((domain, selector), usage)| Selector {
+            domain,
+            selector,
+            usage: Some(usage),
+            key: None,
+        })
+        .collect()
+}
+
+fn key_status(dns: &dyn TxtLookup, selector: &str, domain: &str) -> KeyStatus {
+    match lookup_dkim(dns, selector, domain) {
+        Ok(None) => KeyStatus::Missing,
+        Ok(Some(txt)) => match DkimKey::parse(&txt) {
+            Ok(key) => KeyStatus::Published(key),
+            Err(e) => KeyStatus::Invalid(e.to_string()),
+        },
+        Err(e) => KeyStatus::LookupFailed(e.to_string()),
+    }
+}
+
+/// Looks up the keys of the selectors and probes further selectors of their domains.
+///
+/// Probed selectors with a published key are added to the inventory without usage. A failed lookup
+/// is recorded in the status of the key, and the other selectors are still looked up.
+pub fn fetch_keys(dns: &dyn TxtLookup, selectors: &mut Vec<Selector>, probe: &[String]) {
+    for selector in selectors.iter_mut() {
+        selector.key = Some(key_status(dns, &selector.selector, &selector.domain));
+    }
+    let domains: BTreeSet<String> = selectors.iter().map(|s| s.domain.clone()).collect();
+    for domain in domains {
+        for probed in probe {
+            let probed = probed.to_lowercase();
+            if selectors
+                .iter()
+                .any(|s| s.domain == domain && s.selector == probed)
+            {
+                continue;
+            }
+            let key = key_status(dns, &probed, &domain);
+            // Failed probes are not listed, as most probed selectors are not expected to exist.
+            if !matches!(key, KeyStatus::Missing | KeyStatus::LookupFailed(_)) {
+                selectors.push(Selector {
+                    domain: domain.clone(),
+                    selector: probed,
+                    usage: None,
+                    key: Some(key),
+                });
+            }
+        }
+    }
+    selectors.sort_by(|a, b| (&a.domain, &a.selector).cmp(&(&b.domain, &b.selector)));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io;
+
+    use crate::dns::TxtLookup;
+    use crate::{fixtures, Error};
+
+    use super::{fetch_keys, inventory, DkimKey, KeyStatus, Selector};
+
+    const RSA_1024: &str = "MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQDYLCKXKgH6R50WHQM0bpoiR6aUFc7IsYgGug6faww+dK5FO5xYNmnMkwOOoU0b/mqeI5Z4kX6xNeSyXha1cUMfJxKNT2rT/wFHz5JUDvWgZBx3ucrjYhQU8goCi10hERWd3tni2aoD2+4QCOfmCRZohH7VsXI280tMRvu1FnWjuwIDAQAB";
+
+    #[test]
+    fn parse_key() {
+        let key = DkimKey::parse(&format!("v=DKIM1; k=rsa; t=y; p={RSA_1024}")).unwrap();
+        assert_eq!(key.bits, Some(1024));
+        assert!(key.is_weak());
+        assert!(key.testing);
+
+        let key = DkimKey::parse("v=DKIM1; p=").unwrap();
+        assert!(key.revoked);
+        assert!(!key.is_weak());
+
+        let key =
+            DkimKey::parse("k=ed25519; p=11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=").unwrap();
+        assert_eq!(key.bits, Some(256));
+        assert!(DkimKey::parse("v=DKIM1; k=rsa").is_err());
+    }
+
+    #[test]
+    fn inventory_selectors() {
+        let feedback = fixtures::report();
+        let mut selectors = inventory(&[feedback]);
+        assert_eq!(selectors.len(), 1);
+        let seen = selectors[0].clone();
+        assert_eq!(
+            (seen.domain.as_str(), seen.selector.as_str()),
+            ("example.com", "2023a")
+        );
+        let usage = seen.usage.as_ref().unwrap();
+        assert_eq!((usage.messages, usage.passed), (12, 12));
+        assert_eq!(usage.first_seen.timestamp(), 1700006400);
+        assert_eq!(usage.last_seen.timestamp(), 1700092799);
+
+        let dns = HashMap::from([
+            (
+                format!("{}._domainkey.{}", seen.selector, seen.domain),
+                vec![format!("v=DKIM1; p={RSA_1024}")],
+            ),
+            (
+                format!("unused._domainkey.{}", seen.domain),
+                vec!["v=DKIM1; p=".to_string()],
+            ),
+        ]);
+        let count = selectors.len();
+        fetch_keys(&dns, &mut selectors, &["unused".into(), "missing".into()]);
+        assert_eq!(selectors.len(), count + 1);
+        let unused = selectors.iter().find(|s| s.selector == "unused").unwrap();
+        assert!(unused.usage.is_none());
+        assert!(matches!(&unused.key, Some(KeyStatus::Published(key)) if key.revoked));
+        let seen = selectors
+            .iter()
+            .find(|s| s.selector == seen.selector && s.domain == seen.domain)
+            .unwrap();
+        assert!(matches!(&seen.key, Some(KeyStatus::Published(key)) if key.is_weak()));
+    }
+
+    /// Fails to look up the keys of one selector.
+    struct Failing(HashMap<String, Vec<String>>);
+
+    impl TxtLookup for Failing {
+        fn lookup_txt(&self, name: &str) -> Result<Vec<String>, Error> {
+            if name.starts_with("broken.") {
+                let e = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+                return Err(Error::QueryDns(name.into(), e));
+            }
+            self.0.lookup_txt(name)
+        }
+    }
+
+    #[test]
+    fn fetch_keys_after_failed_lookup() {
+        let selector = |selector: &str| Selector {
+            domain: "example.com".into(),
+            selector: selector.into(),
+            usage: None,
+            key: None,
+        };
+        let mut selectors = vec![selector("broken"), selector("s1")];
+        let dns = Failing(HashMap::from([(
+            "s1._domainkey.example.com".to_string(),
+            vec![format!("v=DKIM1; p={RSA_1024}")],
+        )]));
+        fetch_keys(&dns, &mut selectors, &["broken".into(), "unused".into()]);
+        assert_eq!(selectors.len(), 2);
+        assert!(matches!(
+            &selectors[0].key,
+            Some(KeyStatus::LookupFailed(_))
+        ));
+        assert!(selectors[0].problems()[0].starts_with("key lookup failed"));
+        assert!(matches!(&selectors[1].key, Some(KeyStatus::Published(_))));
+    }
+}
@@ -1,10 +1,11 @@
+use std::fmt;
 use std::net::IpAddr;
 
 use chrono::serde::ts_seconds;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Feedback {
     /// Version is optional since not included in Google report.
     pub version: Option<f32>,
@@ -17,7 +18,7 @@ pub struct Feedback {
 }
 
 /// The time range in UTC covered by messages in this report, specified in seconds since epoch.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct DateRange {
     #[serde(with = "ts_seconds")]
     pub begin: DateTime<Utc>,
@@ -26,7 +27,7 @@ pub struct DateRange {
 }
 
 /// Report generator metadata.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct ReportMetadata {
     pub org_name: String,
     pub email: String,
@@ -40,7 +41,7 @@ pub struct ReportMetadata {
 }
 
 /// Alignment mode (relaxed or strict) for DKIM and SPF.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Alignment {
     #[serde(rename = "r")]
     Relaxed,
@@ -48,8 +49,18 @@ pub enum Alignment {
     Strict,
 }
 
-/// The policy actions specified by p and sp in the DMARC record.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+impl Alignment {
+    /// The name of the alignment mode as used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Relaxed => "r",
+            Self::Strict => "s",
+        }
+    }
+}
+
+/// The policy actions specified by p and sp in the DMARC record, ordered by strictness.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum Disposition {
     None,
@@ -57,8 +68,27 @@ pub enum Disposition {
     Reject,
 }
 
+impl Disposition {
+    /// The name of the policy as used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Quarantine => "quarantine",
+            Self::Reject => "reject",
+        }
+    }
+
+    /// The next less strict disposition, which receivers apply to failing messages excluded by `pct`.
+    pub fn relaxed(self) -> Disposition {
+        match self {
+            Self::Reject => Self::Quarantine,
+            Self::Quarantine | Self::None => Self::None,
+        }
+    }
+}
+
 /// The DMARC policy that applied to the messages in this report.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct PolicyPublished {
     /// The domain at which the DMARC record was found.
     pub domain: String,
@@ -102,6 +132,20 @@ impl PolicyPublished {
         let wrapper = PolicyPublishedWrapper::deserialize(deserializer)?;
         Ok(wrapper.into())
     }
+
+    /// Whether messages from a RFC5322.From domain are subject to `sp` rather than `p`.
+    pub fn is_subdomain_policy(&self, header_from: &str) -> bool {
+        crate::domain::normalize(header_from) != crate::domain::normalize(&self.domain)
+    }
+
+    /// The disposition requested for failing messages from a RFC5322.From domain.
+    pub fn requested(&self, header_from: &str) -> Disposition {
+        if self.is_subdomain_policy(header_from) {
+            self.sp
+        } else {
+            self.p
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -111,7 +155,6 @@ pub struct PolicyPublishedWrapper {
     pub aspf: Option<Alignment>,
     pub p: Disposition,
     /// This is made optional since some reports treat this as optional due to it being inheritive of `p`.
-    #[serde(rename = "$text")]
     pub sp: Option<Disposition>,
     pub pct: Option<u8>,
     /// This is made optional since the Google report does not include this field.
@@ -119,15 +162,25 @@ pub struct PolicyPublishedWrapper {
 }
 
 /// The DMARC-aligned authentication result.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum DmarcResult {
     Pass,
     Fail,
 }
 
+impl DmarcResult {
+    /// The name of the result as used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Fail => "fail",
+        }
+    }
+}
+
 /// Reasons that may affect DMARC disposition or execution thereof.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum PolicyOverride {
     Forwarded,
@@ -135,25 +188,54 @@ pub enum PolicyOverride {
     TrustedForwarder,
     MailingList,
     LocalPolicy,
+    #[default]
     Other,
 }
 
-impl Default for PolicyOverride {
-    fn default() -> Self {
-        Self::Other
+impl PolicyOverride {
+    /// The name of the override reason as used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Forwarded => "forwarded",
+            Self::SampledOut => "sampled_out",
+            Self::TrustedForwarder => "trusted_forwarder",
+            Self::MailingList => "mailing_list",
+            Self::LocalPolicy => "local_policy",
+            Self::Other => "other",
+        }
+    }
+
+    pub fn deserialize_lenient<'de, D>(deserializer: D) -> Result<PolicyOverride, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let typ = String::deserialize(deserializer)?;
+        Ok(match typ.trim().to_lowercase().as_str() {
+            "forwarded" => Self::Forwarded,
+            "sampled_out" => Self::SampledOut,
+            "trusted_forwarder" => Self::TrustedForwarder,
+            "mailing_list" => Self::MailingList,
+            "local_policy" => Self::LocalPolicy,
+            _ => Self::Other,
+        })
     }
 }
 
 /// How do we allow report generators to include new classes of override reasons if they want to be more specific than "other"?
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct PolicyOverrideReason {
-    #[serde(default, rename = "type$text")]
+    /// Empty and unknown types are read as [`PolicyOverride::Other`].
+    #[serde(
+        default,
+        rename = "type",
+        deserialize_with = "PolicyOverride::deserialize_lenient"
+    )]
     pub typ: PolicyOverride,
     pub comment: Option<String>,
 }
 
 /// Taking into account everything else in the record, the results of applying DMARC.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct PolicyEvaluated {
     pub disposition: Disposition,
     pub dkim: DmarcResult,
@@ -162,7 +244,7 @@ pub struct PolicyEvaluated {
     pub reasons: Vec<PolicyOverrideReason>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct Row {
     /// The connecting IP.
     pub source_ip: IpAddr,
@@ -172,7 +254,7 @@ pub struct Row {
     pub policy_evaluated: PolicyEvaluated,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct Identifier {
     /// The envelope recipient domain.
     pub envelope_to: Option<String>,
@@ -185,19 +267,37 @@ pub struct Identifier {
 }
 
 /// DKIM verification result, according to RFC 7001 Section 2.6.1.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
 pub enum DkimResult {
     None,
     Pass,
     Fail,
     Policy,
     Neutral,
+    /// Older reports spell this `temp_error`.
+    #[serde(alias = "temp_error")]
     TempError,
+    #[serde(alias = "perm_error")]
     PermError,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+impl DkimResult {
+    /// The name of the result as used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Pass => "pass",
+            Self::Fail => "fail",
+            Self::Policy => "policy",
+            Self::Neutral => "neutral",
+            Self::TempError => "temperror",
+            Self::PermError => "permerror",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct DkimAuthResult {
     /// The "d=" parameter in the signature.
     pub domain: String,
@@ -209,15 +309,25 @@ pub struct DkimAuthResult {
     pub human_result: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum SpfDomainScope {
     Helo,
     MFrom,
 }
 
+impl SpfDomainScope {
+    /// The name of the scope as used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Helo => "helo",
+            Self::MFrom => "mfrom",
+        }
+    }
+}
+
 /// DKIM verification result, according to RFC 7001 Section 2.6.1.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum SpfResult {
     None,
@@ -226,12 +336,29 @@ pub enum SpfResult {
     Fail,
     Softfail,
     /// "TempError" commonly implemented as "unknown".
+    #[serde(alias = "unknown")]
     TempError,
     /// "PermError" commonly implemented as "error".
+    #[serde(alias = "error")]
     PermError,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+impl SpfResult {
+    /// The name of the result as used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Neutral => "neutral",
+            Self::Pass => "pass",
+            Self::Fail => "fail",
+            Self::Softfail => "softfail",
+            Self::TempError => "temperror",
+            Self::PermError => "permerror",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct SpfAuthResult {
     /// The checked domain.
     pub domain: String,
@@ -244,7 +371,7 @@ pub struct SpfAuthResult {
 }
 
 /// This element contains DKIM and SPF results, uninterpreted with respect to DMARC.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct AuthResult {
     /// There may be no DKIM signatures, or multiple DKIM signatures.
     #[serde(default)]
@@ -254,21 +381,88 @@ pub struct AuthResult {
 }
 
 /// This element contains all the authentication results that were evaluated by the receiving system for the given set of messages.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct Record {
     pub row: Row,
     pub identifiers: Identifier,
     pub auth_results: AuthResult,
 }
 
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} to {}", self.begin, self.end)
+    }
+}
+
+impl fmt::Display for DkimAuthResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} (", self.result)?;
+        write!(f, "d={}", self.domain)?;
+        if let Some(selector) = &self.selector {
+            write!(f, ", selector={selector}")?;
+        }
+        if let Some(human_result) = &self.human_result {
+            write!(f, ", human_result={human_result}")?;
+        }
+        write!(f, ")")?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for SpfAuthResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} (", self.result)?;
+        write!(f, "d={}", self.domain)?;
+        if let Some(scope) = &self.scope {
+            write!(f, ", scope={scope:?}")?;
+        }
+        write!(f, ")")?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for PolicyOverrideReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.typ)?;
+        if let Some(comment) = &self.comment {
+            write!(f, " ({comment})")?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use quick_xml::de::from_str;
 
-    use crate::dmarc::{PolicyOverride, PolicyOverrideReason};
+    use crate::dmarc::{
+        Disposition, DkimResult, PolicyOverride, PolicyOverrideReason, PolicyPublished,
+    };
 
     use super::SpfDomainScope;
 
+    #[derive(serde::Deserialize)]
+    struct PolicyPublishedTest {
+        #[serde(deserialize_with = "PolicyPublished::deserialize_from_wrapper")]
+        policy_published: PolicyPublished,
+    }
+
+    #[test]
+    fn deserialize_policy_published_sp() {
+        let xml = "<feedback><policy_published><domain>example.com</domain><p>reject</p><sp>none</sp></policy_published></feedback>";
+        let policy = from_str::<PolicyPublishedTest>(xml)
+            .unwrap()
+            .policy_published;
+        assert_eq!(policy.p, Disposition::Reject);
+        assert_eq!(policy.sp, Disposition::None);
+
+        let xml = "<feedback><policy_published><domain>example.com</domain><p>reject</p></policy_published></feedback>";
+        let policy = from_str::<PolicyPublishedTest>(xml)
+            .unwrap()
+            .policy_published;
+        assert_eq!(policy.sp, Disposition::Reject);
+    }
+
     #[test]
     fn deserialize_spf_domain_scope() {
         let scope: SpfDomainScope = from_str("<mfrom></mfrom>").unwrap();
@@ -304,9 +498,32 @@ mod tests {
         assert_eq!(
             por,
             PolicyOverrideReason {
-                typ: PolicyOverride::Other,
+                typ: PolicyOverride::Forwarded,
                 comment: None
             }
         );
     }
+
+    #[derive(serde::Deserialize)]
+    struct DkimResultTest {
+        result: DkimResult,
+    }
+
+    #[test]
+    fn deserialize_dkim_result_error_spellings() {
+        for xml in [
+            "<dkim><result>temperror</result></dkim>",
+            "<dkim><result>temp_error</result></dkim>",
+        ] {
+            let dkim: DkimResultTest = from_str(xml).unwrap();
+            assert_eq!(dkim.result, DkimResult::TempError);
+        }
+        for xml in [
+            "<dkim><result>permerror</result></dkim>",
+            "<dkim><result>perm_error</result></dkim>",
+        ] {
+            let dkim: DkimResultTest = from_str(xml).unwrap();
+            assert_eq!(dkim.result, DkimResult::PermError);
+        }
+    }
 }
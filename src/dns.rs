@@ -0,0 +1,701 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::domain::{normalize, organizational_domain};
+use crate::{Error, Feedback};
+
+/// The record type of IPv4 address records.
+pub const A: u16 = 1;
+/// The record type of mail exchanger records.
+pub const MX: u16 = 15;
+/// The record type of TXT records.
+pub const TXT: u16 = 16;
+/// The record type of IPv6 address records.
+pub const AAAA: u16 = 28;
+
+/// The response code of a query for a name that does not exist.
+const NXDOMAIN: u8 = 3;
+
+/// How long the absence of records is cached.
+const NEGATIVE_TTL: Duration = Duration::from_secs(300);
+
+/// Looks up the TXT records of a domain name.
+pub trait TxtLookup {
+    /// The text of each TXT record of a name, with its character strings concatenated.
+    ///
+    /// A name without TXT records, or which does not exist, has no records.
+    fn lookup_txt(&self, name: &str) -> Result<Vec<String>, Error>;
+}
+
+/// Looks up the records needed to evaluate SPF policies.
+pub trait Lookup: TxtLookup {
+    /// The IPv4 or IPv6 addresses of a name.
+    fn lookup_ips(&self, name: &str, ipv6: bool) -> Result<Vec<IpAddr>, Error>;
+
+    /// The mail exchangers of a name, ordered by preference.
+    fn lookup_mx(&self, name: &str) -> Result<Vec<String>, Error>;
+}
+
+/// Fixed TXT records by name, such as records given on the command line.
+impl TxtLookup for HashMap<String, Vec<String>> {
+    fn lookup_txt(&self, name: &str) -> Result<Vec<String>, Error> {
+        Ok(self.get(&normalize(name)).cloned().unwrap_or_default())
+    }
+}
+
+/// A resource record from the answer section of a response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answer {
+    pub rtype: u16,
+    pub ttl: u32,
+    pub data: Vec<u8>,
+}
+
+type Cache = HashMap<(String, u16), (Instant, Vec<Answer>)>;
+
+/// A stub resolver sending recursive queries to a single nameserver.
+///
+/// Answers are cached for their TTL, and the absence of records for a few minutes. Clones of a
+/// resolver share its cache.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    nameserver: SocketAddr,
+    timeout: Duration,
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl Resolver {
+    pub fn new(nameserver: SocketAddr) -> Self {
+        Self {
+            nameserver,
+            timeout: Duration::from_secs(5),
+            cache: Arc::default(),
+        }
+    }
+
+    /// A resolver using the first nameserver of `/etc/resolv.conf`, or the local host if there is none.
+    pub fn from_system() -> Self {
+        let nameserver = fs::read_to_string("/etc/resolv.conf")
+            .ok()
+            .and_then(|conf| {
+                conf.lines().find_map(|line| {
+                    let mut words = line.split_whitespace();
+                    (words.next() == Some("nameserver"))
+                        .then(|| words.next()?.parse::<IpAddr>().ok())
+                        .flatten()
+                })
+            })
+            .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        Self::new(SocketAddr::new(nameserver, 53))
+    }
+
+    /// Queries the records of the given type, answering from the cache while it is fresh.
+    pub fn query(&self, name: &str, rtype: u16) -> Result<Vec<Answer>, Error> {
+        let key = (normalize(name), rtype);
+        let now = Instant::now();
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        match cache.get(&key) {
+            Some((expires, answers)) if *expires > now => return Ok(answers.clone()),
+            Some(_) => {
+                cache.remove(&key);
+            }
+            None => {}
+        }
+        drop(cache);
+
+        let answers = self.query_server(name, rtype)?;
+        let ttl = answers
+            .iter()
+            .map(|answer| Duration::from_secs(answer.ttl.into()))
+            .min()
+            .unwrap_or(NEGATIVE_TTL);
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.insert(key, (now + ttl, answers.clone()));
+        Ok(answers)
+    }
+
+    /// Queries the nameserver, retrying over TCP if the UDP response is truncated.
+    ///
+    /// The query ID and the source port are random, to make forging responses harder.
+    fn query_server(&self, name: &str, rtype: u16) -> Result<Vec<Answer>, Error> {
+        let query_error = |e| Error::QueryDns(name.into(), e);
+        let id = random_u16().map_err(query_error)?;
+        let query = encode_query(id, name, rtype).map_err(query_error)?;
+
+        let socket = self.bind_random_port().map_err(query_error)?;
+        socket
+            .send_to(&query, self.nameserver)
+            .map_err(query_error)?;
+        // Stray datagrams must not extend the time waited for the response.
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = [0; 4096];
+        let response = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                let e = io::Error::new(io::ErrorKind::TimedOut, "no response from nameserver");
+                return Err(query_error(e));
+            }
+            socket
+                .set_read_timeout(Some(remaining))
+                .map_err(query_error)?;
+            let (len, from) = socket.recv_from(&mut buf).map_err(query_error)?;
+            // Ignore stray datagrams which do not answer this query.
+            if from == self.nameserver && answers_query(&query, &buf[..len]) {
+                break buf[..len].to_vec();
+            }
+        };
+        let response = if is_truncated(&response) {
+            self.query_tcp(&query).map_err(query_error)?
+        } else {
+            response
+        };
+        decode_response(&query, rtype, &response)
+            .map_err(|reason| Error::DnsResponse(name.into(), reason))
+    }
+
+    /// Binds a UDP socket to a random unprivileged port, falling back to one chosen by the system.
+    fn bind_random_port(&self) -> io::Result<UdpSocket> {
+        let ip = match self.nameserver {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        for _ in 0..8 {
+            let port = 1024 + random_u16()? % (u16::MAX - 1024);
+            match UdpSocket::bind((ip, port)) {
+                Err(e) if e.kind() == io::ErrorKind::AddrInUse => continue,
+                result => return result,
+            }
+        }
+        UdpSocket::bind((ip, 0))
+    }
+
+    fn query_tcp(&self, query: &[u8]) -> io::Result<Vec<u8>> {
+        let mut stream = TcpStream::connect_timeout(&self.nameserver, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.write_all(&(query.len() as u16).to_be_bytes())?;
+        stream.write_all(query)?;
+        let mut len = [0; 2];
+        stream.read_exact(&mut len)?;
+        let mut response = vec![0; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut response)?;
+        Ok(response)
+    }
+}
+
+impl TxtLookup for Resolver {
+    fn lookup_txt(&self, name: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .query(name, TXT)?
+            .iter()
+            .map(|answer| txt_data(&answer.data))
+            .collect())
+    }
+}
+
+impl Lookup for Resolver {
+    fn lookup_ips(&self, name: &str, ipv6: bool) -> Result<Vec<IpAddr>, Error> {
+        let answers = self.query(name, if ipv6 { AAAA } else { A })?;
+        Ok(answers
+            .iter()
+            .filter_map(|answer| match answer.data.len() {
+                4 => Some(IpAddr::from(<[u8; 4]>::try_from(&answer.data[..]).ok()?)),
+                16 => Some(IpAddr::from(<[u8; 16]>::try_from(&answer.data[..]).ok()?)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn lookup_mx(&self, name: &str) -> Result<Vec<String>, Error> {
+        let mut exchanges: Vec<(u16, String)> = self
+            .query(name, MX)?
+            .iter()
+            .filter_map(|answer| {
+                let preference = read_u16(&answer.data, 0)?;
+                let exchange = String::from_utf8(answer.data.get(2..)?.to_vec()).ok()?;
+                Some((preference, exchange))
+            })
+            .collect();
+        exchanges.sort();
+        Ok(exchanges
+            .into_iter()
+            .map(|(_, exchange)| exchange)
+            .collect())
+    }
+}
+
+/// Whether a TXT record is a DMARC record.
+fn is_dmarc(txt: &str) -> bool {
+    txt.trim_start().starts_with("v=DMARC1")
+}
+
+/// Finds the DMARC record for a domain as described in RFC 7489 Section 6.6.3.
+///
+/// If the domain has no record, the record of its organizational domain is used. Returns the policy
+/// domain the record was found for and its text. If a name has more than one DMARC record, no
+/// record applies.
+pub fn lookup_dmarc(dns: &dyn TxtLookup, domain: &str) -> Result<Option<(String, String)>, Error> {
+    let domain = normalize(domain);
+    let organizational = organizational_domain(&domain);
+    let mut candidates = vec![domain.as_str()];
+    if organizational != domain {
+        candidates.push(&organizational);
+    }
+    for candidate in candidates {
+        let name = format!("_dmarc.{candidate}");
+        let mut records: Vec<String> = dns
+            .lookup_txt(&name)?
+            .into_iter()
+            .filter(|txt| is_dmarc(txt))
+            .collect();
+        match records.len() {
+            0 => continue,
+            1 => return Ok(records.pop().map(|txt| (candidate.to_string(), txt))),
+            // Several records make DMARC not apply, without falling back to the organizational domain.
+            _ => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+/// Finds the SPF record of a domain.
+///
+/// Returns `None` if there is no record, or more than one, which makes SPF fail permanently.
+pub fn lookup_spf(dns: &dyn TxtLookup, domain: &str) -> Result<Option<String>, Error> {
+    let mut records: Vec<String> = dns
+        .lookup_txt(&normalize(domain))?
+        .into_iter()
+        .filter(|txt| {
+            let version = txt.split_whitespace().next().unwrap_or_default();
+            version.eq_ignore_ascii_case("v=spf1")
+        })
+        .collect();
+    Ok(match records.len() {
+        1 => records.pop(),
+        _ => None,
+    })
+}
+
+/// Finds the DKIM key record of a selector of a domain, as described in RFC 6376 Section 3.6.2.
+pub fn lookup_dkim(
+    dns: &dyn TxtLookup,
+    selector: &str,
+    domain: &str,
+) -> Result<Option<String>, Error> {
+    let name = format!("{selector}._domainkey.{}", normalize(domain));
+    Ok(dns.lookup_txt(&name)?.into_iter().next())
+}
+
+/// A record published for a policy domain, or its absence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Published {
+    /// The kind of record, such as `DMARC`.
+    pub kind: &'static str,
+    /// The name the record was queried at.
+    pub name: String,
+    pub record: Option<String>,
+}
+
+/// Looks up the DMARC record of a policy domain and the SPF and DKIM records seen for it in reports.
+///
+/// SPF records are looked up for the policy domain and the SPF domains of its records. DKIM records
+/// are looked up for each domain and selector pair of its DKIM results.
+pub fn lookup_published(
+    dns: &dyn TxtLookup,
+    domain: &str,
+    feedbacks: &[Feedback],
+) -> Result<Vec<Published>, Error> {
+    let domain = normalize(domain);
+    let mut published = vec![];
+    let dmarc = lookup_dmarc(dns, &domain)?;
+    published.push(Published {
+        kind: "DMARC",
+        name: format!(
+            "_dmarc.{}",
+            dmarc.as_ref().map_or(domain.as_str(), |(found, _)| found)
+        ),
+        record: dmarc.map(|(_, txt)| txt),
+    });
+
+    let records = feedbacks
+        .iter()
+        .filter(|feedback| normalize(&feedback.policy_published.domain) == domain)
+        .flat_map(|feedback| &feedback.records);
+    let mut spf_domains = BTreeSet::from([domain.clone()]);
+    let mut selectors = BTreeSet::new();
+    for record in records {
+        for spf in &record.auth_results.spf {
+            spf_domains.insert(normalize(&spf.domain));
+        }
+        for dkim in &record.auth_results.dkim {
+            if let Some(selector) = &dkim.selector {
+                selectors.insert((normalize(&dkim.domain), selector.to_lowercase()));
+            }
+        }
+    }
+    for spf_domain in spf_domains {
+        published.push(Published {
+            kind: "SPF",
+            record: lookup_spf(dns, &spf_domain)?,
+            name: spf_domain,
+        });
+    }
+    for (dkim_domain, selector) in selectors {
+        published.push(Published {
+            kind: "DKIM",
+            record: lookup_dkim(dns, &selector, &dkim_domain)?,
+            name: format!("{selector}._domainkey.{dkim_domain}"),
+        });
+    }
+    Ok(published)
+}
+
+/// Encodes a recursive query for a single question, advertising a larger UDP payload size with EDNS.
+fn encode_query(id: u16, name: &str, rtype: u16) -> io::Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(64);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question and one additional record.
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 1]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid domain name",
+            ));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&rtype.to_be_bytes());
+    query.extend_from_slice(&[0, 1]);
+    // OPT pseudo-record with a payload size of 1232 bytes.
+    query.extend_from_slice(&[0, 0, 41, 0x04, 0xd0, 0, 0, 0, 0, 0, 0]);
+    Ok(query)
+}
+
+fn random_u16() -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    getrandom::fill(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+/// Whether a response has the ID and the question of a query, ignoring the case of the name.
+fn answers_query(query: &[u8], response: &[u8]) -> bool {
+    let Some(question_end) = skip_name(query, 12).map(|pos| pos + 4) else {
+        return false;
+    };
+    response.len() >= question_end
+        && response[..2] == query[..2]
+        && read_u16(response, 4) == Some(1)
+        && response[12..question_end].eq_ignore_ascii_case(&query[12..question_end])
+}
+
+fn is_truncated(response: &[u8]) -> bool {
+    response.len() >= 3 && response[2] & 0x02 != 0
+}
+
+/// Returns the position after a possibly compressed name.
+fn skip_name(message: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // A compression pointer ends the name.
+            len if len & 0xc0 == 0xc0 => return Some(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+/// Reads a possibly compressed name, following at most a few compression pointers.
+fn read_name(message: &[u8], mut pos: usize) -> Option<String> {
+    let mut labels = vec![];
+    let mut jumps = 0;
+    loop {
+        let len = *message.get(pos)?;
+        match len {
+            0 => return Some(labels.join(".")),
+            len if len & 0xc0 == 0xc0 => {
+                jumps += 1;
+                if jumps > 16 {
+                    return None;
+                }
+                pos = (read_u16(message, pos)? & 0x3fff) as usize;
+            }
+            len => {
+                let label = message.get(pos + 1..pos + 1 + len as usize)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + len as usize;
+            }
+        }
+    }
+}
+
+fn read_u16(message: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        message.get(pos..pos + 2)?.try_into().ok()?,
+    ))
+}
+
+/// Why a DNS response could not be used.
+#[derive(Debug, PartialEq)]
+pub enum ResponseError {
+    Malformed,
+    /// The response does not answer the query.
+    Mismatch,
+    /// The server responded with an error code other than NXDOMAIN.
+    Rcode(u8),
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseError::Malformed => write!(f, "malformed response"),
+            ResponseError::Mismatch => write!(f, "response does not match query"),
+            ResponseError::Rcode(rcode) => write!(f, "server responded with code {rcode}"),
+        }
+    }
+}
+
+/// Decodes the answers of the requested type from the response to a query.
+fn decode_response(
+    query: &[u8],
+    rtype: u16,
+    response: &[u8],
+) -> Result<Vec<Answer>, ResponseError> {
+    let malformed = || ResponseError::Malformed;
+    if response.len() < 12 {
+        return Err(malformed());
+    }
+    if !answers_query(query, response) {
+        return Err(ResponseError::Mismatch);
+    }
+    match response[3] & 0x0f {
+        0 => {}
+        NXDOMAIN => return Ok(vec![]),
+        rcode => return Err(ResponseError::Rcode(rcode)),
+    }
+    let questions = read_u16(response, 4).ok_or_else(malformed)?;
+    let answers = read_u16(response, 6).ok_or_else(malformed)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(response, pos).ok_or_else(malformed)? + 4;
+    }
+    let mut records = vec![];
+    for _ in 0..answers {
+        pos = skip_name(response, pos).ok_or_else(malformed)?;
+        let header = response.get(pos..pos + 10).ok_or_else(malformed)?;
+        let answer_type = u16::from_be_bytes([header[0], header[1]]);
+        let ttl = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let len = u16::from_be_bytes([header[8], header[9]]) as usize;
+        pos += 10;
+        let data = response.get(pos..pos + len).ok_or_else(malformed)?;
+        pos += len;
+        // Aliases leading to the records are skipped.
+        if answer_type != rtype {
+            continue;
+        }
+        let data = if answer_type == MX {
+            // The exchange is decompressed, as it may point into the rest of the response.
+            let exchange = read_name(response, pos - len + 2).ok_or_else(malformed)?;
+            [&data[..2.min(data.len())], exchange.as_bytes()].concat()
+        } else {
+            data.to_vec()
+        };
+        records.push(Answer {
+            rtype: answer_type,
+            ttl,
+            data,
+        });
+    }
+    Ok(records)
+}
+
+/// Concatenates the character strings of TXT record data.
+fn txt_data(data: &[u8]) -> String {
+    let mut text = vec![];
+    let mut pos = 0;
+    while let Some(&len) = data.get(pos) {
+        let end = (pos + 1 + len as usize).min(data.len());
+        text.extend_from_slice(&data[pos + 1..end]);
+        pos = end;
+    }
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::UdpSocket;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::Error;
+
+    use super::{
+        decode_response, encode_query, lookup_dkim, lookup_dmarc, lookup_published, lookup_spf,
+        Resolver, ResponseError, TxtLookup, TXT,
+    };
+
+    /// Answers a single TXT query with the given character strings and TTL, using a compressed name.
+    fn serve_txt(socket: &UdpSocket, ttl: u32, strings: &[&str]) {
+        let mut buf = [0; 512];
+        let (len, from) = socket.recv_from(&mut buf).unwrap();
+        let question_end = 12 + buf[12..].iter().position(|b| *b == 0).unwrap() + 5;
+        let mut response = buf[..question_end].to_vec();
+        response[2] = 0x81;
+        response[3] = 0x80;
+        response[6..12].copy_from_slice(&[0, 1, 0, 0, 0, 0]);
+        let data: Vec<u8> = strings
+            .iter()
+            .flat_map(|s| [&[s.len() as u8][..], s.as_bytes()].concat())
+            .collect();
+        response.extend_from_slice(&[0xc0, 12, 0, 16, 0, 1]);
+        response.extend_from_slice(&ttl.to_be_bytes());
+        response.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        response.extend_from_slice(&data);
+        assert!(len > question_end);
+        socket.send_to(&response, from).unwrap();
+    }
+
+    #[test]
+    fn lookup_txt_from_local_server() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let resolver = Resolver::new(socket.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            serve_txt(&socket, 3600, &["v=DMARC1; ", "p=reject"]);
+            serve_txt(&socket, 0, &["v=spf1 -all"]);
+            serve_txt(&socket, 0, &["v=spf1 +all"]);
+        });
+        let txt = resolver.lookup_txt("_dmarc.example.com").unwrap();
+        assert_eq!(txt, vec!["v=DMARC1; p=reject"]);
+        // Answered from the cache, as the server answers other names from now on.
+        assert_eq!(resolver.lookup_txt("_dmarc.Example.com.").unwrap(), txt);
+        // Expired answers are queried again.
+        assert_eq!(
+            resolver.lookup_txt("example.com").unwrap(),
+            vec!["v=spf1 -all"]
+        );
+        assert_eq!(
+            resolver.lookup_txt("example.com").unwrap(),
+            vec!["v=spf1 +all"]
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn time_out_despite_stray_datagrams() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let resolver = Resolver {
+            timeout: Duration::from_millis(200),
+            ..Resolver::new(socket.local_addr().unwrap())
+        };
+        let server = thread::spawn(move || {
+            let mut buf = [0; 512];
+            let (_, from) = socket.recv_from(&mut buf).unwrap();
+            for _ in 0..30 {
+                // The resolver stops listening once it times out.
+                let _ = socket.send_to(b"stray", from);
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+        let start = Instant::now();
+        assert!(matches!(
+            resolver.query_server("example.com", TXT),
+            Err(Error::QueryDns(_, _))
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn reject_responses_to_other_queries() {
+        let query = encode_query(0x1234, "_dmarc.example.com", TXT).unwrap();
+        let mut response = query[..query.len() - 11].to_vec();
+        response[2] = 0x81;
+        response[3] = 0x80;
+        response[10..12].copy_from_slice(&[0, 0]);
+        assert_eq!(decode_response(&query, TXT, &response), Ok(vec![]));
+        // The case of the name may differ.
+        response[13] = b'_';
+        response[14] = b'D';
+        assert_eq!(decode_response(&query, TXT, &response), Ok(vec![]));
+
+        let mut other_id = response.clone();
+        other_id[1] = 0x35;
+        assert_eq!(
+            decode_response(&query, TXT, &other_id),
+            Err(ResponseError::Mismatch)
+        );
+        let mut other_name = response.clone();
+        other_name[15] = b'x';
+        assert_eq!(
+            decode_response(&query, TXT, &other_name),
+            Err(ResponseError::Mismatch)
+        );
+        let mut other_type = response;
+        other_type[query.len() - 14] = 1;
+        assert_eq!(
+            decode_response(&query, TXT, &other_type),
+            Err(ResponseError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn lookup_published_records() {
+        let records = |entries: &[(&str, &[&str])]| -> HashMap<String, Vec<String>> {
+            entries
+                .iter()
+                .map(|(name, txt)| {
+                    (
+                        name.to_string(),
+                        txt.iter().map(|t| t.to_string()).collect(),
+                    )
+                })
+                .collect()
+        };
+        let dns = records(&[
+            ("_dmarc.example.com", &["v=DMARC1; p=reject"]),
+            ("_dmarc.sub.example.com", &["not dmarc"]),
+            (
+                "_dmarc.example.org",
+                &["v=DMARC1; p=none", "v=DMARC1; p=reject"],
+            ),
+            (
+                "example.com",
+                &["google-site-verification=abc", "v=spf1 mx -all"],
+            ),
+            ("example.org", &["v=spf1 -all", "v=spf1 +all"]),
+            ("_dmarc.example.net", &["v=DMARC1; p=reject"]),
+            (
+                "_dmarc.sub.example.net",
+                &["v=DMARC1; p=none", "v=DMARC1; p=quarantine"],
+            ),
+            ("s1._domainkey.example.com", &["v=DKIM1; k=rsa; p=MIIB"]),
+        ]);
+        assert_eq!(
+            lookup_dmarc(&dns, "Sub.Example.com").unwrap(),
+            Some(("example.com".into(), "v=DMARC1; p=reject".into()))
+        );
+        assert_eq!(lookup_dmarc(&dns, "example.org").unwrap(), None);
+        assert_eq!(lookup_dmarc(&dns, "sub.example.net").unwrap(), None);
+        assert_eq!(
+            lookup_spf(&dns, "example.com").unwrap().as_deref(),
+            Some("v=spf1 mx -all")
+        );
+        assert_eq!(lookup_spf(&dns, "example.org").unwrap(), None);
+        assert!(lookup_dkim(&dns, "s1", "example.com").unwrap().is_some());
+        assert!(lookup_dkim(&dns, "s2", "example.com").unwrap().is_none());
+
+        let published = lookup_published(&dns, "sub.example.com", &[]).unwrap();
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0].name, "_dmarc.example.com");
+        assert_eq!(published[1].record, None);
+    }
+}
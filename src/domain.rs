@@ -0,0 +1,88 @@
+use crate::dmarc::Alignment;
+
+/// Public suffixes with more than one label, under which organizations register their domains.
+///
+/// This is a small excerpt of the Public Suffix List covering common second-level registries.
+const MULTI_LABEL_SUFFIXES: &[&str] = &[
+    "ac.uk", "co.uk", "gov.uk", "ltd.uk", "me.uk", "net.uk", "org.uk", "plc.uk", "com.au",
+    "net.au", "org.au", "edu.au", "gov.au", "co.nz", "net.nz", "org.nz", "co.jp", "ne.jp", "or.jp",
+    "ac.jp", "go.jp", "com.br", "net.br", "org.br", "com.cn", "net.cn", "org.cn", "co.in",
+    "net.in", "org.in", "co.za", "org.za", "com.mx", "com.tr", "com.tw", "co.kr", "or.kr",
+    "com.sg", "com.hk", "co.il", "com.ar", "co.id", "com.my", "com.ph", "com.pl", "com.ua",
+];
+
+/// Normalizes a domain for comparison by lowercasing it and removing a trailing dot.
+pub fn normalize(domain: &str) -> String {
+    domain.trim().trim_end_matches('.').to_lowercase()
+}
+
+/// The organizational domain of a domain as defined by RFC 7489 Section 3.2.
+///
+/// The public suffix is determined with a built-in excerpt of the Public Suffix List, assuming a single
+/// label suffix for all other domains.
+pub fn organizational_domain(domain: &str) -> String {
+    let domain = normalize(domain);
+    let labels: Vec<&str> = domain.split('.').collect();
+    let suffix_labels = if labels.len() >= 2
+        && MULTI_LABEL_SUFFIXES.contains(&labels[labels.len() - 2..].join(".").as_str())
+    {
+        2
+    } else {
+        1
+    };
+    let keep = (suffix_labels + 1).min(labels.len());
+    labels[labels.len() - keep..].join(".")
+}
+
+/// Whether an authenticated domain is aligned with the RFC5322.From domain in the given mode.
+pub fn is_aligned(authenticated: &str, header_from: &str, mode: Alignment) -> bool {
+    match mode {
+        Alignment::Strict => normalize(authenticated) == normalize(header_from),
+        Alignment::Relaxed => {
+            organizational_domain(authenticated) == organizational_domain(header_from)
+        }
+    }
+}
+
+/// Whether `domain` equals `parent` or is one of its subdomains.
+pub fn is_subdomain_of(domain: &str, parent: &str) -> bool {
+    let domain = normalize(domain);
+    let parent = normalize(parent);
+    domain == parent || domain.ends_with(&format!(".{parent}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dmarc::Alignment;
+
+    use super::{is_aligned, is_subdomain_of, organizational_domain};
+
+    #[test]
+    fn organizational_domains() {
+        assert_eq!(organizational_domain("mail.example.com"), "example.com");
+        assert_eq!(organizational_domain("Example.COM."), "example.com");
+        assert_eq!(organizational_domain("a.b.example.co.uk"), "example.co.uk");
+        assert_eq!(organizational_domain("com"), "com");
+    }
+
+    #[test]
+    fn alignment() {
+        assert!(is_aligned(
+            "mail.example.com",
+            "example.com",
+            Alignment::Relaxed
+        ));
+        assert!(!is_aligned(
+            "mail.example.com",
+            "example.com",
+            Alignment::Strict
+        ));
+        assert!(!is_aligned(
+            "example.net",
+            "example.com",
+            Alignment::Relaxed
+        ));
+        assert!(is_subdomain_of("a.example.com", "example.com"));
+        assert!(!is_subdomain_of("badexample.com", "example.com"));
+    }
+}
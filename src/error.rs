@@ -0,0 +1,131 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use mailparse::MailParseError;
+use zip::result::ZipError;
+
+use crate::dkim::KeyError;
+use crate::dns::ResponseError;
+use crate::filter::FilterError;
+use crate::generate::OutcomeError;
+use crate::limits::LimitExceeded;
+use crate::record::RecordError;
+use crate::spf::SpfRecordError;
+use crate::zone::ZoneError;
+
+/// Errors that can occur while extracting and parsing reports.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    ParseMail(MailParseError),
+    NoSupportedAttachmentFound,
+    ReadZipArchive(ZipError),
+    ReadXmlFromZip(io::Error),
+    ReadXmlFromGzip(io::Error),
+    DecodeXml(&'static str),
+    ReadMboxFile(PathBuf, io::Error),
+    ReadInput(PathBuf, io::Error),
+    ParseDmarcReport(quick_xml::de::DeError),
+    LimitExceeded(LimitExceeded),
+    WriteQuarantine(PathBuf, io::Error),
+    WriteOutput(io::Error),
+    SerializeJson(serde_json::Error),
+    WriteFile(PathBuf, io::Error),
+    ParseDmarcRecord(RecordError),
+    ReadOutcomes(io::Error),
+    ParseCsv(csv::Error),
+    ParseJson(serde_json::Error),
+    ParseOutcome(OutcomeError),
+    QueryDns(String, io::Error),
+    DnsResponse(String, ResponseError),
+    ParseZone(usize, ZoneError),
+    ParseSpfRecord(SpfRecordError),
+    ParseDkimKey(KeyError),
+    InvalidNetwork(String),
+    InvalidFilter(FilterError),
+    InvalidReportUri(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ParseMail(e) => write!(f, "Could not parse email: {e}"),
+            Error::NoSupportedAttachmentFound => write!(f, "No supported attachement found"),
+            Error::ReadZipArchive(e) => write!(f, "Failed to extract ZIP file from email: {e}"),
+            Error::ReadXmlFromZip(e) => {
+                write!(f, "Unable to extract XML report from ZIP file: {e}")
+            }
+            Error::ReadXmlFromGzip(e) => {
+                write!(f, "Unable to extract XML report from GZIP file: {e}")
+            }
+            Error::DecodeXml(encoding) => write!(f, "Unable to decode XML report as {encoding}"),
+            Error::ReadMboxFile(path, e) => {
+                write!(f, "Could not read mbox file '{}': {}", path.display(), e)
+            }
+            Error::ReadInput(path, e) => {
+                write!(f, "Could not read input '{}': {}", path.display(), e)
+            }
+            Error::ParseDmarcReport(e) => write!(f, "Failed to parse XML as DMARC report: {e}"),
+            Error::LimitExceeded(e) => write!(f, "Resource limit exceeded: {e}"),
+            Error::WriteQuarantine(path, e) => {
+                write!(
+                    f,
+                    "Could not quarantine email to '{}': {}",
+                    path.display(),
+                    e
+                )
+            }
+            Error::WriteOutput(e) => write!(f, "Could not write output: {e}"),
+            Error::SerializeJson(e) => write!(f, "Could not serialize reports as JSON: {e}"),
+            Error::WriteFile(path, e) => {
+                write!(f, "Could not write file '{}': {}", path.display(), e)
+            }
+            Error::ParseDmarcRecord(e) => write!(f, "Invalid DMARC record: {e}"),
+            Error::ReadOutcomes(e) => write!(f, "Could not read message outcomes: {e}"),
+            Error::ParseCsv(e) => write!(f, "Could not parse message outcomes as CSV: {e}"),
+            Error::ParseJson(e) => write!(f, "Could not parse message outcomes as JSON: {e}"),
+            Error::ParseOutcome(e) => write!(f, "Could not determine message outcome: {e}"),
+            Error::QueryDns(name, e) => write!(f, "DNS query for '{name}' failed: {e}"),
+            Error::DnsResponse(name, e) => {
+                write!(f, "Invalid DNS response for '{name}': {e}")
+            }
+            Error::ParseZone(line, e) => write!(f, "Invalid zone file entry on line {line}: {e}"),
+            Error::ParseSpfRecord(e) => write!(f, "Invalid SPF record: {e}"),
+            Error::ParseDkimKey(e) => write!(f, "Invalid DKIM key record: {e}"),
+            Error::InvalidNetwork(network) => write!(f, "Invalid network '{network}'"),
+            Error::InvalidFilter(e) => write!(f, "Invalid filter: {e}"),
+            Error::InvalidReportUri(uri) => write!(f, "Unsupported report URI '{uri}'"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// A short name for the class of error, used to summarize failures.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::ParseMail(_) => "email",
+            Error::NoSupportedAttachmentFound => "no-attachment",
+            Error::ReadZipArchive(_) | Error::ReadXmlFromZip(_) => "zip",
+            Error::ReadXmlFromGzip(_) => "gzip",
+            Error::DecodeXml(_) => "encoding",
+            Error::ReadMboxFile(_, _) => "mbox",
+            Error::ParseDmarcReport(_) => "report",
+            Error::LimitExceeded(_) => "limit",
+            Error::WriteQuarantine(_, _) => "quarantine",
+            Error::ReadInput(_, _) | Error::InvalidNetwork(_) | Error::InvalidFilter(_) => "input",
+            Error::WriteOutput(_) | Error::SerializeJson(_) | Error::WriteFile(_, _) => "output",
+            Error::ParseDmarcRecord(_)
+            | Error::ParseSpfRecord(_)
+            | Error::ParseDkimKey(_)
+            | Error::InvalidReportUri(_) => "record",
+            Error::ReadOutcomes(_)
+            | Error::ParseCsv(_)
+            | Error::ParseJson(_)
+            | Error::ParseOutcome(_) => "outcome",
+            Error::QueryDns(_, _) | Error::DnsResponse(_, _) | Error::ParseZone(_, _) => "dns",
+        }
+    }
+}
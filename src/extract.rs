@@ -0,0 +1,603 @@
+use std::io::Cursor;
+
+use flate2::bufread::GzDecoder;
+use mailparse::parse_mail;
+use mailparse::{DispositionType, ParsedMail};
+use zip::ZipArchive;
+
+use crate::decode::decode_xml;
+use crate::diagnostics::{Diagnostics, ReportEncoding, SkippedPart};
+use crate::dmarc::Feedback;
+use crate::limits::{LimitExceeded, Limits};
+use crate::Error;
+
+/// The supported kinds of report attachments.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum AttachmentKind {
+    Zip,
+    Gzip,
+    Xml,
+}
+
+/// Detects the attachment kind from the leading bytes of its content.
+fn detect_by_magic(body: &[u8]) -> Option<AttachmentKind> {
+    if body.starts_with(b"PK\x03\x04") {
+        return Some(AttachmentKind::Zip);
+    }
+    if body.starts_with(&[0x1f, 0x8b]) {
+        return Some(AttachmentKind::Gzip);
+    }
+    let body = body.strip_prefix(b"\xef\xbb\xbf").unwrap_or(body);
+    let start = body.iter().position(|b| !b.is_ascii_whitespace())?;
+    let body = &body[start..];
+    if body.starts_with(b"<?xml") || body.starts_with(b"<feedback") {
+        return Some(AttachmentKind::Xml);
+    }
+    None
+}
+
+/// The file name of an email part, from its disposition or its MIME type.
+fn file_name(part: &ParsedMail) -> Option<String> {
+    let disposition = part.get_content_disposition();
+    disposition
+        .params
+        .get("filename")
+        .or_else(|| part.ctype.params.get("name"))
+        .cloned()
+}
+
+/// Detects the attachment kind from the file name or the declared MIME type of the email part.
+fn detect_by_metadata(part: &ParsedMail) -> Option<AttachmentKind> {
+    if let Some(filename) = file_name(part).map(|name| name.to_lowercase()) {
+        if filename.ends_with(".zip") {
+            return Some(AttachmentKind::Zip);
+        }
+        if filename.ends_with(".gz") || filename.ends_with(".gzip") {
+            return Some(AttachmentKind::Gzip);
+        }
+        if filename.ends_with(".xml") {
+            return Some(AttachmentKind::Xml);
+        }
+    }
+    match part.ctype.mimetype.as_str() {
+        "application/zip" | "application/x-zip-compressed" => Some(AttachmentKind::Zip),
+        "application/gzip" | "application/x-gzip" => Some(AttachmentKind::Gzip),
+        "text/xml" | "application/xml" => Some(AttachmentKind::Xml),
+        _ => None,
+    }
+}
+
+/// A file in a ZIP archive with its name, or the error reading it.
+type ZipEntry = (String, Result<Vec<u8>, Error>);
+
+/// Extracts the files contained in a ZIP archive, along with their names.
+///
+/// An unreadable archive fails as a whole, while an unreadable entry only fails that entry.
+fn decompress_zip(body: &[u8], limits: &Limits) -> Result<Vec<ZipEntry>, Error> {
+    let cursor = Cursor::new(body);
+    let mut archive = ZipArchive::new(cursor).map_err(Error::ReadZipArchive)?;
+    if archive.len() > limits.max_archive_entries {
+        return Err(Error::LimitExceeded(LimitExceeded::ArchiveEntries(
+            limits.max_archive_entries,
+        )));
+    }
+    let mut entries = vec![];
+    let mut budget = limits.max_decompressed_size;
+    for i in 0..archive.len() {
+        let zip_file = match archive.by_index(i) {
+            Ok(zip_file) => zip_file,
+            Err(e) => {
+                entries.push((format!("entry {i}"), Err(Error::ReadZipArchive(e))));
+                continue;
+            }
+        };
+        if zip_file.is_dir() {
+            continue;
+        }
+        let name = zip_file.name().to_string();
+        let compressed_size = zip_file.compressed_size();
+        let xml = limits.read_bounded(zip_file, compressed_size, budget, Error::ReadXmlFromZip);
+        if let Ok(xml) = &xml {
+            budget -= xml.len() as u64;
+        }
+        entries.push((name, xml));
+    }
+    Ok(entries)
+}
+
+/// Extracts the XML file contained in a GZIP file.
+fn decompress_gzip(body: &[u8], limits: &Limits) -> Result<Vec<u8>, Error> {
+    let cursor = Cursor::new(body);
+    let decoder = GzDecoder::new(cursor);
+    limits.read_bounded(
+        decoder,
+        body.len() as u64,
+        limits.max_decompressed_size,
+        Error::ReadXmlFromGzip,
+    )
+}
+
+/// The reports parsed from the parts of an email or attachment, and the parts which failed.
+#[derive(Default)]
+struct Extraction {
+    feedbacks: Vec<Feedback>,
+    skipped: Vec<SkippedPart>,
+}
+
+impl Extraction {
+    fn skip(&mut self, name: String, error: Error) {
+        self.skipped.push(SkippedPart { name, error });
+    }
+
+    /// Parses a report, skipping the part it was extracted from if that fails.
+    fn parse(&mut self, name: String, xml: &[u8], limits: &Limits, diagnostics: &mut Diagnostics) {
+        match parse_xml(xml, limits, diagnostics) {
+            Ok(feedback) => self.feedbacks.push(feedback),
+            Err(error) => self.skip(name, error),
+        }
+    }
+
+    /// Extracts and parses the reports of an attachment of the given kind.
+    fn attachment(
+        &mut self,
+        kind: AttachmentKind,
+        name: String,
+        body: &[u8],
+        limits: &Limits,
+        diagnostics: &mut Diagnostics,
+    ) {
+        match kind {
+            AttachmentKind::Zip => match decompress_zip(body, limits) {
+                Ok(entries) => {
+                    for (entry, xml) in entries {
+                        let entry = format!("{name}/{entry}");
+                        match xml {
+                            Ok(xml) => self.parse(entry, &xml, limits, diagnostics),
+                            Err(error) => self.skip(entry, error),
+                        }
+                    }
+                }
+                Err(error) => self.skip(name, error),
+            },
+            AttachmentKind::Gzip => match decompress_gzip(body, limits) {
+                Ok(xml) => self.parse(name, &xml, limits, diagnostics),
+                Err(error) => self.skip(name, error),
+            },
+            AttachmentKind::Xml if body.len() as u64 > limits.max_decompressed_size => {
+                let error = LimitExceeded::DecompressedSize(limits.max_decompressed_size);
+                self.skip(name, Error::LimitExceeded(error));
+            }
+            AttachmentKind::Xml => self.parse(name, body, limits, diagnostics),
+        }
+    }
+
+    /// Returns the parsed reports, or the error of the first failed part if there are none.
+    ///
+    /// If any report was parsed, the failed parts are recorded as skipped in the diagnostics.
+    fn finish(self, diagnostics: &mut Diagnostics) -> Result<Vec<Feedback>, Error> {
+        if self.feedbacks.is_empty() {
+            let error = self.skipped.into_iter().next().map(|part| part.error);
+            return Err(error.unwrap_or(Error::NoSupportedAttachmentFound));
+        }
+        diagnostics.skipped_parts.extend(self.skipped);
+        Ok(self.feedbacks)
+    }
+}
+
+/// Whether an email part may be an attachment, rather than the text of the email.
+fn is_attachment(part: &ParsedMail) -> bool {
+    let disposition = part.get_content_disposition();
+    matches!(disposition.disposition, DispositionType::Attachment)
+        || file_name(part).is_some()
+        || !matches!(part.ctype.mimetype.as_str(), "text/plain" | "text/html")
+}
+
+/// Extracts and parses the reports of all supported attachments, descending into embedded messages.
+///
+/// `depth` is the number of messages the email is embedded in.
+fn collect_reports(
+    parsed_mail: &ParsedMail,
+    depth: usize,
+    limits: &Limits,
+    extraction: &mut Extraction,
+    diagnostics: &mut Diagnostics,
+) {
+    for part in parsed_mail.parts() {
+        if !part.subparts.is_empty() {
+            // Multipart containers are visited through their subparts.
+            continue;
+        }
+        let name = file_name(part).unwrap_or_else(|| part.ctype.mimetype.clone());
+        let body = match part.get_body_raw() {
+            Ok(body) => body,
+            Err(e) => {
+                extraction.skip(name, Error::ParseMail(e));
+                continue;
+            }
+        };
+        if part.ctype.mimetype == "message/rfc822" {
+            if depth >= limits.max_message_depth {
+                let error = LimitExceeded::MessageDepth(limits.max_message_depth);
+                extraction.skip(name, Error::LimitExceeded(error));
+                continue;
+            }
+            match parse_mail(&body) {
+                Ok(embedded) => {
+                    collect_reports(&embedded, depth + 1, limits, extraction, diagnostics)
+                }
+                Err(e) => extraction.skip(name, Error::ParseMail(e)),
+            }
+            continue;
+        }
+        if !is_attachment(part) {
+            continue;
+        }
+        if let Some(kind) = detect_by_magic(&body).or_else(|| detect_by_metadata(part)) {
+            extraction.attachment(kind, name, &body, limits, diagnostics);
+        }
+    }
+}
+
+/// Decodes and parses a single XML report after checking it against the limits.
+pub fn parse_xml(
+    xml: &[u8],
+    limits: &Limits,
+    diagnostics: &mut Diagnostics,
+) -> Result<Feedback, Error> {
+    let (xml, encoding) = decode_xml(xml)?;
+    limits.check_xml(&xml)?;
+    let feedback: Feedback = quick_xml::de::from_str(&xml).map_err(Error::ParseDmarcReport)?;
+    diagnostics.encodings.push(ReportEncoding {
+        org_name: feedback.report_metadata.org_name.clone(),
+        report_id: feedback.report_metadata.report_id.clone(),
+        encoding,
+    });
+    Ok(feedback)
+}
+
+/// Extracts and parses the reports of an attachment, detecting its kind by content.
+///
+/// Entries of an archive which cannot be parsed are skipped, unless no report can be parsed at all.
+pub fn process_attachment(
+    content: &[u8],
+    limits: &Limits,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<Feedback>, Error> {
+    let kind = detect_by_magic(content).ok_or(Error::NoSupportedAttachmentFound)?;
+    let mut extraction = Extraction::default();
+    extraction.attachment(kind, "attachment".into(), content, limits, diagnostics);
+    extraction.finish(diagnostics)
+}
+
+/// Extracts and parses the reports of all supported attachments of an email.
+///
+/// Parts which cannot be parsed are skipped, unless no report can be parsed at all.
+pub fn process_email(
+    parsed_mail: &ParsedMail,
+    limits: &Limits,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<Feedback>, Error> {
+    let mut extraction = Extraction::default();
+    collect_reports(parsed_mail, 0, limits, &mut extraction, diagnostics);
+    extraction.finish(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use mailparse::parse_mail;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    use crate::diagnostics::Diagnostics;
+    use crate::limits::{LimitExceeded, Limits};
+    use crate::Error;
+
+    use super::{
+        decompress_gzip, decompress_zip, detect_by_magic, parse_xml, process_attachment,
+        process_email, AttachmentKind,
+    };
+
+    const REPORT: &str = "<?xml version=\"1.0\"?>
+<feedback>
+  <report_metadata>
+    <org_name>example.org</org_name>
+    <email>dmarc@example.org</email>
+    <report_id>1</report_id>
+    <date_range><begin>1700000000</begin><end>1700086400</end></date_range>
+  </report_metadata>
+  <policy_published><domain>example.com</domain><p>none</p></policy_published>
+  <record>
+    <row>
+      <source_ip>192.0.2.1</source_ip>
+      <count>1</count>
+      <policy_evaluated><disposition>none</disposition><dkim>pass</dkim><spf>pass</spf></policy_evaluated>
+    </row>
+    <identifiers><header_from>example.com</header_from></identifiers>
+    <auth_results><spf><domain>example.com</domain><result>pass</result></spf></auth_results>
+  </record>
+</feedback>";
+
+    #[test]
+    fn detect_attachment_kind_by_magic() {
+        assert_eq!(
+            detect_by_magic(b"PK\x03\x04rest"),
+            Some(AttachmentKind::Zip)
+        );
+        assert_eq!(
+            detect_by_magic(&[0x1f, 0x8b, 0x08]),
+            Some(AttachmentKind::Gzip)
+        );
+        assert_eq!(
+            detect_by_magic(b"\xef\xbb\xbf\n<?xml version=\"1.0\"?>"),
+            Some(AttachmentKind::Xml)
+        );
+        assert_eq!(detect_by_magic(b"<feedback>"), Some(AttachmentKind::Xml));
+        assert_eq!(detect_by_magic(b"Hello"), None);
+    }
+
+    #[test]
+    fn process_uncompressed_xml_as_octet_stream() {
+        let email = format!(
+            "Subject: Report\r
+Content-Type: multipart/mixed; boundary=\"b\"\r
+\r
+--b\r
+Content-Type: text/plain\r
+\r
+A report is attached.\r
+--b\r
+Content-Type: application/octet-stream\r
+Content-Disposition: attachment; filename=\"report.xml\"\r
+\r
+{REPORT}\r
+--b--\r
+"
+        );
+        let parsed_mail = parse_mail(email.as_bytes()).unwrap();
+        let feedbacks = process_email(
+            &parsed_mail,
+            &Limits::default(),
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+        assert_eq!(feedbacks.len(), 1);
+        assert_eq!(feedbacks[0].report_metadata.org_name, "example.org");
+    }
+
+    #[test]
+    fn process_forwarded_message() {
+        let email = format!(
+            "Subject: Fwd: Report\r
+Content-Type: multipart/mixed; boundary=\"outer\"\r
+\r
+--outer\r
+Content-Type: message/rfc822\r
+\r
+Subject: Report\r
+Content-Type: multipart/mixed; boundary=\"inner\"\r
+\r
+--inner\r
+Content-Type: text/xml\r
+\r
+{REPORT}\r
+--inner--\r
+--outer--\r
+"
+        );
+        let parsed_mail = parse_mail(email.as_bytes()).unwrap();
+        let feedbacks = process_email(
+            &parsed_mail,
+            &Limits::default(),
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+        assert_eq!(feedbacks.len(), 1);
+    }
+
+    #[test]
+    fn reject_deeply_forwarded_message() {
+        let mut email = format!("Content-Type: text/xml\r\n\r\n{REPORT}\r\n");
+        for _ in 0..3 {
+            email = format!("Subject: Fwd\r\nContent-Type: message/rfc822\r\n\r\n{email}");
+        }
+        let parsed_mail = parse_mail(email.as_bytes()).unwrap();
+        let process = |max_message_depth| {
+            let limits = Limits {
+                max_message_depth,
+                ..Default::default()
+            };
+            process_email(&parsed_mail, &limits, &mut Diagnostics::default())
+        };
+        assert_eq!(process(3).unwrap().len(), 1);
+        assert!(matches!(
+            process(2),
+            Err(Error::LimitExceeded(LimitExceeded::MessageDepth(2)))
+        ));
+    }
+
+    fn zip(files: &[&str]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        for (i, content) in files.iter().enumerate() {
+            writer
+                .start_file(format!("{i}.xml"), SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn decompress_all_zip_entries() {
+        let archive = zip(&["<a/>", "<b/>"]);
+        let entries = decompress_zip(&archive, &Limits::default()).unwrap();
+        let entries: Vec<(&str, &[u8])> = entries
+            .iter()
+            .map(|(name, xml)| (name.as_str(), xml.as_deref().unwrap()))
+            .collect();
+        assert_eq!(entries, [("0.xml", &b"<a/>"[..]), ("1.xml", &b"<b/>"[..])]);
+
+        let limits = Limits {
+            max_archive_entries: 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            decompress_zip(&archive, &limits),
+            Err(Error::LimitExceeded(LimitExceeded::ArchiveEntries(1)))
+        ));
+    }
+
+    #[test]
+    fn process_two_attachments() {
+        let second = REPORT.replace("<report_id>1</report_id>", "<report_id>2</report_id>");
+        let email = format!(
+            "Subject: Reports\r
+Content-Type: multipart/mixed; boundary=\"b\"\r
+\r
+--b\r
+Content-Type: text/xml\r
+Content-Disposition: attachment; filename=\"1.xml\"\r
+\r
+{REPORT}\r
+--b\r
+Content-Type: text/xml\r
+Content-Disposition: attachment; filename=\"2.xml\"\r
+\r
+{second}\r
+--b--\r
+"
+        );
+        let parsed_mail = parse_mail(email.as_bytes()).unwrap();
+        let feedbacks = process_email(
+            &parsed_mail,
+            &Limits::default(),
+            &mut Diagnostics::default(),
+        )
+        .unwrap();
+        let ids: Vec<&str> = feedbacks
+            .iter()
+            .map(|feedback| feedback.report_metadata.report_id.as_str())
+            .collect();
+        assert_eq!(ids, ["1", "2"]);
+    }
+
+    #[test]
+    fn skip_broken_attachment() {
+        let email = format!(
+            "Subject: Reports\r
+Content-Type: multipart/mixed; boundary=\"b\"\r
+\r
+--b\r
+Content-Type: text/xml\r
+Content-Disposition: attachment; filename=\"broken.xml\"\r
+\r
+<feedback>\r
+--b\r
+Content-Type: text/xml\r
+Content-Disposition: attachment; filename=\"report.xml\"\r
+\r
+{REPORT}\r
+--b--\r
+"
+        );
+        let parsed_mail = parse_mail(email.as_bytes()).unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let feedbacks = process_email(&parsed_mail, &Limits::default(), &mut diagnostics).unwrap();
+        assert_eq!(feedbacks.len(), 1);
+        let skipped = &diagnostics.skipped_parts[0];
+        assert_eq!(skipped.name, "broken.xml");
+        assert!(matches!(skipped.error, Error::ParseDmarcReport(_)));
+
+        let broken = parse_mail(b"Content-Type: text/xml\r\n\r\n<feedback>").unwrap();
+        let mut diagnostics = Diagnostics::default();
+        assert!(matches!(
+            process_email(&broken, &Limits::default(), &mut diagnostics),
+            Err(Error::ParseDmarcReport(_))
+        ));
+        assert!(diagnostics.skipped_parts.is_empty());
+    }
+
+    #[test]
+    fn skip_non_xml_zip_entry() {
+        let archive = zip(&[REPORT, "Hello"]);
+        let mut diagnostics = Diagnostics::default();
+        let feedbacks = process_attachment(&archive, &Limits::default(), &mut diagnostics).unwrap();
+        assert_eq!(feedbacks.len(), 1);
+        assert_eq!(diagnostics.skipped_parts[0].name, "attachment/1.xml");
+    }
+
+    #[test]
+    fn ignore_xml_text_body() {
+        let email = format!(
+            "Subject: Report\r
+Content-Type: multipart/mixed; boundary=\"b\"\r
+\r
+--b\r
+Content-Type: text/plain\r
+\r
+<?xml is how a report starts.\r
+--b\r
+Content-Type: application/xml\r
+Content-Disposition: attachment; filename=\"report.xml\"\r
+\r
+{REPORT}\r
+--b--\r
+"
+        );
+        let parsed_mail = parse_mail(email.as_bytes()).unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let feedbacks = process_email(&parsed_mail, &Limits::default(), &mut diagnostics).unwrap();
+        assert_eq!(feedbacks.len(), 1);
+        assert!(diagnostics.skipped_parts.is_empty());
+    }
+
+    #[test]
+    fn process_zip_with_two_reports() {
+        let second = REPORT.replace("<report_id>1</report_id>", "<report_id>2</report_id>");
+        let archive = BASE64_STANDARD.encode(zip(&[REPORT, &second]));
+        let email = format!(
+            "Subject: Reports\r
+Content-Type: application/zip; name=\"reports.zip\"\r
+Content-Transfer-Encoding: base64\r
+\r
+{archive}\r
+"
+        );
+        let parsed_mail = parse_mail(email.as_bytes()).unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let feedbacks = process_email(&parsed_mail, &Limits::default(), &mut diagnostics).unwrap();
+        assert_eq!(feedbacks.len(), 2);
+        assert_eq!(feedbacks[1].report_metadata.report_id, "2");
+        assert_eq!(diagnostics.encodings.len(), 2);
+    }
+
+    #[test]
+    fn reject_gzip_bomb() {
+        let mut encoder = GzEncoder::new(vec![], Compression::best());
+        encoder.write_all(&vec![0; 10 * 1024 * 1024]).unwrap();
+        let bomb = encoder.finish().unwrap();
+        assert!(matches!(
+            decompress_gzip(&bomb, &Limits::default()),
+            Err(Error::LimitExceeded(LimitExceeded::CompressionRatio(_)))
+        ));
+    }
+
+    #[test]
+    fn parse_latin1_report() {
+        let xml = REPORT
+            .replace(
+                "<?xml version=\"1.0\"?>",
+                "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>",
+            )
+            .replace("example.org", "exämple.org");
+        let (latin1, _, _) = encoding_rs::WINDOWS_1252.encode(&xml);
+        let mut diagnostics = Diagnostics::default();
+        let feedback = parse_xml(&latin1, &Limits::default(), &mut diagnostics).unwrap();
+        assert_eq!(feedback.report_metadata.org_name, "exämple.org");
+        assert_eq!(diagnostics.encodings[0].encoding, encoding_rs::WINDOWS_1252);
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::dmarc::{Disposition, Record};
+use crate::domain::is_subdomain_of;
+use crate::spf::Network;
+use crate::{Error, Feedback};
+
+/// Why a criterion could not be parsed.
+#[derive(Debug, PartialEq)]
+pub enum FilterError {
+    UnknownDisposition(String),
+    InvalidDate(String),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::UnknownDisposition(name) => write!(f, "unknown disposition '{name}'"),
+            FilterError::InvalidDate(date) => write!(f, "invalid date '{date}'"),
+        }
+    }
+}
+
+/// Parses the name of a disposition as used in reports.
+pub fn parse_disposition(name: &str) -> Result<Disposition, Error> {
+    match name.trim().to_lowercase().as_str() {
+        "none" => Ok(Disposition::None),
+        "quarantine" => Ok(Disposition::Quarantine),
+        "reject" => Ok(Disposition::Reject),
+        _ => Err(Error::InvalidFilter(FilterError::UnknownDisposition(
+            name.into(),
+        ))),
+    }
+}
+
+fn start_of(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc()
+}
+
+/// Criteria selecting reports and records.
+///
+/// Reports are selected by reporter and date range, records by domain, source IP and disposition.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    /// A policy or RFC5322.From domain, including its subdomains.
+    pub domain: Option<String>,
+    /// The reporting organization, compared case-insensitively.
+    pub reporter: Option<String>,
+    /// The network the messages were sent from.
+    pub source: Option<Network>,
+    /// The disposition applied to the messages.
+    pub disposition: Option<Disposition>,
+    /// The first day covered by the reports.
+    pub since: Option<NaiveDate>,
+    /// The last day covered by the reports.
+    pub until: Option<NaiveDate>,
+}
+
+impl Filter {
+    /// Sets the criterion named `key` from a string, as given in a query string.
+    ///
+    /// Returns `false` if there is no criterion with that name.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<bool, Error> {
+        let date = |value: &str| {
+            value
+                .parse::<NaiveDate>()
+                .map_err(|_| Error::InvalidFilter(FilterError::InvalidDate(value.into())))
+        };
+        match key {
+            "domain" => self.domain = Some(value.to_string()),
+            "reporter" => self.reporter = Some(value.to_string()),
+            "source" => self.source = Some(value.parse()?),
+            "disposition" => self.disposition = Some(parse_disposition(value)?),
+            "since" => self.since = Some(date(value)?),
+            "until" => self.until = Some(date(value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn selects_records(&self) -> bool {
+        self.domain.is_some() || self.source.is_some() || self.disposition.is_some()
+    }
+
+    /// Whether a report is selected by reporter and the days it covers.
+    pub fn matches_report(&self, feedback: &Feedback) -> bool {
+        let metadata = &feedback.report_metadata;
+        let reporter = self
+            .reporter
+            .as_ref()
+            .is_none_or(|reporter| metadata.org_name.eq_ignore_ascii_case(reporter));
+        let since = self
+            .since
+            .is_none_or(|since| metadata.date_range.end > start_of(since));
+        let until = self.until.is_none_or(|until| {
+            until
+                .succ_opt()
+                .is_none_or(|next| metadata.date_range.begin < start_of(next))
+        });
+        reporter && since && until
+    }
+
+    /// Whether a record of a report is selected by domain, source IP and disposition.
+    pub fn matches_record(&self, feedback: &Feedback, record: &Record) -> bool {
+        let domain = self.domain.as_ref().is_none_or(|domain| {
+            is_subdomain_of(&record.identifiers.header_from, domain)
+                || is_subdomain_of(&feedback.policy_published.domain, domain)
+        });
+        let source = self
+            .source
+            .is_none_or(|network| network.contains(record.row.source_ip));
+        let disposition = self
+            .disposition
+            .is_none_or(|disposition| record.row.policy_evaluated.disposition == disposition);
+        domain && source && disposition
+    }
+
+    /// Selects the matching reports with their matching records.
+    ///
+    /// Reports without matching records are left out if records are filtered.
+    pub fn apply(&self, feedbacks: &[Feedback]) -> Vec<Feedback> {
+        feedbacks
+            .iter()
+            .filter(|feedback| self.matches_report(feedback))
+            .filter_map(|feedback| {
+                let mut feedback = feedback.clone();
+                let records = std::mem::take(&mut feedback.records);
+                feedback.records = records
+                    .into_iter()
+                    .filter(|record| self.matches_record(&feedback, record))
+                    .collect();
+                (!feedback.records.is_empty() || !self.selects_records()).then_some(feedback)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+
+    use super::Filter;
+
+    #[test]
+    fn filter_reports_and_records() {
+        let feedback = fixtures::report();
+        let feedbacks = [feedback.clone()];
+        assert_eq!(Filter::default().apply(&feedbacks), feedbacks);
+
+        let mut filter = Filter::default();
+        assert!(filter
+            .set(
+                "reporter",
+                &feedback.report_metadata.org_name.to_uppercase()
+            )
+            .unwrap());
+        assert!(!filter.set("page", "2").unwrap());
+        assert_eq!(filter.apply(&feedbacks).len(), 1);
+
+        let begin = feedback.report_metadata.date_range.begin.date_naive();
+        filter
+            .set("until", &begin.pred_opt().unwrap().to_string())
+            .unwrap();
+        assert!(filter.apply(&feedbacks).is_empty());
+        filter.set("until", &begin.to_string()).unwrap();
+        assert_eq!(filter.apply(&feedbacks).len(), 1);
+
+        let disposition = feedback.records[0].row.policy_evaluated.disposition;
+        filter.set("disposition", disposition.as_str()).unwrap();
+        let expected = feedback
+            .records
+            .iter()
+            .filter(|record| record.row.policy_evaluated.disposition == disposition)
+            .count();
+        assert_eq!(filter.apply(&feedbacks)[0].records.len(), expected);
+
+        filter.set("source", "0.0.0.0/32").unwrap();
+        assert!(filter.apply(&feedbacks).is_empty());
+        assert!(filter.set("disposition", "discard").is_err());
+        assert!(filter.set("since", "yesterday").is_err());
+        assert_eq!(filter.disposition, Some(disposition));
+    }
+}
@@ -0,0 +1,15 @@
+//! Test data built from the report fixtures in `tests/fixtures`.
+
+use crate::{from_xml_str, Feedback, Limits};
+
+/// The report of `full.xml`.
+///
+/// Its first record is forwarded mail from `192.0.2.10` with an aligned DKIM pass, the second a
+/// failing record from an IPv6 address.
+pub fn report() -> Feedback {
+    from_xml_str(
+        include_str!("../tests/fixtures/full.xml"),
+        &Limits::default(),
+    )
+    .unwrap()
+}
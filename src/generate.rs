@@ -0,0 +1,636 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use chrono::serde::ts_seconds;
+use chrono::{DateTime, TimeDelta, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use mailparse::{dateparse, parse_headers, MailHeaderMap};
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+
+use crate::authres::{domain_of, AuthenticationResults};
+use crate::dmarc::{
+    AuthResult, DateRange, Disposition, DkimAuthResult, DkimResult, DmarcResult, Identifier,
+    PolicyEvaluated, PolicyOverride, PolicyOverrideReason, Record, ReportMetadata, Row,
+    SpfAuthResult, SpfDomainScope, SpfResult,
+};
+use crate::domain::{is_aligned, normalize, organizational_domain};
+use crate::hash::fnv1a;
+use crate::record::DmarcRecord;
+use crate::xml;
+use crate::{Error, Feedback};
+
+/// The authentication outcome of a single message received by the reporting system.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MessageOutcome {
+    /// When the message was received, in seconds since epoch.
+    #[serde(with = "ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+    /// The connecting IP.
+    pub source_ip: IpAddr,
+    /// The RFC5322.From domain.
+    pub header_from: String,
+    /// The RFC5321.MailFrom domain.
+    #[serde(default)]
+    pub envelope_from: Option<String>,
+    /// The envelope recipient domain.
+    #[serde(default)]
+    pub envelope_to: Option<String>,
+    #[serde(default)]
+    pub dkim: Vec<DkimAuthResult>,
+    #[serde(default)]
+    pub spf: Vec<SpfAuthResult>,
+    /// The disposition applied to the message, derived from the policy if not known.
+    #[serde(default)]
+    pub disposition: Option<Disposition>,
+    /// Reasons for applying a different disposition than the policy requests.
+    #[serde(default)]
+    pub reasons: Vec<PolicyOverrideReason>,
+}
+
+/// Why the outcome of a message could not be determined.
+#[derive(Debug, PartialEq)]
+pub enum OutcomeError {
+    InvalidTimestamp(i64),
+    MissingClientIp,
+    MissingDate,
+    MissingFrom,
+}
+
+impl fmt::Display for OutcomeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutcomeError::InvalidTimestamp(timestamp) => write!(f, "invalid timestamp {timestamp}"),
+            OutcomeError::MissingClientIp => write!(f, "no client IP in authentication results"),
+            OutcomeError::MissingDate => write!(f, "missing or invalid Received and Date headers"),
+            OutcomeError::MissingFrom => write!(f, "missing From header"),
+        }
+    }
+}
+
+/// A message outcome as a flat CSV row, supporting a single DKIM and SPF result.
+#[derive(Deserialize)]
+struct CsvOutcome {
+    timestamp: i64,
+    source_ip: IpAddr,
+    header_from: String,
+    envelope_from: Option<String>,
+    envelope_to: Option<String>,
+    dkim_domain: Option<String>,
+    dkim_selector: Option<String>,
+    dkim_result: Option<DkimResult>,
+    spf_domain: Option<String>,
+    spf_scope: Option<SpfDomainScope>,
+    spf_result: Option<SpfResult>,
+    disposition: Option<Disposition>,
+}
+
+impl TryFrom<CsvOutcome> for MessageOutcome {
+    type Error = Error;
+
+    fn try_from(row: CsvOutcome) -> Result<Self, Error> {
+        let timestamp = DateTime::from_timestamp(row.timestamp, 0).ok_or(Error::ParseOutcome(
+            OutcomeError::InvalidTimestamp(row.timestamp),
+        ))?;
+        let dkim = match (row.dkim_domain, row.dkim_result) {
+            (Some(domain), Some(result)) => vec![DkimAuthResult {
+                domain,
+                selector: row.dkim_selector,
+                result,
+                human_result: None,
+            }],
+            _ => vec![],
+        };
+        let spf = match (row.spf_domain, row.spf_result) {
+            (Some(domain), Some(result)) => vec![SpfAuthResult {
+                domain,
+                scope: row.spf_scope,
+                result,
+            }],
+            _ => vec![],
+        };
+        Ok(Self {
+            timestamp,
+            source_ip: row.source_ip,
+            header_from: row.header_from,
+            envelope_from: row.envelope_from,
+            envelope_to: row.envelope_to,
+            dkim,
+            spf,
+            disposition: row.disposition,
+            reasons: vec![],
+        })
+    }
+}
+
+/// Reads message outcomes from CSV with a header row.
+///
+/// The columns are `timestamp`, `source_ip`, `header_from`, `envelope_from`, `envelope_to`,
+/// `dkim_domain`, `dkim_selector`, `dkim_result`, `spf_domain`, `spf_scope`, `spf_result` and
+/// `disposition`, of which only the first three are required to be non-empty.
+pub fn read_csv(reader: impl Read) -> Result<Vec<MessageOutcome>, Error> {
+    csv::Reader::from_reader(reader)
+        .deserialize::<CsvOutcome>()
+        .map(|row| row.map_err(Error::ParseCsv)?.try_into())
+        .collect()
+}
+
+/// Reads message outcomes from a JSON array or from JSON lines.
+pub fn read_json(reader: impl Read) -> Result<Vec<MessageOutcome>, Error> {
+    let mut reader = BufReader::new(reader);
+    let is_array = reader
+        .fill_buf()
+        .map_err(Error::ReadOutcomes)?
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        == Some(&b'[');
+    if is_array {
+        serde_json::from_reader(reader).map_err(Error::ParseJson)
+    } else {
+        serde_json::Deserializer::from_reader(reader)
+            .into_iter()
+            .map(|outcome| outcome.map_err(Error::ParseJson))
+            .collect()
+    }
+}
+
+/// Parses a result name using the names of the report schema.
+fn parse_result<'de, T: Deserialize<'de>>(result: &'de str) -> Option<T> {
+    T::deserialize(result.into_deserializer())
+        .map_err(|_: serde::de::value::Error| ())
+        .ok()
+}
+
+/// Derives the outcome of a received message from its Authentication-Results header.
+///
+/// Only headers added by the given authentication server are trusted, if one is given, as
+/// recommended by RFC 8601 Section 5. Returns `None` if the email has no usable header.
+pub fn outcome_from_email(
+    email: &[u8],
+    authserv_id: Option<&str>,
+) -> Result<Option<MessageOutcome>, Error> {
+    let (headers, _) = parse_headers(email).map_err(Error::ParseMail)?;
+    let Some(results) = headers
+        .get_all_values("Authentication-Results")
+        .iter()
+        .filter_map(|header| AuthenticationResults::parse(header))
+        .find(|results| authserv_id.is_none_or(|id| results.authserv_id.eq_ignore_ascii_case(id)))
+    else {
+        return Ok(None);
+    };
+
+    let source_ip = results.client_ip().or_else(|| {
+        headers
+            .get_first_value("Received-SPF")
+            .and_then(|spf| AuthenticationResults::parse(&format!("spf; spf={spf}")))
+            .and_then(|spf| {
+                let spf = spf.method("spf").next()?;
+                spf.property("client-ip")?.parse().ok()
+            })
+    });
+    let Some(source_ip) = source_ip else {
+        return Err(Error::ParseOutcome(OutcomeError::MissingClientIp));
+    };
+    // The topmost Received header is added on delivery, while the Date header is chosen by the
+    // sender and only used if the message was not stamped on delivery.
+    let received = headers
+        .get_first_value("Received")
+        .and_then(|received| dateparse(received.rsplit_once(';')?.1).ok());
+    let timestamp = received
+        .or_else(|| {
+            let date = headers.get_first_value("Date")?;
+            dateparse(&date).ok()
+        })
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .ok_or(Error::ParseOutcome(OutcomeError::MissingDate))?;
+    let header_from = results
+        .method("dmarc")
+        .find_map(|dmarc| dmarc.property("header.from"))
+        .map(String::from)
+        .or_else(|| {
+            headers
+                .get_first_value("From")
+                .map(|from| domain_of(&from).to_lowercase())
+        })
+        .ok_or(Error::ParseOutcome(OutcomeError::MissingFrom))?;
+
+    let dkim = results
+        .method("dkim")
+        .filter_map(|dkim| {
+            let domain = dkim
+                .property("header.d")
+                .or_else(|| dkim.property("header.i").map(domain_of))?;
+            Some(DkimAuthResult {
+                domain: domain.to_lowercase(),
+                selector: dkim.property("header.s").map(String::from),
+                result: parse_result(&dkim.result)?,
+                human_result: None,
+            })
+        })
+        .collect();
+    let mut envelope_from = None;
+    let spf = results
+        .method("spf")
+        .filter_map(|spf| {
+            let (domain, scope) = match spf.property("smtp.mailfrom") {
+                Some(mailfrom) => {
+                    let domain = domain_of(mailfrom).to_lowercase();
+                    envelope_from = Some(domain.clone());
+                    (domain, SpfDomainScope::MFrom)
+                }
+                None => (
+                    spf.property("smtp.helo")?.to_lowercase(),
+                    SpfDomainScope::Helo,
+                ),
+            };
+            Some(SpfAuthResult {
+                domain,
+                scope: Some(scope),
+                result: parse_result(&spf.result)?,
+            })
+        })
+        .collect();
+
+    Ok(Some(MessageOutcome {
+        timestamp,
+        source_ip,
+        header_from,
+        envelope_from,
+        envelope_to: None,
+        dkim,
+        spf,
+        disposition: None,
+        reasons: vec![],
+    }))
+}
+
+/// Information about the organization generating the reports.
+#[derive(Debug, Clone)]
+pub struct Reporter {
+    /// The domain of the reporting organization, also used as receiver in file names.
+    pub org_name: String,
+    /// The contact address of the reporting organization.
+    pub email: String,
+    pub extra_contact_info: Option<String>,
+}
+
+/// The DMARC records known for policy domains.
+#[derive(Debug, Default)]
+pub struct Policies(BTreeMap<String, DmarcRecord>);
+
+impl Policies {
+    pub fn insert(&mut self, domain: &str, record: DmarcRecord) {
+        self.0.insert(normalize(domain), record);
+    }
+
+    /// Finds the policy domain and record applying to an RFC5322.From domain.
+    ///
+    /// As in RFC 7489 Section 6.6.3, the record of the domain itself is preferred over the record
+    /// of its organizational domain.
+    pub fn lookup(&self, header_from: &str) -> Option<(&str, &DmarcRecord)> {
+        let domain = normalize(header_from);
+        self.0
+            .get_key_value(&domain)
+            .or_else(|| self.0.get_key_value(&organizational_domain(&domain)))
+            .map(|(domain, record)| (domain.as_str(), record))
+    }
+}
+
+/// Whether a failing message is excluded from the policy by `pct`.
+///
+/// The sample is chosen by hashing the message, so that generating reports again from the same
+/// outcomes gives the same result.
+fn sampled_out(outcome: &MessageOutcome, pct: u8) -> bool {
+    if pct >= 100 {
+        return false;
+    }
+    let hash = fnv1a(&[
+        &outcome.timestamp.timestamp().to_be_bytes(),
+        outcome.source_ip.to_string().as_bytes(),
+        outcome.header_from.as_bytes(),
+        outcome
+            .envelope_from
+            .as_deref()
+            .unwrap_or_default()
+            .as_bytes(),
+    ]);
+    hash % 100 >= u64::from(pct)
+}
+
+/// Evaluates DMARC for a message as described in RFC 7489 Section 6.6.
+///
+/// Unless the outcome records the disposition applied, failing messages excluded by `pct` get the
+/// next less strict disposition and the `sampled_out` reason, as in Section 6.6.4.
+fn evaluate(outcome: &MessageOutcome, domain: &str, record: &DmarcRecord) -> PolicyEvaluated {
+    let header_from = &outcome.header_from;
+    let dkim = outcome.dkim.iter().any(|dkim| {
+        dkim.result == DkimResult::Pass && is_aligned(&dkim.domain, header_from, record.adkim)
+    });
+    // Only the MAIL FROM identity is relevant for DMARC.
+    let spf = outcome.spf.iter().any(|spf| {
+        spf.result == SpfResult::Pass
+            && spf.scope != Some(SpfDomainScope::Helo)
+            && is_aligned(&spf.domain, header_from, record.aspf)
+    });
+    let mut reasons = outcome.reasons.clone();
+    let disposition = match outcome.disposition {
+        Some(disposition) => disposition,
+        None if dkim || spf => Disposition::None,
+        None => {
+            let requested = if normalize(header_from) == domain {
+                record.p
+            } else {
+                record.sp.unwrap_or(record.p)
+            };
+            if sampled_out(outcome, record.pct) {
+                reasons.push(PolicyOverrideReason {
+                    typ: PolicyOverride::SampledOut,
+                    comment: None,
+                });
+                requested.relaxed()
+            } else {
+                requested
+            }
+        }
+    };
+    let result = |pass| match pass {
+        true => DmarcResult::Pass,
+        false => DmarcResult::Fail,
+    };
+    PolicyEvaluated {
+        disposition,
+        dkim: result(dkim),
+        spf: result(spf),
+        reasons,
+    }
+}
+
+/// The records of a single report under construction.
+#[derive(Default)]
+struct ReportRecords {
+    records: Vec<Record>,
+    /// Index of each distinct record, keyed by the record with a count of zero.
+    index: HashMap<Record, usize>,
+}
+
+/// The outcome of generating reports.
+#[derive(Debug, Default)]
+pub struct Generated {
+    pub feedbacks: Vec<Feedback>,
+    /// The number of messages skipped because no DMARC record is known for their domain.
+    pub skipped: usize,
+}
+
+/// A report ID for the report of a policy domain and day.
+///
+/// The ID is a hex digest, as domains may contain characters not allowed in the `ridtxt` of
+/// RFC 7489 Section 7.2.1.1.
+fn report_id(domain: &str, begin: DateTime<Utc>) -> String {
+    let hash = fnv1a(&[domain.as_bytes(), &begin.timestamp().to_be_bytes()]);
+    format!("{hash:016x}")
+}
+
+/// Groups message outcomes into one aggregate report per policy domain and UTC day.
+pub fn generate(
+    outcomes: &[MessageOutcome],
+    policies: &Policies,
+    reporter: &Reporter,
+) -> Generated {
+    let mut reports: BTreeMap<(String, i64), ReportRecords> = BTreeMap::new();
+    let mut skipped = 0;
+    for outcome in outcomes {
+        let Some((domain, record)) = policies.lookup(&outcome.header_from) else {
+            skipped += 1;
+            continue;
+        };
+        let day = outcome.timestamp.timestamp().div_euclid(86400);
+        let mut spf = outcome.spf.clone();
+        if spf.is_empty() {
+            // The schema requires at least one SPF result.
+            spf.push(SpfAuthResult {
+                domain: outcome
+                    .envelope_from
+                    .clone()
+                    .unwrap_or_else(|| outcome.header_from.clone()),
+                scope: Some(SpfDomainScope::MFrom),
+                result: SpfResult::None,
+            });
+        }
+        let key = Record {
+            row: Row {
+                source_ip: outcome.source_ip,
+                count: 0,
+                policy_evaluated: evaluate(outcome, domain, record),
+            },
+            identifiers: Identifier {
+                envelope_to: outcome.envelope_to.clone(),
+                envelope_from: outcome.envelope_from.clone(),
+                header_from: normalize(&outcome.header_from),
+            },
+            auth_results: AuthResult {
+                dkim: outcome.dkim.clone(),
+                spf,
+            },
+        };
+        let report = reports.entry((domain.to_string(), day)).or_default();
+        let index = *report.index.entry(key.clone()).or_insert_with(|| {
+            report.records.push(key);
+            report.records.len() - 1
+        });
+        report.records[index].row.count += 1;
+    }
+
+    let feedbacks = reports
+        .into_iter()
+        .map(|((domain, day), report)| {
+            let begin = DateTime::from_timestamp(day * 86400, 0).unwrap_or_default();
+            let end = begin + TimeDelta::seconds(86399);
+            let (_, record) = policies.lookup(&domain).expect("policy domain is known");
+            Feedback {
+                version: Some(1.0),
+                report_metadata: ReportMetadata {
+                    org_name: reporter.org_name.clone(),
+                    email: reporter.email.clone(),
+                    extra_contact_info: reporter.extra_contact_info.clone(),
+                    report_id: report_id(&domain, begin),
+                    date_range: DateRange { begin, end },
+                    errors: vec![],
+                },
+                policy_published: record.policy_published(&domain),
+                records: report.records,
+            }
+        })
+        .collect();
+    Generated { feedbacks, skipped }
+}
+
+/// Compresses a report as GZIP compressed XML.
+pub fn to_gzip(feedback: &Feedback) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    xml::write_xml(feedback, &mut encoder).expect("writing to a vector cannot fail");
+    encoder.finish().expect("writing to a vector cannot fail")
+}
+
+/// Writes a report as GZIP compressed XML into a directory, named according to RFC 7489 Section 7.2.1.1.
+pub fn write_gzip(feedback: &Feedback, dir: &Path) -> Result<PathBuf, Error> {
+    let path = dir.join(format!("{}.xml.gz", xml::report_file_name(feedback)));
+    fs::write(&path, to_gzip(feedback)).map_err(|e| Error::WriteFile(path.clone(), e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::audit::{compliance, Compliance};
+    use crate::dmarc::{Disposition, DmarcResult, PolicyOverride, SpfResult};
+    use crate::record::DmarcRecord;
+    use crate::xml::report_file_name;
+
+    use super::{generate, outcome_from_email, read_csv, read_json, report_id, Policies, Reporter};
+
+    fn reporter() -> Reporter {
+        Reporter {
+            org_name: "mx.example.net".into(),
+            email: "dmarc@example.net".into(),
+            extra_contact_info: None,
+        }
+    }
+
+    fn policies() -> Policies {
+        let mut policies = Policies::default();
+        policies.insert(
+            "example.com",
+            DmarcRecord::parse("v=DMARC1; p=reject; sp=quarantine").unwrap(),
+        );
+        policies
+    }
+
+    #[test]
+    fn generate_from_csv() {
+        let csv = "\
+timestamp,source_ip,header_from,envelope_from,envelope_to,dkim_domain,dkim_selector,dkim_result,spf_domain,spf_scope,spf_result,disposition
+1700006400,192.0.2.1,example.com,example.com,example.net,example.com,s1,pass,example.com,mfrom,pass,
+1700006500,192.0.2.1,example.com,example.com,example.net,example.com,s1,pass,example.com,mfrom,pass,
+1700006600,203.0.113.5,news.example.com,,,,,,news.example.com,mfrom,fail,
+1700092800,192.0.2.1,example.com,example.com,example.net,example.com,s1,pass,example.com,mfrom,pass,
+1700006400,192.0.2.1,example.org,,,,,,example.org,mfrom,pass,
+";
+        let outcomes = read_csv(csv.as_bytes()).unwrap();
+        let generated = generate(&outcomes, &policies(), &reporter());
+        assert_eq!(generated.skipped, 1);
+        assert_eq!(generated.feedbacks.len(), 2);
+
+        let feedback = &generated.feedbacks[0];
+        let report_id = &feedback.report_metadata.report_id;
+        assert!(report_id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(
+            report_file_name(feedback),
+            format!("mx.example.net!example.com!1700006400!1700092799!{report_id}")
+        );
+        assert_ne!(generated.feedbacks[1].report_metadata.report_id, *report_id);
+        assert_eq!(feedback.records.len(), 2);
+        assert_eq!(feedback.records[0].row.count, 2);
+        assert_eq!(
+            feedback.records[0].row.policy_evaluated.spf,
+            DmarcResult::Pass
+        );
+        let spoofed = &feedback.records[1].row.policy_evaluated;
+        assert_eq!(spoofed.disposition, Disposition::Quarantine);
+        assert_eq!(spoofed.dkim, DmarcResult::Fail);
+    }
+
+    #[test]
+    fn generate_sampled_out() {
+        let csv = "\
+timestamp,source_ip,header_from,envelope_from,envelope_to,dkim_domain,dkim_selector,dkim_result,spf_domain,spf_scope,spf_result,disposition
+1700006600,203.0.113.5,example.com,,,,,,example.com,mfrom,fail,
+1700006600,192.0.2.1,example.com,,,,,,example.com,mfrom,pass,
+";
+        let outcomes = read_csv(csv.as_bytes()).unwrap();
+        let mut sampled = Policies::default();
+        sampled.insert(
+            "example.com",
+            DmarcRecord::parse("v=DMARC1; p=reject; pct=0").unwrap(),
+        );
+        let feedback = &generate(&outcomes, &sampled, &reporter()).feedbacks[0];
+        let failed = &feedback.records[0];
+        assert_eq!(
+            failed.row.policy_evaluated.disposition,
+            Disposition::Quarantine
+        );
+        assert_eq!(
+            failed.row.policy_evaluated.reasons[0].typ,
+            PolicyOverride::SampledOut
+        );
+        assert_eq!(compliance(failed, feedback), Compliance::Honored);
+        assert!(feedback.records[1].row.policy_evaluated.reasons.is_empty());
+
+        let feedback = &generate(&outcomes, &policies(), &reporter()).feedbacks[0];
+        assert!(feedback.records[0].row.policy_evaluated.reasons.is_empty());
+    }
+
+    #[test]
+    fn read_json_lines() {
+        let json = r#"{"timestamp": 1700006400, "source_ip": "192.0.2.1", "header_from": "example.com", "spf": [{"domain": "example.com", "result": "pass"}]}
+{"timestamp": 1700006400, "source_ip": "192.0.2.2", "header_from": "example.com", "disposition": "none"}"#;
+        let outcomes = read_json(json.as_bytes()).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].spf[0].result, SpfResult::Pass);
+        let array = format!("[{}]", json.replace('\n', ","));
+        assert_eq!(read_json(array.as_bytes()).unwrap(), outcomes);
+    }
+
+    #[test]
+    fn stable_report_id() {
+        let begin = DateTime::from_timestamp(1700006400, 0).unwrap();
+        assert_eq!(report_id("example.com", begin), "31ea860ea8c89273");
+    }
+
+    #[test]
+    fn outcome_from_authentication_results() {
+        let email = b"Authentication-Results: other.example; spf=pass smtp.mailfrom=evil.example
+Authentication-Results: mx.example.net; dkim=pass header.d=example.com header.s=s1;
+ spf=fail smtp.mailfrom=bounce@lists.example.org; iprev=pass policy.iprev=198.51.100.3;
+ dmarc=pass header.from=example.com
+Date: Wed, 15 Nov 2023 00:00:00 +0000
+From: Alice <alice@example.com>
+Subject: Hello
+
+Hello
+";
+        let outcome = outcome_from_email(email, Some("mx.example.net"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            outcome.source_ip,
+            "198.51.100.3".parse::<std::net::IpAddr>().unwrap()
+        );
+        assert_eq!(outcome.header_from, "example.com");
+        assert_eq!(outcome.envelope_from.as_deref(), Some("lists.example.org"));
+        assert_eq!(outcome.dkim[0].selector.as_deref(), Some("s1"));
+        assert_eq!(outcome.spf[0].result, SpfResult::Fail);
+        assert_eq!(outcome.timestamp.timestamp(), 1700006400);
+
+        // Messages are dated by their delivery rather than by their sender.
+        let mut received = b"Received: from mail.example.com by mx.example.net;
+ Thu, 16 Nov 2023 00:00:01 +0000
+Received: from laptop by mail.example.com; Wed, 15 Nov 2023 00:00:00 +0000
+"
+        .to_vec();
+        received.extend_from_slice(email);
+        let outcome = outcome_from_email(&received, Some("mx.example.net"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(outcome.timestamp.timestamp(), 1700092801);
+
+        assert!(outcome_from_email(email, Some("unknown.example"))
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,26 @@
+/// Hashes byte strings with the 64-bit FNV-1a function.
+///
+/// Unlike the hasher of the standard library, the hash is the same on all platforms and Rust
+/// releases. Each part is terminated by a zero byte, so that parts cannot run into each other.
+pub fn fnv1a(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for &byte in part.iter().chain(&[0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fnv1a;
+
+    #[test]
+    fn stable_hashes() {
+        assert_eq!(fnv1a(&[]), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(&[b"a"]), 0x089be207b544f1e4);
+        assert_ne!(fnv1a(&[b"ab", b""]), fnv1a(&[b"a", b"b"]));
+    }
+}
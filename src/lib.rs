@@ -0,0 +1,171 @@
+//! Extraction, parsing and analysis of DMARC aggregate reports as specified in RFC 7489.
+//!
+//! Reports can be parsed from their XML representation, from a compressed attachment, from a whole
+//! email or from the emails of an mbox file. All entry points enforce the given [`Limits`], since
+//! reports are usually received from untrusted senders.
+
+use mailparse::parse_mail;
+
+pub mod arc;
+pub mod audit;
+pub mod authres;
+mod decode;
+pub mod dedup;
+pub mod diagnostics;
+pub mod dkim;
+pub mod dmarc;
+pub mod dns;
+pub mod domain;
+mod error;
+mod extract;
+pub mod filter;
+#[cfg(test)]
+mod fixtures;
+pub mod generate;
+mod hash;
+pub mod limits;
+pub mod lint;
+pub mod record;
+pub mod sink;
+pub mod source;
+pub mod spf;
+pub mod subdomain;
+pub mod traffic;
+pub mod xml;
+pub mod zone;
+
+pub use diagnostics::Diagnostics;
+pub use dmarc::Feedback;
+pub use error::Error;
+pub use limits::Limits;
+pub use sink::ReportSink;
+pub use source::{ingest, ReportSource};
+
+use diagnostics::Failure;
+
+/// Parses a report from its XML representation.
+pub fn from_xml_str(xml: &str, limits: &Limits) -> Result<Feedback, Error> {
+    limits.check_xml(xml)?;
+    quick_xml::de::from_str(xml).map_err(Error::ParseDmarcReport)
+}
+
+/// Parses a report from its encoded XML representation.
+///
+/// The encoding is taken from the byte order mark or the XML declaration and defaults to UTF-8.
+pub fn from_xml_bytes(
+    xml: &[u8],
+    limits: &Limits,
+    diagnostics: &mut Diagnostics,
+) -> Result<Feedback, Error> {
+    extract::parse_xml(xml, limits, diagnostics)
+}
+
+/// Parses the reports contained in a ZIP, GZIP or uncompressed XML attachment.
+pub fn from_attachment(
+    content: &[u8],
+    limits: &Limits,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<Feedback>, Error> {
+    extract::process_attachment(content, limits, diagnostics)
+}
+
+/// Parses the reports contained in all supported attachments of a raw email.
+pub fn from_email(
+    email: &[u8],
+    limits: &Limits,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<Feedback>, Error> {
+    let parsed_mail = parse_mail(email).map_err(Error::ParseMail)?;
+    extract::process_email(&parsed_mail, limits, diagnostics)
+}
+
+/// Parses the reports of a sequence of raw emails, such as the emails of an mbox file.
+///
+/// Emails which cannot be processed do not abort parsing but are recorded as failures in the
+/// diagnostics, identified by their position in the sequence.
+pub fn from_mbox<'a>(
+    emails: impl IntoIterator<Item = &'a [u8]>,
+    limits: &Limits,
+    diagnostics: &mut Diagnostics,
+) -> Vec<Feedback> {
+    let mut feedbacks = vec![];
+    for (index, email) in emails.into_iter().enumerate() {
+        diagnostics.messages += 1;
+        match from_email(email, limits, diagnostics) {
+            Ok(email_feedbacks) => feedbacks.extend(email_feedbacks),
+            Err(error) => diagnostics
+                .failures
+                .push(Failure::new(index, None, Some(email), error)),
+        }
+    }
+    feedbacks
+}
+
+/// Splits an mbox file into its emails at the `From ` lines starting each of them.
+///
+/// The emails are not decoded, since they may use any encoding. `From ` lines are only recognized
+/// at the beginning of a line and are not part of the emails.
+pub fn split_mbox(mbox: &[u8]) -> Vec<Vec<u8>> {
+    let mut emails = vec![];
+    let mut email: Option<Vec<u8>> = None;
+    for line in mbox.split_inclusive(|&b| b == b'\n') {
+        if line.starts_with(b"From ") {
+            emails.extend(email.replace(vec![]));
+        } else if let Some(email) = &mut email {
+            email.extend_from_slice(line);
+        }
+    }
+    emails.extend(email);
+    for email in &mut emails {
+        // The line separating two emails is not part of the first one.
+        let trimmed = email.trim_ascii_end().len();
+        email.truncate(trimmed);
+    }
+    emails
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{from_mbox, split_mbox, Diagnostics, Error, Limits};
+
+    #[test]
+    fn record_headers_of_failed_emails() {
+        let broken = b"Message-ID: <1@example.org>\r\nSubject: Report\r\n\r\nno report";
+        let no_headers = b"no report";
+        let mut diagnostics = Diagnostics::default();
+        let feedbacks = from_mbox(
+            [&broken[..], &no_headers[..]],
+            &Limits::default(),
+            &mut diagnostics,
+        );
+        assert!(feedbacks.is_empty());
+        assert_eq!(diagnostics.messages, 2);
+        let failure = &diagnostics.failures[0];
+        assert_eq!(failure.index, 0);
+        assert_eq!(failure.message_id.as_deref(), Some("<1@example.org>"));
+        assert_eq!(failure.subject.as_deref(), Some("Report"));
+        assert!(matches!(failure.error, Error::NoSupportedAttachmentFound));
+        let failure = &diagnostics.failures[1];
+        assert_eq!((failure.index, failure.subject.as_deref()), (1, None));
+    }
+
+    #[test]
+    fn split_mbox_bytes() {
+        let mbox = b"From a@example.org Mon Jan  1 00:00:00 2024\r
+Subject: caf\xe9\r
+\r
+Sent From home\r
+\r
+From b@example.org Mon Jan  1 00:00:01 2024\r
+Subject: b\r
+";
+        assert_eq!(
+            split_mbox(mbox),
+            [
+                b"Subject: caf\xe9\r\n\r\nSent From home".to_vec(),
+                b"Subject: b".to_vec()
+            ]
+        );
+        assert!(split_mbox(b"").is_empty());
+    }
+}
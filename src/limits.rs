@@ -0,0 +1,198 @@
+use std::fmt;
+use std::io;
+use std::io::Read;
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::Error;
+
+/// Resource limits applied while extracting reports from untrusted emails.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Maximum number of bytes decompressed from a single attachment.
+    pub max_decompressed_size: u64,
+    /// Maximum ratio between the decompressed and the compressed size of an attachment.
+    pub max_compression_ratio: u64,
+    /// Maximum number of files in a ZIP archive.
+    pub max_archive_entries: usize,
+    /// Maximum nesting depth of XML elements in a report.
+    pub max_xml_depth: usize,
+    /// Maximum number of records in a report.
+    pub max_records: usize,
+    /// Maximum nesting depth of messages forwarded as attachments of an email.
+    pub max_message_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_decompressed_size: 50 * 1024 * 1024,
+            max_compression_ratio: 200,
+            max_archive_entries: 100,
+            max_xml_depth: 32,
+            max_records: 100_000,
+            max_message_depth: 8,
+        }
+    }
+}
+
+/// The limit that was exceeded by a report.
+#[derive(Debug, PartialEq)]
+pub enum LimitExceeded {
+    DecompressedSize(u64),
+    CompressionRatio(u64),
+    ArchiveEntries(usize),
+    XmlDepth(usize),
+    Records(usize),
+    MessageDepth(usize),
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::DecompressedSize(limit) => {
+                write!(f, "decompressed size exceeds {limit} bytes")
+            }
+            LimitExceeded::CompressionRatio(limit) => {
+                write!(f, "compression ratio exceeds {limit}")
+            }
+            LimitExceeded::ArchiveEntries(limit) => {
+                write!(f, "archive contains more than {limit} files")
+            }
+            LimitExceeded::XmlDepth(limit) => write!(f, "XML nesting exceeds depth {limit}"),
+            LimitExceeded::Records(limit) => write!(f, "report contains more than {limit} records"),
+            LimitExceeded::MessageDepth(limit) => {
+                write!(f, "forwarded messages nested deeper than {limit}")
+            }
+        }
+    }
+}
+
+impl Limits {
+    /// Reads the decompressed content of an attachment, enforcing the size and compression ratio limits.
+    ///
+    /// `budget` is the number of decompressed bytes still allowed for the attachment, which allows
+    /// limiting the total size of all files in an archive.
+    pub fn read_bounded(
+        &self,
+        reader: impl Read,
+        compressed_size: u64,
+        budget: u64,
+        map_err: fn(io::Error) -> Error,
+    ) -> Result<Vec<u8>, Error> {
+        let ratio_limit = compressed_size.saturating_mul(self.max_compression_ratio);
+        let limit = budget.min(ratio_limit);
+        let mut content = vec![];
+        reader
+            .take(limit.saturating_add(1))
+            .read_to_end(&mut content)
+            .map_err(map_err)?;
+        if content.len() as u64 > limit {
+            let exceeded = if ratio_limit < budget {
+                LimitExceeded::CompressionRatio(self.max_compression_ratio)
+            } else {
+                LimitExceeded::DecompressedSize(self.max_decompressed_size)
+            };
+            return Err(Error::LimitExceeded(exceeded));
+        }
+        Ok(content)
+    }
+
+    /// Checks the nesting depth and the number of records of a report before deserializing it.
+    ///
+    /// Malformed XML is not reported here but left to the deserializer.
+    pub fn check_xml(&self, xml: &str) -> Result<(), Error> {
+        let mut reader = Reader::from_str(xml);
+        let mut depth: usize = 0;
+        let mut records = 0;
+        loop {
+            let (name, is_empty) = match reader.read_event() {
+                Ok(Event::Start(e)) => (e.local_name().as_ref().to_vec(), false),
+                Ok(Event::Empty(e)) => (e.local_name().as_ref().to_vec(), true),
+                Ok(Event::End(_)) => {
+                    depth = depth.saturating_sub(1);
+                    continue;
+                }
+                Ok(Event::Eof) | Err(_) => return Ok(()),
+                Ok(_) => continue,
+            };
+            if depth + 1 > self.max_xml_depth {
+                return Err(Error::LimitExceeded(LimitExceeded::XmlDepth(
+                    self.max_xml_depth,
+                )));
+            }
+            if name == b"record" {
+                records += 1;
+                if records > self.max_records {
+                    return Err(Error::LimitExceeded(LimitExceeded::Records(
+                        self.max_records,
+                    )));
+                }
+            }
+            if !is_empty {
+                depth += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Error;
+
+    use super::{LimitExceeded, Limits};
+
+    fn limit_exceeded(result: Result<impl Sized, Error>) -> Option<LimitExceeded> {
+        match result {
+            Err(Error::LimitExceeded(exceeded)) => Some(exceeded),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn read_bounded_enforces_size_and_ratio() {
+        let limits = Limits {
+            max_decompressed_size: 100,
+            max_compression_ratio: 10,
+            ..Default::default()
+        };
+        let content = [b'a'; 101];
+        let read = |compressed_size, budget| {
+            limits.read_bounded(
+                &content[..],
+                compressed_size,
+                budget,
+                Error::ReadXmlFromGzip,
+            )
+        };
+        assert_eq!(read(50, 101).unwrap().len(), 101);
+        assert_eq!(
+            limit_exceeded(read(50, 100)),
+            Some(LimitExceeded::DecompressedSize(100))
+        );
+        assert_eq!(
+            limit_exceeded(read(10, 200)),
+            Some(LimitExceeded::CompressionRatio(10))
+        );
+    }
+
+    #[test]
+    fn check_xml_enforces_depth_and_records() {
+        let limits = Limits {
+            max_xml_depth: 3,
+            max_records: 2,
+            ..Default::default()
+        };
+        let xml = "<feedback><record><row/></record><record/></feedback>";
+        assert!(limits.check_xml(xml).is_ok());
+        assert_eq!(
+            limit_exceeded(limits.check_xml("<a><b><c><d/></c></b></a>")),
+            Some(LimitExceeded::XmlDepth(3))
+        );
+        assert_eq!(
+            limit_exceeded(limits.check_xml("<feedback><record/><record/><record/></feedback>")),
+            Some(LimitExceeded::Records(2))
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use crate::authres::domain_of;
+use crate::dmarc::{Disposition, PolicyPublished};
+use crate::domain::{normalize, organizational_domain};
+use crate::record::DmarcRecord;
+use crate::record::ReportUri;
+use crate::Feedback;
+
+/// The tags defined for DMARC records by RFC 7489 and RFC 9091.
+const KNOWN_TAGS: &[&str] = &[
+    "v", "p", "sp", "np", "pct", "rua", "ruf", "adkim", "aspf", "fo", "rf", "ri",
+];
+
+/// How severe a finding of the linter is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The record is invalid and will be ignored or misinterpreted by receivers.
+    Error,
+    /// The record is valid but likely does not do what was intended.
+    Warning,
+    /// A noteworthy but harmless property of the record.
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// A problem or questionable setting found in a DMARC record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// The tag the finding is about, if it is about a single tag.
+    pub tag: Option<String>,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, tag: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            tag: tag.map(String::from),
+            message: message.into(),
+        }
+    }
+}
+
+/// Checks the syntax of the tags which the parser tolerates.
+fn lint_syntax(txt: &str, findings: &mut Vec<Finding>) {
+    let mut seen = BTreeSet::new();
+    for (i, tag) in txt.split(';').map(str::trim).enumerate() {
+        if tag.is_empty() {
+            continue;
+        }
+        let Some((name, value)) = tag.split_once('=') else {
+            // Reported by the parser.
+            continue;
+        };
+        let name = name.trim().to_lowercase();
+        if !seen.insert(name.clone()) {
+            findings.push(Finding::new(
+                Severity::Error,
+                Some(&name),
+                "tag is given more than once",
+            ));
+        }
+        if name == "v" && i > 0 {
+            findings.push(Finding::new(
+                Severity::Error,
+                Some(&name),
+                "'v' must be the first tag",
+            ));
+        }
+        if value.trim().is_empty() {
+            findings.push(Finding::new(
+                Severity::Error,
+                Some(&name),
+                "tag has no value",
+            ));
+        }
+        if !KNOWN_TAGS.contains(&name.as_str()) {
+            findings.push(Finding::new(
+                Severity::Warning,
+                Some(&name),
+                "unknown tag is ignored by receivers",
+            ));
+        }
+    }
+}
+
+fn lint_uris(domain: Option<&str>, tag: &str, uris: &[String], findings: &mut Vec<Finding>) {
+    for uri in uris {
+        let uri = match ReportUri::parse(uri) {
+            Ok(uri) => uri,
+            Err(_) => {
+                findings.push(Finding::new(
+                    Severity::Error,
+                    Some(tag),
+                    format!("'{uri}' is not a valid mailto URI"),
+                ));
+                continue;
+            }
+        };
+        let destination = normalize(domain_of(&uri.address));
+        if let Some(domain) = domain {
+            if organizational_domain(&destination) != organizational_domain(domain) {
+                findings.push(Finding::new(
+                    Severity::Info,
+                    Some(tag),
+                    format!(
+                        "external destination {destination} must publish \
+                         {}._report._dmarc.{destination}",
+                        normalize(domain)
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Lints the text of a DMARC record, returning the parsed record if it is valid.
+///
+/// The domain the record is published for enables checks of the report destinations.
+pub fn lint(txt: &str, domain: Option<&str>) -> (Option<DmarcRecord>, Vec<Finding>) {
+    let mut findings = vec![];
+    lint_syntax(txt, &mut findings);
+    let record = match DmarcRecord::parse(txt) {
+        Ok(record) => record,
+        Err(e) => {
+            findings.push(Finding::new(Severity::Error, None, e.to_string()));
+            return (None, findings);
+        }
+    };
+
+    if record.p == Disposition::None {
+        findings.push(Finding::new(
+            Severity::Info,
+            Some("p"),
+            "policy only monitors, failing messages are delivered",
+        ));
+        if record.pct < 100 {
+            findings.push(Finding::new(
+                Severity::Warning,
+                Some("pct"),
+                "pct has no effect with p=none",
+            ));
+        }
+    } else if record.pct < 100 {
+        findings.push(Finding::new(
+            Severity::Info,
+            Some("pct"),
+            format!("policy applies to {}% of failing messages only", record.pct),
+        ));
+    }
+    if record.sp.is_some_and(|sp| sp == Disposition::None) && record.p != Disposition::None {
+        findings.push(Finding::new(
+            Severity::Warning,
+            Some("sp"),
+            "subdomains are not protected by the policy",
+        ));
+    }
+    if record.rua.is_empty() {
+        findings.push(Finding::new(
+            Severity::Warning,
+            Some("rua"),
+            "no aggregate reports are requested",
+        ));
+    }
+    if !record.ruf.is_empty() {
+        findings.push(Finding::new(
+            Severity::Warning,
+            Some("ruf"),
+            "failure reports may contain personal data and are sent by few receivers",
+        ));
+    }
+    lint_uris(domain, "rua", &record.rua, &mut findings);
+    lint_uris(domain, "ruf", &record.ruf, &mut findings);
+    for option in record.fo.split(':').map(str::trim) {
+        if !["0", "1", "d", "s"].contains(&option) {
+            findings.push(Finding::new(
+                Severity::Error,
+                Some("fo"),
+                format!("unknown failure reporting option '{option}'"),
+            ));
+        }
+    }
+    if record.fo != "0" && record.ruf.is_empty() {
+        findings.push(Finding::new(
+            Severity::Info,
+            Some("fo"),
+            "fo has no effect without ruf",
+        ));
+    }
+    for format in &record.rf {
+        if !format.eq_ignore_ascii_case("afrf") {
+            findings.push(Finding::new(
+                Severity::Error,
+                Some("rf"),
+                format!("unknown failure report format '{format}'"),
+            ));
+        }
+    }
+    if record.ri != 86400 {
+        findings.push(Finding::new(
+            Severity::Info,
+            Some("ri"),
+            "most receivers send reports daily regardless of ri",
+        ));
+    }
+    findings.sort_by_key(|finding| finding.severity);
+    (Some(record), findings)
+}
+
+/// A difference between the published policy and the policy a reporter saw.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drift {
+    /// The name of the differing tag.
+    pub tag: &'static str,
+    pub published: String,
+    pub reported: String,
+    /// The organizations reporting the differing value.
+    pub reporters: BTreeSet<String>,
+    /// The number of reports with the differing value.
+    pub reports: usize,
+    /// The end of the latest report with the differing value.
+    pub last_seen: DateTime<Utc>,
+}
+
+/// The values of a policy that reports include, by tag.
+fn reported_values(policy: &PolicyPublished) -> Vec<(&'static str, Option<String>)> {
+    vec![
+        ("p", Some(policy.p.as_str().into())),
+        ("sp", Some(policy.sp.as_str().into())),
+        ("adkim", policy.adkim.map(|adkim| adkim.as_str().into())),
+        ("aspf", policy.aspf.map(|aspf| aspf.as_str().into())),
+        ("pct", Some(policy.pct.to_string())),
+        // Many reporters omit fo.
+        ("fo", Some(policy.fo.clone()).filter(|fo| !fo.is_empty())),
+    ]
+}
+
+/// Compares a record with the policy that reports for its domain say was published.
+///
+/// Values omitted by reporters are not compared. Differences are grouped by tag and reported value.
+pub fn drift(record: &DmarcRecord, domain: &str, feedbacks: &[Feedback]) -> Vec<Drift> {
+    let published: BTreeMap<_, _> = reported_values(&record.policy_published(domain))
+        .into_iter()
+        .filter_map(|(tag, value)| Some((tag, value?)))
+        .collect();
+    let mut drifts: BTreeMap<(&'static str, String), Drift> = BTreeMap::new();
+    for feedback in feedbacks {
+        if normalize(&feedback.policy_published.domain) != normalize(domain) {
+            continue;
+        }
+        for (tag, reported) in reported_values(&feedback.policy_published) {
+            let (Some(reported), Some(published)) = (reported, published.get(tag)) else {
+                continue;
+            };
+            if reported.eq_ignore_ascii_case(published) {
+                continue;
+            }
+            let end = feedback.report_metadata.date_range.end;
+            let drift = drifts
+                .entry((tag, reported.clone()))
+                .or_insert_with(|| Drift {
+                    tag,
+                    published: published.clone(),
+                    reported,
+                    reporters: BTreeSet::new(),
+                    reports: 0,
+                    last_seen: end,
+                });
+            drift
+                .reporters
+                .insert(feedback.report_metadata.org_name.clone());
+            drift.reports += 1;
+            drift.last_seen = drift.last_seen.max(end);
+        }
+    }
+    drifts.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dmarc::{Alignment, Disposition};
+    use crate::fixtures;
+    use crate::record::DmarcRecord;
+
+    use super::{drift, lint, Severity};
+
+    fn tags(findings: &[super::Finding], severity: Severity) -> Vec<&str> {
+        findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .filter_map(|finding| finding.tag.as_deref())
+            .collect()
+    }
+
+    #[test]
+    fn lint_questionable_settings() {
+        let (record, findings) = lint(
+            "v=DMARC1; p=none; pct=50; ruf=mailto:f@example.org; fo=1:x; foo=bar",
+            Some("example.com"),
+        );
+        assert_eq!(record.unwrap().p, Disposition::None);
+        assert_eq!(tags(&findings, Severity::Error), vec!["fo"]);
+        assert_eq!(
+            tags(&findings, Severity::Warning),
+            vec!["foo", "pct", "rua", "ruf"]
+        );
+        assert!(tags(&findings, Severity::Info).contains(&"ruf"));
+    }
+
+    #[test]
+    fn lint_syntax_errors() {
+        let (record, findings) = lint("p=reject; v=DMARC1; p=none", None);
+        assert!(record.is_none());
+        assert_eq!(findings.len(), 3);
+        assert!(findings
+            .iter()
+            .all(|finding| finding.severity == Severity::Error));
+
+        let (record, findings) = lint("v=DMARC1; p=reject; rua=mailto:d@example.com", None);
+        assert!(record.is_some());
+        assert!(findings.is_empty());
+    }
+
+    #[test]
+    fn lint_values_regardless_of_case() {
+        let (record, findings) = lint(
+            "v=DMARC1; p=Reject; sp=NONE; adkim=S; aspf=r; fo=D:S; rf=AFRF; ruf=mailto:f@example.com",
+            None,
+        );
+        let record = record.unwrap();
+        assert_eq!(
+            (record.p, record.sp),
+            (Disposition::Reject, Some(Disposition::None))
+        );
+        assert_eq!(
+            (record.adkim, record.aspf),
+            (Alignment::Strict, Alignment::Relaxed)
+        );
+        assert_eq!(record.fo, "d:s");
+        assert!(tags(&findings, Severity::Error).is_empty());
+    }
+
+    #[test]
+    fn detect_drift() {
+        let feedback = fixtures::report();
+        let domain = feedback.policy_published.domain.clone();
+        let record = DmarcRecord::parse("v=DMARC1; p=none").unwrap();
+        let drifts = drift(&record, &domain, &[feedback.clone(), feedback]);
+        let p = drifts.iter().find(|drift| drift.tag == "p").unwrap();
+        assert_eq!(p.published, "none");
+        assert_eq!(p.reports, 2);
+        assert_eq!(p.reporters.len(), 1);
+    }
+}
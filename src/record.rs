@@ -0,0 +1,276 @@
+use std::fmt;
+
+use serde::de::{DeserializeOwned, IntoDeserializer};
+
+use crate::dmarc::{Alignment, Disposition, PolicyPublished};
+use crate::Error;
+
+/// A DMARC policy record as published in DNS, according to RFC 7489 Section 6.3.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmarcRecord {
+    /// The policy for the domain.
+    pub p: Disposition,
+    /// The policy for subdomains, inheriting from `p` if not set.
+    pub sp: Option<Disposition>,
+    /// The policy for non-existent subdomains, inheriting from `sp` if not set.
+    pub np: Option<Disposition>,
+    /// The DKIM alignment mode.
+    pub adkim: Alignment,
+    /// The SPF alignment mode.
+    pub aspf: Alignment,
+    /// The percentage of messages to which the policy applies.
+    pub pct: u8,
+    /// Failure reporting options.
+    pub fo: String,
+    /// Addresses to which aggregate reports are sent.
+    pub rua: Vec<String>,
+    /// Addresses to which failure reports are sent.
+    pub ruf: Vec<String>,
+    /// Formats requested for failure reports.
+    pub rf: Vec<String>,
+    /// The requested interval between aggregate reports in seconds.
+    pub ri: u32,
+}
+
+/// Why the text of a DMARC record could not be parsed.
+#[derive(Debug, PartialEq)]
+pub enum RecordError {
+    MissingVersion,
+    MalformedTag(String),
+    InvalidValue { tag: String, value: String },
+    MissingPolicy,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::MissingVersion => write!(f, "record does not start with 'v=DMARC1'"),
+            RecordError::MalformedTag(tag) => write!(f, "malformed tag '{tag}'"),
+            RecordError::InvalidValue { tag, value } => {
+                write!(f, "invalid value '{value}' for tag '{tag}'")
+            }
+            RecordError::MissingPolicy => write!(f, "required tag 'p' is missing"),
+        }
+    }
+}
+
+/// Parses a tag value using the names of the report schema, regardless of case as RFC 7489
+/// Section 6.4 requires.
+fn parse_value<T: DeserializeOwned>(tag: &str, value: &str) -> Result<T, Error> {
+    let keyword = value.to_ascii_lowercase();
+    T::deserialize(keyword.as_str().into_deserializer())
+        .map_err(|_: serde::de::value::Error| invalid(tag, value))
+}
+
+fn invalid(tag: &str, value: &str) -> Error {
+    Error::ParseDmarcRecord(RecordError::InvalidValue {
+        tag: tag.into(),
+        value: value.into(),
+    })
+}
+
+/// Splits a comma separated list of URIs.
+fn parse_uris(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|uri| !uri.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+impl DmarcRecord {
+    /// Parses the text of a DMARC TXT record.
+    pub fn parse(txt: &str) -> Result<Self, Error> {
+        let mut tags = txt.split(';').map(str::trim).filter(|tag| !tag.is_empty());
+        match tags.next().and_then(|tag| tag.split_once('=')) {
+            Some((v, version)) if v.trim() == "v" && version.trim() == "DMARC1" => {}
+            _ => return Err(Error::ParseDmarcRecord(RecordError::MissingVersion)),
+        }
+        let mut p = None;
+        let mut record = Self {
+            p: Disposition::None,
+            sp: None,
+            np: None,
+            adkim: Alignment::Relaxed,
+            aspf: Alignment::Relaxed,
+            pct: 100,
+            fo: "0".into(),
+            rua: vec![],
+            ruf: vec![],
+            rf: vec!["afrf".into()],
+            ri: 86400,
+        };
+        for tag in tags {
+            let (name, value) = tag
+                .split_once('=')
+                .ok_or_else(|| Error::ParseDmarcRecord(RecordError::MalformedTag(tag.into())))?;
+            let name = name.trim().to_lowercase();
+            let value = value.trim();
+            match name.as_str() {
+                "p" => p = Some(parse_value(&name, value)?),
+                "sp" => record.sp = Some(parse_value(&name, value)?),
+                "np" => record.np = Some(parse_value(&name, value)?),
+                "adkim" => record.adkim = parse_value(&name, value)?,
+                "aspf" => record.aspf = parse_value(&name, value)?,
+                "pct" => {
+                    record.pct = value
+                        .parse()
+                        .ok()
+                        .filter(|pct| *pct <= 100)
+                        .ok_or_else(|| invalid(&name, value))?
+                }
+                "fo" => record.fo = value.to_ascii_lowercase(),
+                "rua" => record.rua = parse_uris(value),
+                "ruf" => record.ruf = parse_uris(value),
+                "rf" => {
+                    let formats = value.split(':').map(|f| f.trim().to_ascii_lowercase());
+                    record.rf = formats.collect()
+                }
+                "ri" => record.ri = value.parse().map_err(|_| invalid(&name, value))?,
+                // Unknown tags must be ignored.
+                _ => {}
+            }
+        }
+        record.p = p.ok_or(Error::ParseDmarcRecord(RecordError::MissingPolicy))?;
+        Ok(record)
+    }
+
+    /// The policy as it is included in aggregate reports for the domain the record was found at.
+    pub fn policy_published(&self, domain: &str) -> PolicyPublished {
+        PolicyPublished {
+            domain: domain.into(),
+            adkim: Some(self.adkim),
+            aspf: Some(self.aspf),
+            p: self.p,
+            sp: self.sp.unwrap_or(self.p),
+            pct: self.pct,
+            fo: self.fo.clone(),
+        }
+    }
+}
+
+/// A destination for aggregate reports from the `rua` tag of a DMARC record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportUri {
+    /// The email address reports are sent to.
+    pub address: String,
+    /// The maximum size of a report email the receiver accepts, in bytes.
+    pub max_size: Option<u64>,
+}
+
+impl ReportUri {
+    /// Parses a `mailto:` URI with an optional size limit, such as `mailto:dmarc@example.com!10m`.
+    pub fn parse(uri: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidReportUri(uri.into());
+        let (scheme, rest) = uri.trim().split_once(':').ok_or_else(invalid)?;
+        if !scheme.eq_ignore_ascii_case("mailto") {
+            return Err(invalid());
+        }
+        let (address, size) = match rest.rsplit_once('!') {
+            Some((address, size)) => (address, Some(size)),
+            None => (rest, None),
+        };
+        let max_size = size
+            .map(|size| {
+                let (number, unit) = match size.char_indices().last() {
+                    Some((i, unit)) if unit.is_ascii_alphabetic() => (&size[..i], Some(unit)),
+                    _ => (size, None),
+                };
+                let factor = match unit.map(|unit| unit.to_ascii_lowercase()) {
+                    None => 1,
+                    Some('k') => 1 << 10,
+                    Some('m') => 1 << 20,
+                    Some('g') => 1 << 30,
+                    Some('t') => 1 << 40,
+                    Some(_) => return None,
+                };
+                number.parse::<u64>().ok()?.checked_mul(factor)
+            })
+            .map(|size| size.ok_or_else(invalid))
+            .transpose()?;
+        let address = percent_decode(address);
+        if !address.contains('@') {
+            return Err(invalid());
+        }
+        Ok(Self { address, max_size })
+    }
+}
+
+/// Decodes percent encoded characters of a URI, such as `%21` for `!`.
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dmarc::{Alignment, Disposition};
+
+    use crate::Error;
+
+    use super::{DmarcRecord, RecordError, ReportUri};
+
+    #[test]
+    fn parse_record() {
+        let record = DmarcRecord::parse(
+            "v=DMARC1; p=reject; sp=none; np=reject; adkim=s; pct=50; rua=mailto:a@example.com, mailto:b@example.net; ri=3600",
+        )
+        .unwrap();
+        assert_eq!(record.p, Disposition::Reject);
+        assert_eq!(record.sp, Some(Disposition::None));
+        assert_eq!(record.np, Some(Disposition::Reject));
+        assert_eq!(record.adkim, Alignment::Strict);
+        assert_eq!(record.aspf, Alignment::Relaxed);
+        assert_eq!(record.pct, 50);
+        assert_eq!(
+            record.rua,
+            vec!["mailto:a@example.com", "mailto:b@example.net"]
+        );
+        assert_eq!(record.ri, 3600);
+        assert_eq!(record.rf, vec!["afrf"]);
+    }
+
+    #[test]
+    fn parse_invalid_record() {
+        assert!(DmarcRecord::parse("p=reject; v=DMARC1").is_err());
+        assert!(DmarcRecord::parse("v=DMARC1; sp=none").is_err());
+        assert!(matches!(
+            DmarcRecord::parse("v=DMARC1; p=block"),
+            Err(Error::ParseDmarcRecord(RecordError::InvalidValue { tag, value }))
+                if tag == "p" && value == "block"
+        ));
+        assert!(DmarcRecord::parse("v=DMARC1; p=none; pct=101").is_err());
+    }
+
+    #[test]
+    fn parse_report_uri() {
+        let uri = ReportUri::parse("mailto:dmarc%21reports@example.com!10m").unwrap();
+        assert_eq!(uri.address, "dmarc!reports@example.com");
+        assert_eq!(uri.max_size, Some(10 << 20));
+        assert_eq!(
+            ReportUri::parse(" mailto:a@example.com").unwrap().max_size,
+            None
+        );
+        assert!(ReportUri::parse("https://example.com").is_err());
+        assert!(ReportUri::parse("mailto:a@example.com!10x").is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::dmarc::Feedback;
+use crate::error::Error;
+use crate::xml;
+
+/// A consumer of parsed reports.
+///
+/// The error type is left to the implementor, so that sinks can report their own errors. The sinks of
+/// this crate support any error type convertible from [`Error`].
+pub trait ReportSink<E = Error> {
+    /// Consumes the complete collection of reports gathered from all sources.
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), E>;
+}
+
+/// Writes all reports as a JSON array.
+pub struct JsonSink<W: Write>(pub W);
+
+impl<W: Write, E: From<Error>> ReportSink<E> for JsonSink<W> {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), E> {
+        serde_json::to_writer_pretty(&mut self.0, feedbacks).map_err(Error::SerializeJson)?;
+        writeln!(self.0).map_err(Error::WriteOutput)?;
+        Ok(())
+    }
+}
+
+/// Writes each report as an XML file following RFC 7489 into a directory.
+///
+/// Files are named according to the RFC 7489 naming convention.
+pub struct XmlDirSink(pub PathBuf);
+
+impl<E: From<Error>> ReportSink<E> for XmlDirSink {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), E> {
+        fs::create_dir_all(&self.0).map_err(|e| Error::WriteFile(self.0.clone(), e))?;
+        for feedback in feedbacks {
+            let path = self
+                .0
+                .join(format!("{}.xml", xml::report_file_name(feedback)));
+            let file = File::create(&path).map_err(|e| Error::WriteFile(path.clone(), e))?;
+            xml::write_xml(feedback, file)?;
+        }
+        Ok(())
+    }
+}
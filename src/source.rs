@@ -0,0 +1,299 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::{self, Diagnostics, Failure};
+use crate::dmarc::Feedback;
+use crate::error::Error;
+use crate::hash::fnv1a;
+use crate::limits::Limits;
+
+/// The kind of content yielded by a source.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RawKind {
+    /// A complete email carrying reports as attachments.
+    Email,
+    /// A report file, either compressed as ZIP or GZIP or uncompressed XML.
+    Report,
+}
+
+/// An unparsed email or report yielded by a source.
+#[derive(Debug)]
+pub struct Raw {
+    /// Where the content came from, such as a file path.
+    pub origin: String,
+    pub kind: RawKind,
+    pub content: Vec<u8>,
+}
+
+/// An email or report of a source which could not be read.
+#[derive(Debug)]
+pub struct ReadError {
+    /// Where the content came from, such as a file path.
+    pub origin: String,
+    pub error: Error,
+}
+
+/// A source of raw emails or reports.
+pub trait ReportSource {
+    /// Returns the next email or report, or `None` if the source is exhausted.
+    ///
+    /// Errors only affect a single item and the source may be read further.
+    fn next_raw(&mut self) -> Option<Result<Raw, ReadError>>;
+}
+
+/// The emails of an mbox file.
+pub struct MboxSource {
+    path: PathBuf,
+    emails: std::vec::IntoIter<Vec<u8>>,
+    index: usize,
+}
+
+impl MboxSource {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let mbox = fs::read(path).map_err(|e| Error::ReadMboxFile(path.into(), e))?;
+        let emails = crate::split_mbox(&mbox);
+        Ok(Self {
+            path: path.into(),
+            emails: emails.into_iter(),
+            index: 0,
+        })
+    }
+}
+
+impl ReportSource for MboxSource {
+    fn next_raw(&mut self) -> Option<Result<Raw, ReadError>> {
+        let email = self.emails.next()?;
+        let origin = format!("{}#{}", self.path.display(), self.index);
+        self.index += 1;
+        Some(Ok(Raw {
+            origin,
+            kind: RawKind::Email,
+            content: email,
+        }))
+    }
+}
+
+/// Individual files, each containing either an email or a report.
+///
+/// Files with the extension `.eml` are read as emails, all other files as reports.
+pub struct FilesSource {
+    paths: VecDeque<PathBuf>,
+    kind: Option<RawKind>,
+}
+
+impl FilesSource {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self {
+            paths: paths.into_iter().collect(),
+            kind: None,
+        }
+    }
+
+    /// Reads all files directly contained in a directory, in order of their names.
+    pub fn from_dir(dir: &Path) -> Result<Self, Error> {
+        Ok(Self::new(list_files(dir)?))
+    }
+}
+
+impl ReportSource for FilesSource {
+    fn next_raw(&mut self) -> Option<Result<Raw, ReadError>> {
+        let path = self.paths.pop_front()?;
+        let kind =
+            self.kind
+                .unwrap_or_else(|| match path.extension().and_then(|ext| ext.to_str()) {
+                    Some(ext) if ext.eq_ignore_ascii_case("eml") => RawKind::Email,
+                    _ => RawKind::Report,
+                });
+        let origin = path.display().to_string();
+        let result = match fs::read(&path) {
+            Ok(content) => Ok(Raw {
+                origin,
+                kind,
+                content,
+            }),
+            Err(e) => Err(ReadError {
+                origin,
+                error: Error::ReadInput(path, e),
+            }),
+        };
+        Some(result)
+    }
+}
+
+/// The emails of a Maildir, both from its `new` and `cur` subdirectories.
+pub struct MaildirSource(FilesSource);
+
+impl MaildirSource {
+    pub fn open(dir: &Path) -> Result<Self, Error> {
+        let mut paths = list_files(&dir.join("new"))?;
+        paths.extend(list_files(&dir.join("cur"))?);
+        Ok(Self(FilesSource {
+            paths: paths.into(),
+            kind: Some(RawKind::Email),
+        }))
+    }
+
+    /// Whether the directory has the layout of a Maildir.
+    pub fn is_maildir(dir: &Path) -> bool {
+        dir.join("new").is_dir() && dir.join("cur").is_dir()
+    }
+}
+
+impl ReportSource for MaildirSource {
+    fn next_raw(&mut self) -> Option<Result<Raw, ReadError>> {
+        self.0.next_raw()
+    }
+}
+
+/// Lists the regular files of a directory, sorted by name.
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir).map_err(|e| Error::ReadInput(dir.into(), e))? {
+        let path = entry.map_err(|e| Error::ReadInput(dir.into(), e))?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Parses the reports of all emails and report files yielded by a source.
+///
+/// Items which cannot be read or processed are recorded as failures in the diagnostics, identified by
+/// their position across all sources read with the same diagnostics and by their origin. If a
+/// quarantine directory is given, the raw content of items which cannot be processed is written to
+/// it, named by their position and a digest of their content.
+pub fn ingest(
+    source: &mut dyn ReportSource,
+    limits: &Limits,
+    quarantine: Option<&Path>,
+    diagnostics: &mut Diagnostics,
+) -> Vec<Feedback> {
+    let mut feedbacks = vec![];
+    while let Some(raw) = source.next_raw() {
+        let index = diagnostics.messages;
+        diagnostics.messages += 1;
+        let Raw {
+            origin,
+            kind,
+            content,
+        } = match raw {
+            Ok(raw) => raw,
+            Err(ReadError { origin, error }) => {
+                diagnostics
+                    .failures
+                    .push(Failure::new(index, Some(origin), None, error));
+                continue;
+            }
+        };
+        let result = match kind {
+            RawKind::Email => crate::from_email(&content, limits, diagnostics),
+            RawKind::Report => crate::from_attachment(&content, limits, diagnostics),
+        };
+        match result {
+            Ok(raw_feedbacks) => feedbacks.extend(raw_feedbacks),
+            Err(error) => {
+                let email = (kind == RawKind::Email).then_some(content.as_slice());
+                let failure = Failure::new(index, Some(origin), email, error);
+                if let Some(dir) = quarantine {
+                    // The digest keeps the items of different runs apart.
+                    let digest = fnv1a(&[&content]);
+                    let file_name = match kind {
+                        RawKind::Email => format!("message-{index}-{digest:016x}.eml"),
+                        RawKind::Report => format!("report-{index}-{digest:016x}"),
+                    };
+                    if let Err(error) = diagnostics::quarantine(dir, &file_name, &content) {
+                        diagnostics.quarantine_errors.push(error);
+                    }
+                }
+                diagnostics.failures.push(failure);
+            }
+        }
+    }
+    feedbacks
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::diagnostics::Diagnostics;
+    use crate::limits::Limits;
+
+    use super::{ingest, FilesSource, MaildirSource, RawKind, ReportSource};
+
+    #[test]
+    fn read_maildir_and_files() {
+        let dir = std::env::temp_dir().join(format!("dagger-source-{}", std::process::id()));
+        fs::create_dir_all(dir.join("new")).unwrap();
+        fs::create_dir_all(dir.join("cur")).unwrap();
+        fs::create_dir_all(dir.join("tmp")).unwrap();
+        fs::write(dir.join("new").join("2"), "Subject: b").unwrap();
+        fs::write(dir.join("cur").join("1"), "Subject: a").unwrap();
+        fs::write(dir.join("tmp").join("3"), "Subject: c").unwrap();
+        assert!(MaildirSource::is_maildir(&dir));
+
+        let mut maildir = MaildirSource::open(&dir).unwrap();
+        let raw = maildir.next_raw().unwrap().unwrap();
+        assert_eq!(
+            (raw.kind, raw.content),
+            (RawKind::Email, b"Subject: b".to_vec())
+        );
+        let raw = maildir.next_raw().unwrap().unwrap();
+        assert_eq!(raw.content, b"Subject: a");
+        assert!(maildir.next_raw().is_none());
+
+        let mut files = FilesSource::new([dir.join("cur").join("1"), dir.join("missing.eml")]);
+        assert_eq!(files.next_raw().unwrap().unwrap().kind, RawKind::Report);
+        assert!(files.next_raw().unwrap().is_err());
+        assert!(files.next_raw().is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn record_origin_and_quarantine_failures() {
+        let dir = std::env::temp_dir().join(format!("dagger-ingest-{}", std::process::id()));
+        let quarantine = dir.join("quarantine");
+        fs::create_dir_all(&dir).unwrap();
+        let broken = dir.join("broken.eml");
+        let missing = dir.join("missing.eml");
+        for content in ["Subject: a", "Subject: b"] {
+            fs::write(&broken, content).unwrap();
+            let mut source = FilesSource::new([missing.clone(), broken.clone()]);
+            let mut diagnostics = Diagnostics::default();
+            ingest(
+                &mut source,
+                &Limits::default(),
+                Some(&quarantine),
+                &mut diagnostics,
+            );
+            let origins: Vec<Option<String>> = diagnostics
+                .failures
+                .iter()
+                .map(|failure| failure.origin.clone())
+                .collect();
+            assert_eq!(
+                origins,
+                [
+                    Some(missing.display().to_string()),
+                    Some(broken.display().to_string())
+                ]
+            );
+        }
+
+        // Each run quarantines its failures, even at the same positions.
+        let quarantined: Vec<String> = fs::read_dir(&quarantine)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(quarantined.len(), 2);
+        assert!(quarantined
+            .iter()
+            .all(|name| name.starts_with("message-1-") && name.ends_with(".eml")));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,1501 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::dmarc::{SpfDomainScope, SpfResult};
+use crate::dns::Lookup;
+use crate::domain::normalize;
+use crate::{Error, Feedback};
+
+/// The maximum number of mechanisms and modifiers causing DNS lookups, from RFC 7208 Section 4.6.4.
+pub const LOOKUP_LIMIT: usize = 10;
+
+/// The maximum number of lookups returning no records.
+pub const VOID_LOOKUP_LIMIT: usize = 2;
+
+/// The maximum number of mail exchangers considered by the mx mechanism.
+const MX_LIMIT: usize = 10;
+
+/// The result of a matching mechanism.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Qualifier {
+    Pass,
+    Fail,
+    SoftFail,
+    Neutral,
+}
+
+impl Qualifier {
+    fn result(self) -> SpfResult {
+        match self {
+            Qualifier::Pass => SpfResult::Pass,
+            Qualifier::Fail => SpfResult::Fail,
+            Qualifier::SoftFail => SpfResult::Softfail,
+            Qualifier::Neutral => SpfResult::Neutral,
+        }
+    }
+}
+
+/// A network given by an address and prefix length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Network {
+    pub ip: IpAddr,
+    pub prefix: u8,
+}
+
+impl Network {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.ip, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this network contains all addresses of another network.
+    pub fn covers(&self, other: &Network) -> bool {
+        self.prefix <= other.prefix && self.contains(other.ip)
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.ip, self.prefix)
+    }
+}
+
+impl FromStr for Network {
+    type Err = Error;
+
+    /// Parses a network in CIDR notation, or a single address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidNetwork(s.into());
+        let (ip, prefix) = match s.split_once('/') {
+            Some((ip, prefix)) => (ip, Some(prefix)),
+            None => (s, None),
+        };
+        let ip: IpAddr = ip.parse().map_err(|_| invalid())?;
+        let max = if ip.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(invalid)?,
+            None => max,
+        };
+        Ok(Self { ip, prefix })
+    }
+}
+
+/// A mechanism of an SPF record, as defined in RFC 7208 Section 5.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mechanism {
+    All,
+    Include(String),
+    A {
+        domain: Option<String>,
+        prefix4: u8,
+        prefix6: u8,
+    },
+    Mx {
+        domain: Option<String>,
+        prefix4: u8,
+        prefix6: u8,
+    },
+    /// The ptr mechanism is not recommended and never matches in this implementation.
+    Ptr(Option<String>),
+    Ip4(Ipv4Addr, u8),
+    Ip6(Ipv6Addr, u8),
+    Exists(String),
+}
+
+impl Mechanism {
+    /// Whether evaluating the mechanism counts towards the DNS lookup limit.
+    pub fn needs_lookup(&self) -> bool {
+        !matches!(
+            self,
+            Mechanism::All | Mechanism::Ip4(_, _) | Mechanism::Ip6(_, _)
+        )
+    }
+}
+
+/// A qualified mechanism.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directive {
+    pub qualifier: Qualifier,
+    pub mechanism: Mechanism,
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.qualifier {
+            Qualifier::Pass => {}
+            Qualifier::Fail => write!(f, "-")?,
+            Qualifier::SoftFail => write!(f, "~")?,
+            Qualifier::Neutral => write!(f, "?")?,
+        }
+        let prefixes = |f: &mut fmt::Formatter<'_>, prefix4: u8, prefix6: u8| {
+            if prefix4 != 32 {
+                write!(f, "/{prefix4}")?;
+            }
+            if prefix6 != 128 {
+                write!(f, "//{prefix6}")?;
+            }
+            Ok(())
+        };
+        match &self.mechanism {
+            Mechanism::All => write!(f, "all"),
+            Mechanism::Include(domain) => write!(f, "include:{domain}"),
+            Mechanism::A {
+                domain,
+                prefix4,
+                prefix6,
+            }
+            | Mechanism::Mx {
+                domain,
+                prefix4,
+                prefix6,
+            } => {
+                let name = match self.mechanism {
+                    Mechanism::A { .. } => "a",
+                    _ => "mx",
+                };
+                write!(f, "{name}")?;
+                if let Some(domain) = domain {
+                    write!(f, ":{domain}")?;
+                }
+                prefixes(f, *prefix4, *prefix6)
+            }
+            Mechanism::Ptr(None) => write!(f, "ptr"),
+            Mechanism::Ptr(Some(domain)) => write!(f, "ptr:{domain}"),
+            Mechanism::Ip4(ip, prefix) => write!(f, "ip4:{ip}/{prefix}"),
+            Mechanism::Ip6(ip, prefix) => write!(f, "ip6:{ip}/{prefix}"),
+            Mechanism::Exists(domain) => write!(f, "exists:{domain}"),
+        }
+    }
+}
+
+/// A parsed SPF record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpfRecord {
+    pub directives: Vec<Directive>,
+    /// The domain whose record is used if no mechanism matches.
+    pub redirect: Option<String>,
+}
+
+/// Why the text of an SPF record could not be parsed.
+#[derive(Debug, PartialEq)]
+pub enum SpfRecordError {
+    MissingVersion,
+    InvalidTerm(String),
+    DuplicateRedirect,
+    InvalidMacro(String),
+}
+
+impl fmt::Display for SpfRecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpfRecordError::MissingVersion => write!(f, "record does not start with 'v=spf1'"),
+            SpfRecordError::InvalidTerm(term) => write!(f, "invalid term '{term}'"),
+            SpfRecordError::DuplicateRedirect => write!(f, "redirect is given twice"),
+            SpfRecordError::InvalidMacro(spec) => write!(f, "invalid macro in '{spec}'"),
+        }
+    }
+}
+
+fn invalid(term: &str) -> Error {
+    Error::ParseSpfRecord(SpfRecordError::InvalidTerm(term.into()))
+}
+
+/// Parses the optional `/prefix4//prefix6` suffix of the a and mx mechanisms.
+fn parse_dual_prefix(term: &str, spec: &str) -> Result<(Option<String>, u8, u8), Error> {
+    let (rest, prefix6) = match spec.split_once("//") {
+        Some((rest, prefix6)) => (rest, Some(prefix6)),
+        None => (spec, None),
+    };
+    let (domain, prefix4) = match rest.split_once('/') {
+        Some((domain, prefix4)) => (domain, Some(prefix4)),
+        None => (rest, None),
+    };
+    let parse = |prefix: Option<&str>, max: u8| match prefix {
+        Some(prefix) => prefix
+            .parse()
+            .ok()
+            .filter(|prefix| *prefix <= max)
+            .ok_or_else(|| invalid(term)),
+        None => Ok(max),
+    };
+    let domain = match domain {
+        "" => None,
+        domain => Some(
+            domain
+                .strip_prefix(':')
+                .ok_or_else(|| invalid(term))?
+                .to_string(),
+        ),
+    };
+    Ok((domain, parse(prefix4, 32)?, parse(prefix6, 128)?))
+}
+
+fn parse_ip<T: std::str::FromStr>(term: &str, spec: &str, max: u8) -> Result<(T, u8), Error> {
+    let (ip, prefix) = match spec.split_once('/') {
+        Some((ip, prefix)) => (
+            ip,
+            prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| invalid(term))?,
+        ),
+        None => (spec, max),
+    };
+    Ok((ip.parse().map_err(|_| invalid(term))?, prefix))
+}
+
+impl SpfRecord {
+    /// Parses the text of an SPF record as described in RFC 7208 Section 4.6.
+    pub fn parse(txt: &str) -> Result<Self, Error> {
+        let mut terms = txt.split_whitespace();
+        if !terms
+            .next()
+            .is_some_and(|version| version.eq_ignore_ascii_case("v=spf1"))
+        {
+            return Err(Error::ParseSpfRecord(SpfRecordError::MissingVersion));
+        }
+        let mut record = Self {
+            directives: vec![],
+            redirect: None,
+        };
+        for term in terms {
+            if let Some((name, value)) = term.split_once('=') {
+                if name.eq_ignore_ascii_case("redirect") {
+                    if record.redirect.is_some() {
+                        return Err(Error::ParseSpfRecord(SpfRecordError::DuplicateRedirect));
+                    }
+                    record.redirect = Some(value.into());
+                }
+                // Unknown modifiers and exp are ignored.
+                continue;
+            }
+            let (qualifier, rest) = match term.chars().next() {
+                Some('+') => (Qualifier::Pass, &term[1..]),
+                Some('-') => (Qualifier::Fail, &term[1..]),
+                Some('~') => (Qualifier::SoftFail, &term[1..]),
+                Some('?') => (Qualifier::Neutral, &term[1..]),
+                _ => (Qualifier::Pass, term),
+            };
+            let name_end = rest.find([':', '/']).unwrap_or(rest.len());
+            let (name, spec) = rest.split_at(name_end);
+            let target = || {
+                spec.strip_prefix(':')
+                    .filter(|domain| !domain.is_empty())
+                    .map(String::from)
+                    .ok_or_else(|| invalid(term))
+            };
+            let mechanism = match name.to_lowercase().as_str() {
+                "all" if spec.is_empty() => Mechanism::All,
+                "include" => Mechanism::Include(target()?),
+                "exists" => Mechanism::Exists(target()?),
+                "a" => {
+                    let (domain, prefix4, prefix6) = parse_dual_prefix(term, spec)?;
+                    Mechanism::A {
+                        domain,
+                        prefix4,
+                        prefix6,
+                    }
+                }
+                "mx" => {
+                    let (domain, prefix4, prefix6) = parse_dual_prefix(term, spec)?;
+                    Mechanism::Mx {
+                        domain,
+                        prefix4,
+                        prefix6,
+                    }
+                }
+                "ptr" => Mechanism::Ptr(target().ok()),
+                "ip4" => {
+                    let (ip, prefix) = parse_ip(term, target()?.as_str(), 32)?;
+                    Mechanism::Ip4(ip, prefix)
+                }
+                "ip6" => {
+                    let (ip, prefix) = parse_ip(term, target()?.as_str(), 128)?;
+                    Mechanism::Ip6(ip, prefix)
+                }
+                _ => return Err(invalid(term)),
+            };
+            record.directives.push(Directive {
+                qualifier,
+                mechanism,
+            });
+        }
+        Ok(record)
+    }
+
+    /// The number of terms causing DNS lookups in this record, without those of included records.
+    pub fn lookups(&self) -> usize {
+        self.directives
+            .iter()
+            .filter(|directive| directive.mechanism.needs_lookup())
+            .count()
+            + self.redirect.iter().count()
+    }
+}
+
+/// The identities and connection an SPF policy is evaluated for.
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub ip: IpAddr,
+    /// The RFC5321.MailFrom address, or `postmaster@<domain>` if unknown.
+    pub sender: String,
+    pub helo: Option<String>,
+}
+
+impl Context {
+    pub fn new(ip: IpAddr, domain: &str) -> Self {
+        Self {
+            ip,
+            sender: format!("postmaster@{}", normalize(domain)),
+            helo: None,
+        }
+    }
+}
+
+/// Expands the macros of a domain spec as described in RFC 7208 Section 7.
+///
+/// Macros depending on reverse lookups expand to "unknown".
+pub fn expand(spec: &str, domain: &str, context: &Context) -> Result<String, Error> {
+    let invalid = || Error::ParseSpfRecord(SpfRecordError::InvalidMacro(spec.into()));
+    let (local, sender_domain) = context
+        .sender
+        .rsplit_once('@')
+        .unwrap_or(("postmaster", &context.sender));
+    let mut expanded = String::new();
+    let mut chars = spec.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next().ok_or_else(invalid)? {
+            '%' => expanded.push('%'),
+            '_' => expanded.push(' '),
+            '-' => expanded.push_str("%20"),
+            '{' => {
+                let body: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let mut body = body.chars();
+                let letter = body.next().ok_or_else(invalid)?.to_ascii_lowercase();
+                let value = match letter {
+                    's' => context.sender.clone(),
+                    'l' => local.into(),
+                    'o' => sender_domain.into(),
+                    'd' => domain.into(),
+                    'i' => match context.ip {
+                        IpAddr::V4(ip) => ip.to_string(),
+                        IpAddr::V6(ip) => ip
+                            .octets()
+                            .iter()
+                            .flat_map(|b| [b >> 4, b & 0xf])
+                            .map(|nibble| format!("{nibble:x}"))
+                            .collect::<Vec<String>>()
+                            .join("."),
+                    },
+                    'v' => match context.ip {
+                        IpAddr::V4(_) => "in-addr".into(),
+                        IpAddr::V6(_) => "ip6".into(),
+                    },
+                    'h' => context.helo.clone().unwrap_or_else(|| "unknown".into()),
+                    'p' => "unknown".into(),
+                    _ => return Err(invalid()),
+                };
+                let transformers: String = body.collect();
+                let digits: String = transformers
+                    .chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect();
+                let rest = &transformers[digits.len()..];
+                let (reverse, delimiters) = match rest.strip_prefix(['r', 'R']) {
+                    Some(delimiters) => (true, delimiters),
+                    None => (false, rest),
+                };
+                let delimiters = if delimiters.is_empty() {
+                    "."
+                } else {
+                    delimiters
+                };
+                let mut parts: Vec<&str> = value.split(|c| delimiters.contains(c)).collect();
+                if reverse {
+                    parts.reverse();
+                }
+                if !digits.is_empty() {
+                    let keep: usize = digits.parse().map_err(|_| invalid())?;
+                    if keep == 0 {
+                        return Err(invalid());
+                    }
+                    parts = parts.split_off(parts.len().saturating_sub(keep));
+                }
+                expanded.push_str(&parts.join("."));
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(expanded)
+}
+
+/// The mechanism that determined the result of an evaluation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matched {
+    /// The domains from the evaluated domain to the one whose record contains the mechanism,
+    /// following includes and redirects.
+    pub chain: Vec<String>,
+    pub directive: Directive,
+}
+
+impl Matched {
+    /// How many includes or redirects were followed to reach the mechanism.
+    pub fn depth(&self) -> usize {
+        self.chain.len() - 1
+    }
+}
+
+/// The outcome of evaluating an SPF policy for an address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub result: SpfResult,
+    pub matched: Option<Matched>,
+    /// The number of DNS lookups counting towards the limit.
+    pub lookups: usize,
+    /// The number of lookups returning no records.
+    pub void_lookups: usize,
+    /// Why the result is an error, if it is one.
+    pub error: Option<String>,
+}
+
+impl Evaluation {
+    /// Whether evaluation stopped because a lookup limit was exceeded.
+    pub fn limit_exceeded(&self) -> bool {
+        self.lookups > LOOKUP_LIMIT || self.void_lookups > VOID_LOOKUP_LIMIT
+    }
+}
+
+/// Why evaluation was aborted.
+enum Abort {
+    Temp(String),
+    Perm(String),
+}
+
+struct Evaluator<'a> {
+    dns: &'a dyn Lookup,
+    context: &'a Context,
+    lookups: usize,
+    void_lookups: usize,
+}
+
+impl Evaluator<'_> {
+    fn count_lookup(&mut self) -> Result<(), Abort> {
+        self.lookups += 1;
+        if self.lookups > LOOKUP_LIMIT {
+            return Err(Abort::Perm(format!("more than {LOOKUP_LIMIT} DNS lookups")));
+        }
+        Ok(())
+    }
+
+    fn count_void<T>(&mut self, records: &[T]) -> Result<(), Abort> {
+        if records.is_empty() {
+            self.void_lookups += 1;
+            if self.void_lookups > VOID_LOOKUP_LIMIT {
+                return Err(Abort::Perm(format!(
+                    "more than {VOID_LOOKUP_LIMIT} void lookups"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn ips(&mut self, name: &str) -> Result<Vec<IpAddr>, Abort> {
+        let ips = self
+            .dns
+            .lookup_ips(name, self.context.ip.is_ipv6())
+            .map_err(|e| Abort::Temp(e.to_string()))?;
+        self.count_void(&ips)?;
+        Ok(ips)
+    }
+
+    fn target(&self, spec: &Option<String>, domain: &str) -> Result<String, Abort> {
+        match spec {
+            Some(spec) => {
+                expand(spec, domain, self.context).map_err(|e| Abort::Perm(e.to_string()))
+            }
+            None => Ok(domain.into()),
+        }
+    }
+
+    /// Whether any of the addresses is within the prefix length of the connecting address.
+    fn matches_any(&self, ips: &[IpAddr], prefix4: u8, prefix6: u8) -> bool {
+        ips.iter().any(|ip| {
+            let prefix = if ip.is_ipv4() { prefix4 } else { prefix6 };
+            Network { ip: *ip, prefix }.contains(self.context.ip)
+        })
+    }
+
+    fn matches(
+        &mut self,
+        mechanism: &Mechanism,
+        domain: &str,
+        chain: &mut Vec<String>,
+    ) -> Result<Option<Matched>, Abort> {
+        if mechanism.needs_lookup() {
+            self.count_lookup()?;
+        }
+        let matched = |matches: bool| {
+            Ok(matches.then(|| Matched {
+                chain: chain.clone(),
+                directive: Directive {
+                    qualifier: Qualifier::Pass,
+                    mechanism: mechanism.clone(),
+                },
+            }))
+        };
+        match mechanism {
+            Mechanism::All => matched(true),
+            Mechanism::Ip4(ip, prefix) => matched(
+                Network {
+                    ip: IpAddr::V4(*ip),
+                    prefix: *prefix,
+                }
+                .contains(self.context.ip),
+            ),
+            Mechanism::Ip6(ip, prefix) => matched(
+                Network {
+                    ip: IpAddr::V6(*ip),
+                    prefix: *prefix,
+                }
+                .contains(self.context.ip),
+            ),
+            Mechanism::A {
+                domain: spec,
+                prefix4,
+                prefix6,
+            } => {
+                let target = self.target(spec, domain)?;
+                let ips = self.ips(&target)?;
+                matched(self.matches_any(&ips, *prefix4, *prefix6))
+            }
+            Mechanism::Mx {
+                domain: spec,
+                prefix4,
+                prefix6,
+            } => {
+                let target = self.target(spec, domain)?;
+                let exchanges = self
+                    .dns
+                    .lookup_mx(&target)
+                    .map_err(|e| Abort::Temp(e.to_string()))?;
+                self.count_void(&exchanges)?;
+                if exchanges.len() > MX_LIMIT {
+                    return Err(Abort::Perm(format!(
+                        "{target} has more than {MX_LIMIT} MX records"
+                    )));
+                }
+                for exchange in exchanges {
+                    let ips = self.ips(&exchange)?;
+                    if self.matches_any(&ips, *prefix4, *prefix6) {
+                        return matched(true);
+                    }
+                }
+                matched(false)
+            }
+            Mechanism::Ptr(_) => matched(false),
+            Mechanism::Exists(spec) => {
+                let target = self.target(&Some(spec.clone()), domain)?;
+                let ips = self
+                    .dns
+                    .lookup_ips(&target, false)
+                    .map_err(|e| Abort::Temp(e.to_string()))?;
+                self.count_void(&ips)?;
+                matched(!ips.is_empty())
+            }
+            Mechanism::Include(spec) => {
+                let target = self.target(&Some(spec.clone()), domain)?;
+                chain.push(normalize(&target));
+                let (result, matched) = self.check_host(&target, chain)?;
+                chain.pop();
+                match result {
+                    SpfResult::Pass => Ok(matched),
+                    SpfResult::Fail | SpfResult::Softfail | SpfResult::Neutral => Ok(None),
+                    SpfResult::None => Err(Abort::Perm(format!(
+                        "included domain {target} has no SPF record"
+                    ))),
+                    // Errors of the included record have already aborted evaluation.
+                    SpfResult::TempError | SpfResult::PermError => Ok(None),
+                }
+            }
+        }
+    }
+
+    fn check_host(
+        &mut self,
+        domain: &str,
+        chain: &mut Vec<String>,
+    ) -> Result<(SpfResult, Option<Matched>), Abort> {
+        let records: Vec<String> = self
+            .dns
+            .lookup_txt(domain)
+            .map_err(|e| Abort::Temp(e.to_string()))?
+            .into_iter()
+            .filter(|txt| {
+                let version = txt.split_whitespace().next().unwrap_or_default();
+                version.eq_ignore_ascii_case("v=spf1")
+            })
+            .collect();
+        let txt = match records.as_slice() {
+            [] => return Ok((SpfResult::None, None)),
+            [txt] => txt,
+            _ => {
+                return Err(Abort::Perm(format!(
+                    "{domain} has more than one SPF record"
+                )))
+            }
+        };
+        let record = SpfRecord::parse(txt).map_err(|e| Abort::Perm(format!("{domain}: {e}")))?;
+        for directive in &record.directives {
+            if let Some(mut matched) = self.matches(&directive.mechanism, domain, chain)? {
+                // The qualifier of an include is applied to the result of the included record.
+                if matched.chain.len() == chain.len() {
+                    matched.directive.qualifier = directive.qualifier;
+                }
+                let result = match directive.mechanism {
+                    Mechanism::Include(_) => directive.qualifier.result(),
+                    _ => matched.directive.qualifier.result(),
+                };
+                return Ok((result, Some(matched)));
+            }
+        }
+        if let Some(redirect) = &record.redirect {
+            self.count_lookup()?;
+            let target = self.target(&Some(redirect.clone()), domain)?;
+            chain.push(normalize(&target));
+            let (result, matched) = self.check_host(&target, chain)?;
+            chain.pop();
+            if result == SpfResult::None {
+                return Err(Abort::Perm(format!(
+                    "redirect domain {target} has no SPF record"
+                )));
+            }
+            return Ok((result, matched));
+        }
+        Ok((SpfResult::Neutral, None))
+    }
+}
+
+/// Evaluates the SPF policy of a domain for a connection as described in RFC 7208 Section 4.
+pub fn check_host(dns: &dyn Lookup, context: &Context, domain: &str) -> Evaluation {
+    let mut evaluator = Evaluator {
+        dns,
+        context,
+        lookups: 0,
+        void_lookups: 0,
+    };
+    let domain = normalize(domain);
+    let mut chain = vec![domain.clone()];
+    let (result, matched, error) = match evaluator.check_host(&domain, &mut chain) {
+        Ok((result, matched)) => (result, matched, None),
+        Err(Abort::Temp(e)) => (SpfResult::TempError, None, Some(e)),
+        Err(Abort::Perm(e)) => (SpfResult::PermError, None, Some(e)),
+    };
+    Evaluation {
+        result,
+        matched,
+        lookups: evaluator.lookups,
+        void_lookups: evaluator.void_lookups,
+        error,
+    }
+}
+
+/// Whether adding an include to the SPF record of a domain would make it pass for a connection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IncludeFix {
+    /// The included record would authorize the address, using the given number of lookups in total.
+    Passes(usize),
+    /// The included record would authorize the address, but exceed the lookup limit.
+    ExceedsLimit(usize),
+    /// The included record does not authorize the address.
+    NoMatch(SpfResult),
+}
+
+impl fmt::Display for IncludeFix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IncludeFix::Passes(lookups) => write!(f, "passes ({lookups}/{LOOKUP_LIMIT} lookups)"),
+            IncludeFix::ExceedsLimit(lookups) => {
+                write!(
+                    f,
+                    "would pass, but exceeds limit ({lookups}/{LOOKUP_LIMIT} lookups)"
+                )
+            }
+            IncludeFix::NoMatch(result) => write!(f, "does not help ({})", result.as_str()),
+        }
+    }
+}
+
+/// Checks whether adding `include:<include>` in front of the record of a domain would make it pass.
+///
+/// The lookups of the current record are counted in full, as an added include is evaluated before
+/// its other mechanisms in the worst case.
+pub fn try_include(
+    dns: &dyn Lookup,
+    context: &Context,
+    domain: &str,
+    include: &str,
+) -> Result<IncludeFix, Error> {
+    let included = check_host(dns, context, include);
+    if included.result != SpfResult::Pass {
+        return Ok(IncludeFix::NoMatch(included.result));
+    }
+    let mut current = 0;
+    if let Some(txt) = crate::dns::lookup_spf(dns, domain)? {
+        count_lookups(dns, &txt, 0, &mut current);
+    }
+    // The include itself, the included records and the lookups already made by the record.
+    let mut total = current + 1;
+    count_lookups_for(dns, include, 0, &mut total);
+    Ok(match total {
+        total if total > LOOKUP_LIMIT => IncludeFix::ExceedsLimit(total),
+        total => IncludeFix::Passes(total),
+    })
+}
+
+/// Adds the lookups of the record of a domain and all records it includes or redirects to.
+fn count_lookups_for(dns: &dyn Lookup, domain: &str, depth: usize, total: &mut usize) {
+    if let Ok(Some(txt)) = crate::dns::lookup_spf(dns, domain) {
+        count_lookups(dns, &txt, depth, total);
+    }
+}
+
+/// Adds the lookups of a record and all records it includes or redirects to.
+///
+/// Counting stops once the total exceeds the limit, as records including each other several times
+/// would take exponentially many lookups to count in full.
+fn count_lookups(dns: &dyn Lookup, txt: &str, depth: usize, total: &mut usize) {
+    // Guard against include loops, which exceed the limit anyway.
+    if depth > LOOKUP_LIMIT || *total > LOOKUP_LIMIT {
+        return;
+    }
+    let Ok(record) = SpfRecord::parse(txt) else {
+        return;
+    };
+    *total += record.lookups();
+    let nested = record
+        .directives
+        .iter()
+        .filter_map(|directive| match &directive.mechanism {
+            Mechanism::Include(domain) => Some(domain),
+            _ => None,
+        })
+        .chain(record.redirect.as_ref());
+    for domain in nested {
+        if *total > LOOKUP_LIMIT {
+            return;
+        }
+        count_lookups_for(dns, domain, depth + 1, total);
+    }
+}
+
+/// The evaluation of a failing SPF result from reports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub source_ip: IpAddr,
+    /// The domain whose SPF policy was evaluated.
+    pub domain: String,
+    /// The number of messages with this result.
+    pub count: u64,
+    /// The result the reporters determined.
+    pub reported: SpfResult,
+    pub evaluation: Evaluation,
+    /// The outcome of adding each of the candidate includes.
+    pub fixes: Vec<(String, IncludeFix)>,
+}
+
+/// Evaluates the SPF policies behind the failing MAIL FROM results of reports.
+///
+/// Results are grouped by source IP and domain. Each candidate include is checked for whether adding
+/// it would make the policy pass.
+pub fn explain_failures(
+    dns: &dyn Lookup,
+    feedbacks: &[Feedback],
+    includes: &[String],
+) -> Result<Vec<Explanation>, Error> {
+    let mut failures: BTreeMap<(IpAddr, String), (u64, SpfResult)> = BTreeMap::new();
+    for record in feedbacks.iter().flat_map(|feedback| &feedback.records) {
+        for spf in &record.auth_results.spf {
+            if !matches!(spf.result, SpfResult::Fail | SpfResult::Softfail)
+                || spf.scope == Some(SpfDomainScope::Helo)
+            {
+                continue;
+            }
+            let key = (record.row.source_ip, normalize(&spf.domain));
+            let (count, _) = failures.entry(key).or_insert((0, spf.result));
+            *count += u64::from(record.row.count);
+        }
+    }
+    failures
+        .into_iter()
+        .map(|((source_ip, domain), (count, reported))| {
+            let context = Context::new(source_ip, &domain);
+            let evaluation = check_host(dns, &context, &domain);
+            let fixes = match evaluation.result {
+                SpfResult::Pass => vec![],
+                _ => includes
+                    .iter()
+                    .map(|include| {
+                        Ok((
+                            include.clone(),
+                            try_include(dns, &context, &domain, include)?,
+                        ))
+                    })
+                    .collect::<Result<_, Error>>()?,
+            };
+            Ok(Explanation {
+                source_ip,
+                domain,
+                count,
+                reported,
+                evaluation,
+                fixes,
+            })
+        })
+        .collect()
+}
+
+/// A term of an SPF record and the records and addresses it expands to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    /// The domain whose record contains the term.
+    pub domain: String,
+    /// The term, or the domain for the record the expansion starts at.
+    pub term: String,
+    /// Whether matching the term authorizes an address, considering the qualifiers of includes.
+    pub authorizes: bool,
+    /// The networks the term matches.
+    pub networks: Vec<Network>,
+    /// The lookups of the term counting towards the limit.
+    pub lookups: usize,
+    /// The lookups of the term returning no records.
+    pub void_lookups: usize,
+    /// Why the term could not be expanded, or a remark about it.
+    pub note: Option<String>,
+    /// Whether the record of the term was not expanded, as the lookup limit was already exceeded.
+    pub truncated: bool,
+    /// The terms of the included or redirected to record.
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn new(domain: &str, term: String, authorizes: bool) -> Self {
+        Self {
+            domain: domain.into(),
+            term,
+            authorizes,
+            networks: vec![],
+            lookups: 0,
+            void_lookups: 0,
+            note: None,
+            truncated: false,
+            children: vec![],
+        }
+    }
+
+    /// The lookups of this term and all terms below it.
+    pub fn total_lookups(&self) -> usize {
+        self.lookups + self.children.iter().map(Node::total_lookups).sum::<usize>()
+    }
+
+    /// The void lookups of this term and all terms below it.
+    pub fn total_void_lookups(&self) -> usize {
+        self.void_lookups
+            + self
+                .children
+                .iter()
+                .map(Node::total_void_lookups)
+                .sum::<usize>()
+    }
+
+    /// This node and all nodes below it in depth-first order, with their depth.
+    pub fn walk(&self) -> Vec<(usize, &Node)> {
+        let mut nodes = vec![(0, self)];
+        for child in &self.children {
+            nodes.extend(
+                child
+                    .walk()
+                    .into_iter()
+                    .map(|(depth, node)| (depth + 1, node)),
+            );
+        }
+        nodes
+    }
+
+    /// Looks up the addresses of a name in both address families, counting a void lookup if
+    /// there are none.
+    fn resolve(&mut self, dns: &dyn Lookup, name: &str, prefix4: u8, prefix6: u8) {
+        let mut ips = vec![];
+        for ipv6 in [false, true] {
+            match dns.lookup_ips(name, ipv6) {
+                Ok(found) => ips.extend(found),
+                Err(e) => {
+                    self.note = Some(e.to_string());
+                    return;
+                }
+            }
+        }
+        if ips.is_empty() {
+            self.void_lookups += 1;
+        }
+        self.networks.extend(ips.into_iter().map(|ip| Network {
+            ip,
+            prefix: if ip.is_ipv4() { prefix4 } else { prefix6 },
+        }));
+    }
+}
+
+/// The state of expanding a record with the records it includes or redirects to.
+struct Expander<'a> {
+    dns: &'a dyn Lookup,
+    /// The domains of the records being expanded, to detect loops.
+    chain: Vec<String>,
+    /// The lookups of the terms expanded so far.
+    lookups: usize,
+    /// The expansions of the records of domains, by domain and whether they authorize addresses.
+    expanded: HashMap<(String, bool), Node>,
+}
+
+impl Expander<'_> {
+    /// Expands the terms of the record of a domain into the children of a node.
+    ///
+    /// Once the terms expanded so far exceed the lookup limit, further records are not expanded,
+    /// as records including each other several times would take exponentially many lookups.
+    /// Records already expanded are reused. Records nested more deeply than the lookup limit
+    /// allows are not expanded either.
+    fn expand_record(&mut self, node: &mut Node, domain: &str) {
+        if self.chain.iter().any(|seen| seen == domain) {
+            node.note = Some(format!("{domain} is included in a loop"));
+            return;
+        }
+        if self.chain.len() > LOOKUP_LIMIT {
+            node.truncated = true;
+            node.note = Some(format!(
+                "{domain} is not expanded, as includes are nested too deeply"
+            ));
+            return;
+        }
+        if self.lookups > LOOKUP_LIMIT {
+            node.truncated = true;
+            node.note = Some(format!(
+                "{domain} is not expanded, as the lookup limit is exceeded"
+            ));
+            return;
+        }
+        let key = (domain.to_string(), node.authorizes);
+        if let Some(expanded) = self.expanded.get(&key) {
+            self.lookups += expanded.total_lookups();
+            node.void_lookups += expanded.void_lookups;
+            node.note.clone_from(&expanded.note);
+            node.children.clone_from(&expanded.children);
+            return;
+        }
+        let mut expanded = Node::new(domain, domain.to_string(), node.authorizes);
+        self.expand_terms(&mut expanded, domain);
+        node.void_lookups += expanded.void_lookups;
+        node.note.clone_from(&expanded.note);
+        node.children.clone_from(&expanded.children);
+        self.expanded.insert(key, expanded);
+    }
+
+    fn expand_terms(&mut self, node: &mut Node, domain: &str) {
+        let dns = self.dns;
+        let txt = match crate::dns::lookup_spf(dns, domain) {
+            Ok(Some(txt)) => txt,
+            Ok(None) => {
+                node.void_lookups += 1;
+                node.note = Some(format!("{domain} has no single SPF record"));
+                return;
+            }
+            Err(e) => {
+                node.note = Some(e.to_string());
+                return;
+            }
+        };
+        let record = match SpfRecord::parse(&txt) {
+            Ok(record) => record,
+            Err(e) => {
+                node.note = Some(e.to_string());
+                return;
+            }
+        };
+        // Names with macros depend on the connection and cannot be expanded.
+        let target = |spec: Option<&str>| match spec {
+            Some(spec) if spec.contains('%') => None,
+            Some(spec) => Some(normalize(spec)),
+            None => Some(domain.to_string()),
+        };
+        self.chain.push(domain.into());
+        let mut has_all = false;
+        for directive in &record.directives {
+            let authorizes = node.authorizes && directive.qualifier == Qualifier::Pass;
+            let mut child = Node::new(domain, directive.to_string(), authorizes);
+            if directive.mechanism.needs_lookup() {
+                child.lookups = 1;
+                self.lookups += 1;
+            }
+            match &directive.mechanism {
+                Mechanism::All => {
+                    has_all = true;
+                    if authorizes {
+                        child.networks = vec![
+                            Network {
+                                ip: Ipv4Addr::UNSPECIFIED.into(),
+                                prefix: 0,
+                            },
+                            Network {
+                                ip: Ipv6Addr::UNSPECIFIED.into(),
+                                prefix: 0,
+                            },
+                        ];
+                        child.note = Some("authorizes all addresses".into());
+                    }
+                }
+                Mechanism::Ip4(ip, prefix) => child.networks.push(Network {
+                    ip: IpAddr::V4(*ip),
+                    prefix: *prefix,
+                }),
+                Mechanism::Ip6(ip, prefix) => child.networks.push(Network {
+                    ip: IpAddr::V6(*ip),
+                    prefix: *prefix,
+                }),
+                Mechanism::Include(spec) => match target(Some(spec)) {
+                    Some(target) => self.expand_record(&mut child, &target),
+                    None => child.note = Some("depends on macros".into()),
+                },
+                Mechanism::A {
+                    domain: spec,
+                    prefix4,
+                    prefix6,
+                } => match target(spec.as_deref()) {
+                    Some(target) => child.resolve(dns, &target, *prefix4, *prefix6),
+                    None => child.note = Some("depends on macros".into()),
+                },
+                Mechanism::Mx {
+                    domain: spec,
+                    prefix4,
+                    prefix6,
+                } => match target(spec.as_deref()).map(|target| dns.lookup_mx(&target)) {
+                    Some(Ok(exchanges)) => {
+                        if exchanges.is_empty() {
+                            child.void_lookups += 1;
+                        }
+                        if exchanges.len() > MX_LIMIT {
+                            child.note = Some(format!("more than {MX_LIMIT} MX records"));
+                        }
+                        for exchange in exchanges.iter().take(MX_LIMIT) {
+                            child.resolve(dns, exchange, *prefix4, *prefix6);
+                        }
+                    }
+                    Some(Err(e)) => child.note = Some(e.to_string()),
+                    None => child.note = Some("depends on macros".into()),
+                },
+                Mechanism::Ptr(_) => child.note = Some("ptr is not recommended".into()),
+                Mechanism::Exists(_) => child.note = Some("depends on the queried name".into()),
+            }
+            node.children.push(child);
+        }
+        if let Some(redirect) = &record.redirect {
+            let mut child = Node::new(domain, format!("redirect={redirect}"), node.authorizes);
+            if has_all {
+                child.note = Some("ignored because of all".into());
+            } else {
+                child.lookups = 1;
+                self.lookups += 1;
+                match target(Some(redirect)) {
+                    Some(target) => self.expand_record(&mut child, &target),
+                    None => child.note = Some("depends on macros".into()),
+                }
+            }
+            node.children.push(child);
+        }
+        self.chain.pop();
+    }
+}
+
+/// A network authorized by a term that another term authorizes as well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    pub network: Network,
+    /// The domain whose record contains the term.
+    pub domain: String,
+    pub term: String,
+    /// The network of the other term, containing the duplicate.
+    pub covered_by: Network,
+    pub covering_domain: String,
+    pub covering_term: String,
+}
+
+/// The expansion of the SPF record of a domain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpfTree {
+    pub root: Node,
+    /// The networks authorized by the record, without those contained in others.
+    ///
+    /// The order of terms is not considered, so a network excluded by an earlier term is listed
+    /// if a later term authorizes it.
+    pub authorized: Vec<Network>,
+    pub duplicates: Vec<Duplicate>,
+    /// The source IPs reported for the domain outside of the authorized networks, with the number
+    /// of messages.
+    pub unauthorized: Vec<(IpAddr, u64)>,
+}
+
+impl SpfTree {
+    pub fn domain(&self) -> &str {
+        &self.root.domain
+    }
+
+    /// Whether expanding the whole record exceeds a lookup limit.
+    pub fn limit_exceeded(&self) -> bool {
+        self.root.total_lookups() > LOOKUP_LIMIT
+            || self.root.total_void_lookups() > VOID_LOOKUP_LIMIT
+    }
+
+    pub fn is_authorized(&self, ip: IpAddr) -> bool {
+        self.authorized.iter().any(|network| network.contains(ip))
+    }
+}
+
+/// Expands the SPF record of a domain with all records it includes or redirects to.
+///
+/// Unlike evaluation, every term is expanded, so the lookups are those of the worst case in which
+/// no term matches.
+pub fn build_tree(dns: &dyn Lookup, domain: &str) -> SpfTree {
+    let domain = normalize(domain);
+    let mut root = Node::new(&domain, domain.clone(), true);
+    let mut expander = Expander {
+        dns,
+        chain: vec![],
+        lookups: 0,
+        expanded: HashMap::new(),
+    };
+    expander.expand_record(&mut root, &domain);
+
+    let authorizing: Vec<(Network, &Node)> = root
+        .walk()
+        .into_iter()
+        .filter(|(_, node)| node.authorizes)
+        .flat_map(|(_, node)| node.networks.iter().map(move |network| (*network, node)))
+        .collect();
+    let mut authorized = vec![];
+    let mut duplicates = vec![];
+    for (i, (network, node)) in authorizing.iter().enumerate() {
+        // Of equal networks, the first one is kept.
+        let covering = authorizing.iter().enumerate().find(|(j, (other, _))| {
+            *j != i && other.covers(network) && (other != network || *j < i)
+        });
+        match covering {
+            Some((_, (covered_by, covering_node))) => duplicates.push(Duplicate {
+                network: *network,
+                domain: node.domain.clone(),
+                term: node.term.clone(),
+                covered_by: *covered_by,
+                covering_domain: covering_node.domain.clone(),
+                covering_term: covering_node.term.clone(),
+            }),
+            None => authorized.push(*network),
+        }
+    }
+    authorized.sort();
+    SpfTree {
+        root,
+        authorized,
+        duplicates,
+        unauthorized: vec![],
+    }
+}
+
+/// Expands the SPF records of domains, defaulting to the domains of the SPF results in reports.
+///
+/// The source IPs reported for each domain are checked against its authorized networks.
+pub fn analyze_trees(dns: &dyn Lookup, domains: &[String], feedbacks: &[Feedback]) -> Vec<SpfTree> {
+    let mut sources: BTreeMap<String, BTreeMap<IpAddr, u64>> = BTreeMap::new();
+    for record in feedbacks.iter().flat_map(|feedback| &feedback.records) {
+        for spf in &record.auth_results.spf {
+            let domain = normalize(&spf.domain);
+            if domain.is_empty() {
+                continue;
+            }
+            *sources
+                .entry(domain)
+                .or_default()
+                .entry(record.row.source_ip)
+                .or_default() += u64::from(record.row.count);
+        }
+    }
+    let domains: Vec<String> = match domains {
+        [] => sources.keys().cloned().collect(),
+        domains => domains.iter().map(|domain| normalize(domain)).collect(),
+    };
+    domains
+        .into_iter()
+        .map(|domain| {
+            let mut tree = build_tree(dns, &domain);
+            tree.unauthorized = sources
+                .remove(&domain)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(ip, _)| !tree.is_authorized(*ip))
+                .collect();
+            tree
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::net::IpAddr;
+
+    use crate::dmarc::SpfResult;
+    use crate::dns::{Lookup, TxtLookup};
+    use crate::zone::Zone;
+    use crate::Error;
+
+    use super::{build_tree, check_host, expand, try_include, Context, IncludeFix, SpfRecord};
+
+    fn zone() -> Zone {
+        let mut zone = Zone::default();
+        zone.parse(
+            r#"
+@        TXT "v=spf1 ip4:192.0.2.0/24 include:_spf.example.net mx ~all"
+@        MX  10 mx
+mx       A   198.51.100.25
+loop     TXT "v=spf1 include:loop.example.com -all"
+redir    TXT "v=spf1 redirect=example.com"
+dup      TXT "v=spf1 ip4:203.0.113.0/24 include:_spf.example.net a:void.example.com -all"
+twice    TXT "v=spf1 include:_spf.example.net include:_spf.example.net"
+$ORIGIN example.net.
+_spf     TXT "v=spf1 ip4:203.0.113.0/25 -all"
+esp      TXT "v=spf1 ip4:203.0.113.128/25 -all"
+"#,
+            "example.com",
+        )
+        .unwrap();
+        zone
+    }
+
+    /// The test zone with records including the record of the next level three times each, down to
+    /// 20 levels.
+    fn fan_out_zone() -> Zone {
+        let mut text = String::new();
+        for level in 0..20 {
+            let next = format!(" include:level{}.example.com", level + 1);
+            text.push_str(&format!(
+                "level{level} TXT \"v=spf1{} -all\"\n",
+                next.repeat(3)
+            ));
+        }
+        let mut zone = zone();
+        zone.parse(&text, "example.com").unwrap();
+        zone
+    }
+
+    /// Counts the TXT lookups made in a zone.
+    struct Counting<'a> {
+        zone: &'a Zone,
+        queries: Cell<usize>,
+    }
+
+    impl TxtLookup for Counting<'_> {
+        fn lookup_txt(&self, name: &str) -> Result<Vec<String>, Error> {
+            self.queries.set(self.queries.get() + 1);
+            self.zone.lookup_txt(name)
+        }
+    }
+
+    impl Lookup for Counting<'_> {
+        fn lookup_ips(&self, name: &str, ipv6: bool) -> Result<Vec<IpAddr>, Error> {
+            self.zone.lookup_ips(name, ipv6)
+        }
+
+        fn lookup_mx(&self, name: &str) -> Result<Vec<String>, Error> {
+            self.zone.lookup_mx(name)
+        }
+    }
+
+    fn context(ip: &str) -> Context {
+        Context::new(ip.parse().unwrap(), "example.com")
+    }
+
+    #[test]
+    fn parse_spf_record() {
+        let record = SpfRecord::parse(
+            "v=spf1 a:mail.%{d}/24//64 -ip6:2001:db8::/32 ?all redirect=x.example",
+        )
+        .unwrap();
+        let directives: Vec<String> = record.directives.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            directives,
+            vec!["a:mail.%{d}/24//64", "-ip6:2001:db8::/32", "?all"]
+        );
+        assert_eq!(record.redirect.as_deref(), Some("x.example"));
+        assert_eq!(record.lookups(), 2);
+        assert!(SpfRecord::parse("v=spf1 ip4:192.0.2.0/33").is_err());
+        assert!(SpfRecord::parse("v=spf1 foo").is_err());
+    }
+
+    #[test]
+    fn expand_macros() {
+        let context = Context {
+            ip: "192.0.2.3".parse().unwrap(),
+            sender: "strong-bad@email.example.com".into(),
+            helo: None,
+        };
+        let expand = |spec| expand(spec, "email.example.com", &context).unwrap();
+        assert_eq!(
+            expand("%{ir}.%{v}._spf.%{d2}"),
+            "3.2.0.192.in-addr._spf.example.com"
+        );
+        assert_eq!(expand("%{l-}.%{o}"), "strong.bad.email.example.com");
+        assert_eq!(expand("%{lr-}"), "bad.strong");
+    }
+
+    #[test]
+    fn evaluate_mechanisms() {
+        let zone = zone();
+        let evaluation = check_host(&zone, &context("203.0.113.7"), "example.com");
+        assert_eq!(evaluation.result, SpfResult::Pass);
+        let matched = evaluation.matched.unwrap();
+        assert_eq!(matched.directive.to_string(), "ip4:203.0.113.0/25");
+        assert_eq!(matched.chain, vec!["example.com", "_spf.example.net"]);
+        assert_eq!(matched.depth(), 1);
+        assert_eq!(evaluation.lookups, 1);
+
+        let evaluation = check_host(&zone, &context("198.51.100.25"), "redir.example.com");
+        assert_eq!(evaluation.result, SpfResult::Pass);
+        assert_eq!(evaluation.matched.unwrap().directive.to_string(), "mx");
+
+        let evaluation = check_host(&zone, &context("203.0.113.200"), "example.com");
+        assert_eq!(evaluation.result, SpfResult::Softfail);
+        assert_eq!(evaluation.matched.unwrap().directive.to_string(), "~all");
+        assert_eq!(evaluation.lookups, 2);
+
+        let evaluation = check_host(&zone, &context("203.0.113.200"), "loop.example.com");
+        assert_eq!(evaluation.result, SpfResult::PermError);
+        assert!(evaluation.limit_exceeded());
+    }
+
+    #[test]
+    fn suggest_include() {
+        let zone = zone();
+        let context = context("203.0.113.200");
+        assert_eq!(
+            try_include(&zone, &context, "example.com", "esp.example.net").unwrap(),
+            IncludeFix::Passes(3)
+        );
+        assert!(matches!(
+            try_include(&zone, &context, "example.com", "_spf.example.net").unwrap(),
+            IncludeFix::NoMatch(SpfResult::Fail)
+        ));
+
+        // Counting stops once the limit is exceeded, instead of expanding every include.
+        let counting = Counting {
+            zone: &fan_out_zone(),
+            queries: Cell::new(0),
+        };
+        let fix = try_include(&counting, &context, "level0.example.com", "esp.example.net");
+        assert!(matches!(fix.unwrap(), IncludeFix::ExceedsLimit(_)));
+        assert_eq!(counting.queries.get(), 6);
+    }
+
+    #[test]
+    fn expand_tree() {
+        let zone = zone();
+        let tree = build_tree(&zone, "example.com");
+        let terms: Vec<(usize, &str)> = tree
+            .root
+            .walk()
+            .into_iter()
+            .map(|(depth, node)| (depth, node.term.as_str()))
+            .collect();
+        assert_eq!(
+            terms,
+            vec![
+                (0, "example.com"),
+                (1, "ip4:192.0.2.0/24"),
+                (1, "include:_spf.example.net"),
+                (2, "ip4:203.0.113.0/25"),
+                (2, "-all"),
+                (1, "mx"),
+                (1, "~all"),
+            ]
+        );
+        assert_eq!(tree.root.total_lookups(), 2);
+        let authorized: Vec<String> = tree.authorized.iter().map(|n| n.to_string()).collect();
+        assert_eq!(
+            authorized,
+            vec!["192.0.2.0/24", "198.51.100.25/32", "203.0.113.0/25"]
+        );
+        assert!(tree.duplicates.is_empty());
+
+        let tree = build_tree(&zone, "dup.example.com");
+        assert_eq!(tree.root.total_lookups(), 2);
+        assert_eq!(tree.root.total_void_lookups(), 1);
+        assert_eq!(tree.authorized.len(), 1);
+        assert_eq!(tree.duplicates.len(), 1);
+        assert_eq!(tree.duplicates[0].domain, "_spf.example.net");
+        assert!(tree.is_authorized("203.0.113.200".parse().unwrap()));
+        assert!(!tree.is_authorized("192.0.2.1".parse().unwrap()));
+
+        let tree = build_tree(&zone, "loop.example.com");
+        let (_, include) = tree.root.walk()[1];
+        assert_eq!(
+            include.note.as_deref(),
+            Some("loop.example.com is included in a loop")
+        );
+
+        // Expansion stops once the limits are exceeded, and records included again are reused.
+        let fan_out = fan_out_zone();
+        let counting = Counting {
+            zone: &fan_out,
+            queries: Cell::new(0),
+        };
+        let tree = build_tree(&counting, "level0.example.com");
+        assert!(tree.limit_exceeded());
+        let notes: Vec<&str> = tree
+            .root
+            .walk()
+            .iter()
+            .filter(|(_, node)| node.truncated)
+            .filter_map(|(_, node)| node.note.as_deref())
+            .collect();
+        assert_eq!(
+            notes[0],
+            "level11.example.com is not expanded, as includes are nested too deeply"
+        );
+        assert!(
+            notes.contains(&"level10.example.com is not expanded, as the lookup limit is exceeded")
+        );
+        assert_eq!(counting.queries.get(), 11);
+
+        let counting = Counting {
+            zone: &zone,
+            queries: Cell::new(0),
+        };
+        let tree = build_tree(&counting, "twice.example.com");
+        assert_eq!(counting.queries.get(), 2);
+        assert_eq!(tree.root.total_lookups(), 2);
+        assert_eq!(tree.root.children[0], tree.root.children[1]);
+    }
+}
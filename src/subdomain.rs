@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::dmarc::{Disposition, DmarcResult};
+use crate::domain::normalize;
+use crate::Feedback;
+
+/// The messages of a RFC5322.From domain under a policy domain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subdomain {
+    pub policy_domain: String,
+    pub header_from: String,
+    /// Whether the subdomain policy `sp` applied rather than `p`.
+    pub uses_sp: bool,
+    /// The disposition requested for failing messages by the latest report.
+    pub requested: Disposition,
+    pub messages: u64,
+    /// The number of messages passing DMARC.
+    pub passed: u64,
+    /// The end of the latest report with the domain.
+    pub last_seen: DateTime<Utc>,
+}
+
+impl Subdomain {
+    /// The share of messages passing DMARC, between 0 and 1.
+    pub fn pass_rate(&self) -> f64 {
+        match self.messages {
+            0 => 0.0,
+            messages => self.passed as f64 / messages as f64,
+        }
+    }
+}
+
+/// Lists the RFC5322.From domains of each policy domain with their volume and pass rate.
+///
+/// The requested disposition is taken from the latest report, in whatever order reports are given.
+pub fn subdomains(feedbacks: &[Feedback]) -> Vec<Subdomain> {
+    let mut subdomains: BTreeMap<(String, String), Subdomain> = BTreeMap::new();
+    for feedback in feedbacks {
+        let policy = &feedback.policy_published;
+        let policy_domain = normalize(&policy.domain);
+        let end = feedback.report_metadata.date_range.end;
+        for record in &feedback.records {
+            let header_from = normalize(&record.identifiers.header_from);
+            let subdomain = subdomains
+                .entry((policy_domain.clone(), header_from.clone()))
+                .or_insert_with(|| Subdomain {
+                    policy_domain: policy_domain.clone(),
+                    header_from: header_from.clone(),
+                    uses_sp: policy.is_subdomain_policy(&header_from),
+                    requested: policy.requested(&header_from),
+                    messages: 0,
+                    passed: 0,
+                    last_seen: end,
+                });
+            if end >= subdomain.last_seen {
+                subdomain.uses_sp = policy.is_subdomain_policy(&header_from);
+                subdomain.requested = policy.requested(&header_from);
+                subdomain.last_seen = end;
+            }
+            let count = u64::from(record.row.count);
+            subdomain.messages += count;
+            let evaluated = &record.row.policy_evaluated;
+            if evaluated.dkim == DmarcResult::Pass || evaluated.spf == DmarcResult::Pass {
+                subdomain.passed += count;
+            }
+        }
+    }
+    subdomains.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use crate::dmarc::{Disposition, DmarcResult};
+    use crate::fixtures;
+
+    use super::subdomains;
+
+    #[test]
+    fn list_subdomains() {
+        let mut feedback = fixtures::report();
+        feedback.policy_published.domain = "example.com".into();
+        feedback.policy_published.p = Disposition::Reject;
+        feedback.policy_published.sp = Disposition::None;
+        let mut record = feedback.records[0].clone();
+        record.row.count = 3;
+        record.identifiers.header_from = "Example.com".into();
+        record.row.policy_evaluated.dkim = DmarcResult::Pass;
+        let mut forgotten = record.clone();
+        forgotten.identifiers.header_from = "old.example.com".into();
+        forgotten.row.policy_evaluated.dkim = DmarcResult::Fail;
+        forgotten.row.policy_evaluated.spf = DmarcResult::Fail;
+        feedback.records = vec![record, forgotten];
+
+        let subdomains = subdomains(&[feedback]);
+        assert_eq!(subdomains.len(), 2);
+        assert_eq!(subdomains[0].header_from, "example.com");
+        assert!(!subdomains[0].uses_sp);
+        assert_eq!(subdomains[0].requested, Disposition::Reject);
+        assert_eq!(subdomains[0].pass_rate(), 1.0);
+        assert!(subdomains[1].uses_sp);
+        assert_eq!(subdomains[1].requested, Disposition::None);
+        assert_eq!(subdomains[1].pass_rate(), 0.0);
+    }
+
+    #[test]
+    fn request_of_latest_report() {
+        let mut latest = fixtures::report();
+        latest.policy_published.domain = "example.com".into();
+        latest.policy_published.p = Disposition::Reject;
+        latest.records.truncate(1);
+        latest.records[0].identifiers.header_from = "example.com".into();
+        let mut earlier = latest.clone();
+        earlier.policy_published.p = Disposition::None;
+        earlier.report_metadata.date_range.end -= TimeDelta::days(1);
+
+        for feedbacks in [[latest.clone(), earlier.clone()], [earlier, latest]] {
+            let subdomains = subdomains(&feedbacks);
+            assert_eq!(subdomains[0].requested, Disposition::Reject);
+        }
+    }
+}
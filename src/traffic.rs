@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::dmarc::{
+    Alignment, DkimResult, DmarcResult, PolicyOverride, Record, SpfDomainScope, SpfResult,
+};
+use crate::domain::{is_aligned, normalize};
+use crate::spf::Network;
+use crate::Feedback;
+
+/// How the messages of a record most likely reached the receiver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Traffic {
+    /// Sent directly by a server authorized for the domain.
+    Direct,
+    /// Relayed by a forwarder, such as a mailbox provider forwarding to another address.
+    Forwarded,
+    /// Redistributed by a mailing list, which usually modifies messages.
+    MailingList,
+    /// Failing DMARC without signs of forwarding.
+    LikelySpoof,
+}
+
+impl Traffic {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Traffic::Direct => "direct",
+            Traffic::Forwarded => "forwarded",
+            Traffic::MailingList => "mailing list",
+            Traffic::LikelySpoof => "likely spoof",
+        }
+    }
+
+    /// Whether the messages were relayed by a forwarder or mailing list.
+    pub fn is_forwarded(&self) -> bool {
+        matches!(self, Traffic::Forwarded | Traffic::MailingList)
+    }
+}
+
+impl fmt::Display for Traffic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The class of a record and the heuristic which determined it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Classification {
+    pub traffic: Traffic,
+    pub reason: &'static str,
+}
+
+/// Classifies records as direct, forwarded, mailing list or likely spoofed traffic.
+#[derive(Debug, Clone, Default)]
+pub struct Classifier {
+    /// Networks of known forwarders, whose messages are considered forwarded.
+    pub forwarders: Vec<Network>,
+}
+
+impl Classifier {
+    /// Classifies a record by the first matching heuristic.
+    ///
+    /// Override reasons given by the reporter take precedence over the source IP and the pattern of
+    /// authentication results.
+    pub fn classify(&self, record: &Record) -> Classification {
+        let classification = |traffic, reason| Classification { traffic, reason };
+        let evaluated = &record.row.policy_evaluated;
+        let header_from = &record.identifiers.header_from;
+        let reasons: Vec<PolicyOverride> = evaluated.reasons.iter().map(|r| r.typ).collect();
+        if reasons.contains(&PolicyOverride::MailingList) {
+            return classification(Traffic::MailingList, "mailing list override");
+        }
+        if reasons.contains(&PolicyOverride::Forwarded)
+            || reasons.contains(&PolicyOverride::TrustedForwarder)
+        {
+            return classification(Traffic::Forwarded, "forwarded override");
+        }
+        let from_forwarder = self
+            .forwarders
+            .iter()
+            .any(|network| network.contains(record.row.source_ip));
+        if from_forwarder {
+            return classification(Traffic::Forwarded, "known forwarder network");
+        }
+
+        let mail_from = record.auth_results.spf.iter().find(|spf| {
+            spf.scope != Some(SpfDomainScope::Helo) && !normalize(&spf.domain).is_empty()
+        });
+        let envelope = record
+            .identifiers
+            .envelope_from
+            .as_deref()
+            .or(mail_from.map(|spf| spf.domain.as_str()));
+        let envelope_aligned =
+            envelope.is_some_and(|envelope| is_aligned(envelope, header_from, Alignment::Relaxed));
+        let spf_failed = mail_from
+            .is_some_and(|spf| matches!(spf.result, SpfResult::Fail | SpfResult::Softfail));
+        // Forwarders keep the signature of the author's domain, while anyone can sign with theirs.
+        let aligned_dkim_passed = record.auth_results.dkim.iter().any(|dkim| {
+            dkim.result == DkimResult::Pass
+                && is_aligned(&dkim.domain, header_from, Alignment::Relaxed)
+        });
+        if spf_failed && aligned_dkim_passed && !envelope_aligned {
+            return classification(Traffic::Forwarded, "SPF fail with DKIM pass");
+        }
+        if evaluated.dkim == DmarcResult::Pass || evaluated.spf == DmarcResult::Pass {
+            return classification(Traffic::Direct, "DMARC pass");
+        }
+        // Lists break the signature of the author's domain and send from their own domain.
+        let aligned_signature_failed = record.auth_results.dkim.iter().any(|dkim| {
+            dkim.result == DkimResult::Fail
+                && is_aligned(&dkim.domain, header_from, Alignment::Relaxed)
+        });
+        let spf_passed = mail_from.is_some_and(|spf| spf.result == SpfResult::Pass);
+        if aligned_signature_failed && spf_passed && !envelope_aligned {
+            return classification(Traffic::MailingList, "broken signature of From domain");
+        }
+        classification(Traffic::LikelySpoof, "DMARC fail")
+    }
+}
+
+/// The traffic of a From domain by class.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Breakdown {
+    pub domain: String,
+    /// The number of messages by class.
+    pub messages: BTreeMap<Traffic, u64>,
+    /// The number of messages counted for the pass rate.
+    pub counted: u64,
+    /// The number of counted messages passing DMARC.
+    pub passed: u64,
+}
+
+impl Breakdown {
+    pub fn total(&self) -> u64 {
+        self.messages.values().sum()
+    }
+
+    /// The share of counted messages passing DMARC, between 0 and 1.
+    pub fn pass_rate(&self) -> f64 {
+        match self.counted {
+            0 => 0.0,
+            counted => self.passed as f64 / counted as f64,
+        }
+    }
+}
+
+/// Breaks down the traffic of each RFC5322.From domain by class.
+///
+/// Forwarded and mailing list traffic can be excluded from the pass rate, as the failures of
+/// forwarded messages cannot be fixed by the sender.
+pub fn breakdown(
+    feedbacks: &[Feedback],
+    classifier: &Classifier,
+    exclude_forwarded: bool,
+) -> Vec<Breakdown> {
+    let mut breakdowns: BTreeMap<String, Breakdown> = BTreeMap::new();
+    for record in feedbacks.iter().flat_map(|feedback| &feedback.records) {
+        let domain = normalize(&record.identifiers.header_from);
+        let traffic = classifier.classify(record).traffic;
+        let count = u64::from(record.row.count);
+        let breakdown = breakdowns
+            .entry(domain.clone())
+            .or_insert_with(|| Breakdown {
+                domain,
+                ..Default::default()
+            });
+        *breakdown.messages.entry(traffic).or_default() += count;
+        if exclude_forwarded && traffic.is_forwarded() {
+            continue;
+        }
+        breakdown.counted += count;
+        let evaluated = &record.row.policy_evaluated;
+        if evaluated.dkim == DmarcResult::Pass || evaluated.spf == DmarcResult::Pass {
+            breakdown.passed += count;
+        }
+    }
+    breakdowns.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dmarc::{
+        DkimAuthResult, DkimResult, DmarcResult, PolicyOverride, PolicyOverrideReason, Record,
+        SpfResult,
+    };
+    use crate::fixtures;
+
+    use super::{breakdown, Classifier, Traffic};
+
+    fn record() -> Record {
+        let feedback = fixtures::report();
+        let mut record = feedback.records[0].clone();
+        record.row.source_ip = "198.51.100.1".parse().unwrap();
+        record.identifiers.header_from = "example.com".into();
+        record.identifiers.envelope_from = Some("example.com".into());
+        record.row.policy_evaluated.reasons.clear();
+        record.row.policy_evaluated.dkim = DmarcResult::Pass;
+        record.row.policy_evaluated.spf = DmarcResult::Pass;
+        record.auth_results.dkim = vec![DkimAuthResult {
+            domain: "example.com".into(),
+            selector: Some("s1".into()),
+            result: DkimResult::Pass,
+            human_result: None,
+        }];
+        record.auth_results.spf.truncate(1);
+        record.auth_results.spf[0].domain = "example.com".into();
+        record.auth_results.spf[0].scope = None;
+        record.auth_results.spf[0].result = SpfResult::Pass;
+        record
+    }
+
+    #[test]
+    fn classify_records() {
+        let classifier = Classifier {
+            forwarders: vec!["192.0.2.0/24".parse().unwrap()],
+        };
+        let direct = record();
+        assert_eq!(classifier.classify(&direct).traffic, Traffic::Direct);
+
+        let mut forwarded = record();
+        forwarded.identifiers.envelope_from = Some("forwarder.example.net".into());
+        forwarded.auth_results.spf[0].domain = "forwarder.example.net".into();
+        forwarded.auth_results.spf[0].result = SpfResult::Fail;
+        forwarded.row.policy_evaluated.spf = DmarcResult::Fail;
+        assert_eq!(classifier.classify(&forwarded).traffic, Traffic::Forwarded);
+
+        let mut list = forwarded.clone();
+        list.auth_results.spf[0].result = SpfResult::Pass;
+        list.auth_results.dkim[0].result = DkimResult::Fail;
+        list.row.policy_evaluated.dkim = DmarcResult::Fail;
+        assert_eq!(classifier.classify(&list).traffic, Traffic::MailingList);
+
+        let mut signed_by_other = forwarded.clone();
+        signed_by_other.auth_results.dkim[0].domain = "spoofer.example.org".into();
+        signed_by_other.row.policy_evaluated.dkim = DmarcResult::Fail;
+        assert_eq!(
+            classifier.classify(&signed_by_other).traffic,
+            Traffic::LikelySpoof
+        );
+
+        let mut spoof = list.clone();
+        spoof.auth_results.dkim.clear();
+        assert_eq!(classifier.classify(&spoof).traffic, Traffic::LikelySpoof);
+        spoof.row.source_ip = "192.0.2.1".parse().unwrap();
+        assert_eq!(classifier.classify(&spoof).traffic, Traffic::Forwarded);
+        spoof.row.policy_evaluated.reasons = vec![PolicyOverrideReason {
+            typ: PolicyOverride::MailingList,
+            comment: None,
+        }];
+        assert_eq!(classifier.classify(&spoof).traffic, Traffic::MailingList);
+    }
+
+    #[test]
+    fn exclude_forwarded_from_pass_rate() {
+        let mut feedback = fixtures::report();
+        let direct = record();
+        let mut list = record();
+        list.row.policy_evaluated.dkim = DmarcResult::Fail;
+        list.row.policy_evaluated.spf = DmarcResult::Fail;
+        list.row.policy_evaluated.reasons = vec![PolicyOverrideReason {
+            typ: PolicyOverride::MailingList,
+            comment: None,
+        }];
+        feedback.records = vec![direct, list];
+        let feedbacks = [feedback];
+        let classifier = Classifier::default();
+
+        let all = &breakdown(&feedbacks, &classifier, false)[0];
+        assert_eq!(all.pass_rate(), 0.5);
+        let direct = &breakdown(&feedbacks, &classifier, true)[0];
+        assert_eq!(direct.pass_rate(), 1.0);
+        assert_eq!(direct.total(), all.total());
+    }
+}
@@ -0,0 +1,280 @@
+use std::io;
+use std::io::Write;
+
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::Writer;
+
+use crate::dmarc::{
+    AuthResult, DkimAuthResult, Identifier, PolicyEvaluated, PolicyOverrideReason, PolicyPublished,
+    Record, ReportMetadata, SpfAuthResult,
+};
+use crate::{Error, Feedback};
+
+/// Writes an element containing only text.
+fn text<W: Write>(writer: &mut Writer<W>, name: &str, text: &str) -> io::Result<()> {
+    writer
+        .create_element(name)
+        .write_text_content(BytesText::new(text))?;
+    Ok(())
+}
+
+/// Writes an element containing only text, if the text is present.
+fn optional_text<W: Write>(
+    writer: &mut Writer<W>,
+    name: &str,
+    value: &Option<String>,
+) -> io::Result<()> {
+    match value {
+        Some(value) => text(writer, name, value),
+        None => Ok(()),
+    }
+}
+
+fn write_report_metadata<W: Write>(
+    writer: &mut Writer<W>,
+    metadata: &ReportMetadata,
+) -> io::Result<()> {
+    text(writer, "org_name", &metadata.org_name)?;
+    text(writer, "email", &metadata.email)?;
+    optional_text(writer, "extra_contact_info", &metadata.extra_contact_info)?;
+    text(writer, "report_id", &metadata.report_id)?;
+    writer
+        .create_element("date_range")
+        .write_inner_content(|writer| {
+            text(
+                writer,
+                "begin",
+                &metadata.date_range.begin.timestamp().to_string(),
+            )?;
+            text(
+                writer,
+                "end",
+                &metadata.date_range.end.timestamp().to_string(),
+            )
+        })?;
+    for error in &metadata.errors {
+        text(writer, "error", error)?;
+    }
+    Ok(())
+}
+
+fn write_policy_published<W: Write>(
+    writer: &mut Writer<W>,
+    policy: &PolicyPublished,
+) -> io::Result<()> {
+    text(writer, "domain", &policy.domain)?;
+    if let Some(adkim) = &policy.adkim {
+        text(writer, "adkim", adkim.as_str())?;
+    }
+    if let Some(aspf) = &policy.aspf {
+        text(writer, "aspf", aspf.as_str())?;
+    }
+    text(writer, "p", policy.p.as_str())?;
+    text(writer, "sp", policy.sp.as_str())?;
+    text(writer, "pct", &policy.pct.to_string())?;
+    text(writer, "fo", &policy.fo)
+}
+
+fn write_reason<W: Write>(writer: &mut Writer<W>, reason: &PolicyOverrideReason) -> io::Result<()> {
+    text(writer, "type", reason.typ.as_str())?;
+    optional_text(writer, "comment", &reason.comment)
+}
+
+fn write_policy_evaluated<W: Write>(
+    writer: &mut Writer<W>,
+    policy: &PolicyEvaluated,
+) -> io::Result<()> {
+    text(writer, "disposition", policy.disposition.as_str())?;
+    text(writer, "dkim", policy.dkim.as_str())?;
+    text(writer, "spf", policy.spf.as_str())?;
+    for reason in &policy.reasons {
+        writer
+            .create_element("reason")
+            .write_inner_content(|writer| write_reason(writer, reason))?;
+    }
+    Ok(())
+}
+
+fn write_identifiers<W: Write>(writer: &mut Writer<W>, identifiers: &Identifier) -> io::Result<()> {
+    optional_text(writer, "envelope_to", &identifiers.envelope_to)?;
+    optional_text(writer, "envelope_from", &identifiers.envelope_from)?;
+    text(writer, "header_from", &identifiers.header_from)
+}
+
+fn write_dkim<W: Write>(writer: &mut Writer<W>, dkim: &DkimAuthResult) -> io::Result<()> {
+    text(writer, "domain", &dkim.domain)?;
+    optional_text(writer, "selector", &dkim.selector)?;
+    text(writer, "result", dkim.result.as_str())?;
+    optional_text(writer, "human_result", &dkim.human_result)
+}
+
+fn write_spf<W: Write>(writer: &mut Writer<W>, spf: &SpfAuthResult) -> io::Result<()> {
+    text(writer, "domain", &spf.domain)?;
+    if let Some(scope) = &spf.scope {
+        text(writer, "scope", scope.as_str())?;
+    }
+    text(writer, "result", spf.result.as_str())
+}
+
+fn write_auth_results<W: Write>(writer: &mut Writer<W>, results: &AuthResult) -> io::Result<()> {
+    for dkim in &results.dkim {
+        writer
+            .create_element("dkim")
+            .write_inner_content(|writer| write_dkim(writer, dkim))?;
+    }
+    for spf in &results.spf {
+        writer
+            .create_element("spf")
+            .write_inner_content(|writer| write_spf(writer, spf))?;
+    }
+    Ok(())
+}
+
+fn write_record<W: Write>(writer: &mut Writer<W>, record: &Record) -> io::Result<()> {
+    writer.create_element("row").write_inner_content(|writer| {
+        text(writer, "source_ip", &record.row.source_ip.to_string())?;
+        text(writer, "count", &record.row.count.to_string())?;
+        writer
+            .create_element("policy_evaluated")
+            .write_inner_content(|writer| {
+                write_policy_evaluated(writer, &record.row.policy_evaluated)
+            })?;
+        Ok(())
+    })?;
+    writer
+        .create_element("identifiers")
+        .write_inner_content(|writer| write_identifiers(writer, &record.identifiers))?;
+    writer
+        .create_element("auth_results")
+        .write_inner_content(|writer| write_auth_results(writer, &record.auth_results))?;
+    Ok(())
+}
+
+fn write_feedback<W: Write>(writer: &mut Writer<W>, feedback: &Feedback) -> io::Result<()> {
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    writer
+        .create_element("feedback")
+        .write_inner_content(|writer| {
+            if let Some(version) = feedback.version {
+                text(writer, "version", &format!("{version:?}"))?;
+            }
+            writer
+                .create_element("report_metadata")
+                .write_inner_content(|writer| {
+                    write_report_metadata(writer, &feedback.report_metadata)
+                })?;
+            writer
+                .create_element("policy_published")
+                .write_inner_content(|writer| {
+                    write_policy_published(writer, &feedback.policy_published)
+                })?;
+            for record in &feedback.records {
+                writer
+                    .create_element("record")
+                    .write_inner_content(|writer| write_record(writer, record))?;
+            }
+            Ok(())
+        })?;
+    writer.get_mut().write_all(b"\n")
+}
+
+/// Writes a report as XML following the aggregate report schema of RFC 7489 Appendix C.
+///
+/// Values which were defaulted while parsing, such as `sp` inheriting from `p`, are written explicitly.
+pub fn write_xml(feedback: &Feedback, writer: impl Write) -> Result<(), Error> {
+    let mut writer = Writer::new_with_indent(writer, b' ', 2);
+    write_feedback(&mut writer, feedback).map_err(Error::WriteOutput)
+}
+
+/// Serializes a report as XML following the aggregate report schema of RFC 7489 Appendix C.
+pub fn to_xml_string(feedback: &Feedback) -> String {
+    let mut xml = vec![];
+    write_xml(feedback, &mut xml).expect("writing to a vector cannot fail");
+    String::from_utf8(xml).expect("XML is written as UTF-8")
+}
+
+/// The file name of a report according to RFC 7489 Section 7.2.1.1, without extension.
+///
+/// The name is made up of the receiver, the policy domain, the covered time range and the report ID.
+pub fn report_file_name(feedback: &Feedback) -> String {
+    let metadata = &feedback.report_metadata;
+    let unsafe_char = |c: char| c == '!' || c == '/' || c == '\\' || c.is_control();
+    let org_name = metadata.org_name.replace(unsafe_char, "_");
+    let domain = feedback.policy_published.domain.replace(unsafe_char, "_");
+    let report_id = metadata.report_id.replace(unsafe_char, "_");
+    let mut name = format!(
+        "{org_name}!{domain}!{}!{}",
+        metadata.date_range.begin.timestamp(),
+        metadata.date_range.end.timestamp()
+    );
+    if !report_id.is_empty() {
+        name.push('!');
+        name.push_str(&report_id);
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::{from_xml_str, Limits};
+
+    use super::{report_file_name, to_xml_string};
+
+    fn fixtures() -> Vec<(String, String)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut fixtures: Vec<(String, String)> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "xml"))
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, fs::read_to_string(path).unwrap())
+            })
+            .collect();
+        fixtures.sort();
+        assert!(!fixtures.is_empty());
+        fixtures
+    }
+
+    #[test]
+    fn round_trip_fixtures() {
+        for (name, xml) in fixtures() {
+            let feedback = from_xml_str(&xml, &Limits::default()).unwrap();
+            let written = to_xml_string(&feedback);
+            let reparsed = from_xml_str(&written, &Limits::default())
+                .unwrap_or_else(|e| panic!("{name}: {e}\n{written}"));
+            assert_eq!(feedback, reparsed, "{name}");
+            assert_eq!(written, to_xml_string(&reparsed), "{name}");
+        }
+    }
+
+    #[test]
+    fn write_schema_element_order() {
+        let xml = fixtures()
+            .into_iter()
+            .find(|(name, _)| name == "full.xml")
+            .unwrap()
+            .1;
+        let feedback = from_xml_str(&xml, &Limits::default()).unwrap();
+        let written = to_xml_string(&feedback);
+        let expected = xml.replace("\r\n", "\n");
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn file_name() {
+        let xml = fixtures()
+            .into_iter()
+            .find(|(name, _)| name == "full.xml")
+            .unwrap()
+            .1;
+        let feedback = from_xml_str(&xml, &Limits::default()).unwrap();
+        assert_eq!(
+            report_file_name(&feedback),
+            "mail.receiver.example!example.com!1700006400!1700092799!17000064-0001"
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::dns::{Lookup, TxtLookup};
+use crate::domain::normalize;
+use crate::Error;
+
+/// The data of a resource record supported in zone files.
+#[derive(Debug, Clone, PartialEq)]
+enum Data {
+    Address(IpAddr),
+    Mx(u16, String),
+    Txt(String),
+    Cname(String),
+}
+
+/// Why an entry of a zone file could not be parsed.
+#[derive(Debug, PartialEq)]
+pub enum ZoneError {
+    MissingOrigin,
+    MissingType,
+    InvalidAddress,
+    InvalidMxPreference,
+    InvalidMx,
+    MissingCnameTarget,
+}
+
+impl fmt::Display for ZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZoneError::MissingOrigin => write!(f, "missing origin"),
+            ZoneError::MissingType => write!(f, "missing record type"),
+            ZoneError::InvalidAddress => write!(f, "invalid address"),
+            ZoneError::InvalidMxPreference => write!(f, "invalid MX preference"),
+            ZoneError::InvalidMx => write!(f, "invalid MX record"),
+            ZoneError::MissingCnameTarget => write!(f, "missing CNAME target"),
+        }
+    }
+}
+
+/// Records read from zone files, answering lookups offline.
+///
+/// Only A, AAAA, MX, TXT and CNAME records are supported; other records are ignored.
+#[derive(Debug, Default, Clone)]
+pub struct Zone {
+    records: HashMap<String, Vec<Data>>,
+}
+
+/// Removes a comment, which starts with a semicolon outside of quoted strings.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Splits an entry into fields, keeping quoted strings, with their quotes, as single fields.
+fn fields(entry: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in entry.chars() {
+        match c {
+            _ if escaped => {
+                field.push(c);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            '"' => {
+                quoted = !quoted;
+                field.push(c);
+            }
+            c if (c.is_whitespace() || c == '(' || c == ')') && !quoted => {
+                if !field.is_empty() {
+                    fields.push(std::mem::take(&mut field));
+                }
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() {
+        fields.push(field);
+    }
+    fields
+}
+
+/// Makes a name absolute relative to the origin.
+fn absolute(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.into()
+    } else if let Some(name) = name.strip_suffix('.') {
+        normalize(name)
+    } else if origin.is_empty() {
+        normalize(name)
+    } else {
+        normalize(&format!("{name}.{origin}"))
+    }
+}
+
+impl Zone {
+    /// Parses records in the format of RFC 1035 Section 5, relative to an initial origin.
+    ///
+    /// Entries may span lines within parentheses. `$ORIGIN` is supported and `$TTL` is ignored, as
+    /// records read from files never expire.
+    pub fn parse(&mut self, text: &str, origin: &str) -> Result<(), Error> {
+        let mut origin = normalize(origin);
+        let mut owner = origin.clone();
+        let mut entry = String::new();
+        let mut entry_line = 0;
+        let mut depth = 0;
+        for (i, line) in text.lines().enumerate() {
+            let line = strip_comment(line);
+            if depth == 0 {
+                entry.clear();
+                entry_line = i + 1;
+            }
+            entry.push_str(line);
+            entry.push(' ');
+            depth += line.matches('(').count() as i32 - line.matches(')').count() as i32;
+            if depth > 0 {
+                continue;
+            }
+            let error = |reason| Error::ParseZone(entry_line, reason);
+            let starts_with_owner = !entry.starts_with(char::is_whitespace);
+            let mut fields = fields(&entry).into_iter().peekable();
+            let Some(first) = fields.peek().cloned() else {
+                continue;
+            };
+            if first.eq_ignore_ascii_case("$ORIGIN") {
+                fields.next();
+                origin = absolute(
+                    &fields
+                        .next()
+                        .ok_or_else(|| error(ZoneError::MissingOrigin))?,
+                    "",
+                );
+                continue;
+            }
+            if first.starts_with('$') {
+                continue;
+            }
+            if starts_with_owner {
+                owner = absolute(&first, &origin);
+                fields.next();
+            }
+            // The TTL and class may precede the type in either order.
+            let rtype = loop {
+                let field = fields.next().ok_or_else(|| error(ZoneError::MissingType))?;
+                if !field.chars().all(|c| c.is_ascii_digit())
+                    && !["IN", "CH", "HS"].contains(&field.to_uppercase().as_str())
+                {
+                    break field.to_uppercase();
+                }
+            };
+            let rdata: Vec<String> = fields.collect();
+            let data = match rtype.as_str() {
+                "A" | "AAAA" => Data::Address(
+                    rdata
+                        .first()
+                        .and_then(|ip| ip.parse().ok())
+                        .ok_or_else(|| error(ZoneError::InvalidAddress))?,
+                ),
+                "MX" => match rdata.as_slice() {
+                    [preference, exchange] => Data::Mx(
+                        preference
+                            .parse()
+                            .map_err(|_| error(ZoneError::InvalidMxPreference))?,
+                        absolute(exchange, &origin),
+                    ),
+                    _ => return Err(error(ZoneError::InvalidMx)),
+                },
+                "TXT" => Data::Txt(
+                    rdata
+                        .iter()
+                        .map(|s| {
+                            s.strip_prefix('"')
+                                .and_then(|s| s.strip_suffix('"'))
+                                .unwrap_or(s)
+                        })
+                        .collect(),
+                ),
+                "CNAME" => Data::Cname(absolute(
+                    rdata
+                        .first()
+                        .ok_or_else(|| error(ZoneError::MissingCnameTarget))?,
+                    &origin,
+                )),
+                _ => continue,
+            };
+            self.records.entry(owner.clone()).or_default().push(data);
+        }
+        Ok(())
+    }
+
+    /// Reads a zone file whose initial origin is its file name without a `.zone` extension.
+    pub fn load(&mut self, path: &Path) -> Result<(), Error> {
+        let text = fs::read_to_string(path).map_err(|e| Error::ReadInput(path.into(), e))?;
+        let origin = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let origin = origin.strip_suffix(".zone").unwrap_or(&origin);
+        self.parse(&text, origin)
+    }
+
+    /// The records of a name, following CNAME records.
+    fn data(&self, name: &str) -> &[Data] {
+        let mut name = normalize(name);
+        for _ in 0..8 {
+            let data = self.records.get(&name).map_or(&[][..], Vec::as_slice);
+            match data {
+                [Data::Cname(target)] => name = target.clone(),
+                data => return data,
+            }
+        }
+        &[]
+    }
+}
+
+impl TxtLookup for Zone {
+    fn lookup_txt(&self, name: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .data(name)
+            .iter()
+            .filter_map(|data| match data {
+                Data::Txt(txt) => Some(txt.clone()),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+impl Lookup for Zone {
+    fn lookup_ips(&self, name: &str, ipv6: bool) -> Result<Vec<IpAddr>, Error> {
+        Ok(self
+            .data(name)
+            .iter()
+            .filter_map(|data| match data {
+                Data::Address(ip) if ip.is_ipv6() == ipv6 => Some(*ip),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn lookup_mx(&self, name: &str) -> Result<Vec<String>, Error> {
+        let mut exchanges: Vec<(u16, String)> = self
+            .data(name)
+            .iter()
+            .filter_map(|data| match data {
+                Data::Mx(preference, exchange) => Some((*preference, exchange.clone())),
+                _ => None,
+            })
+            .collect();
+        exchanges.sort();
+        Ok(exchanges
+            .into_iter()
+            .map(|(_, exchange)| exchange)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns::{Lookup, TxtLookup};
+
+    use super::Zone;
+
+    #[test]
+    fn parse_zone() {
+        let mut zone = Zone::default();
+        zone.parse(
+            r#"
+$TTL 3600
+@       IN  TXT  "v=spf1 mx " "include:_spf.example.net -all" ; comment
+        IN  MX   10 mx1
+        3600 IN MX 5 mx2.example.com.
+mx1     A    192.0.2.1
+mx2     IN   AAAA 2001:db8::2
+_dmarc  TXT  ( "v=DMARC1; p=reject;"
+               "rua=mailto:d@example.com" )
+www     CNAME example.com.
+$ORIGIN example.net.
+_spf    TXT  "v=spf1 ip4:198.51.100.0/24 -all"
+"#,
+            "example.com",
+        )
+        .unwrap();
+        assert_eq!(
+            zone.lookup_txt("example.com").unwrap(),
+            vec!["v=spf1 mx include:_spf.example.net -all"]
+        );
+        assert_eq!(
+            zone.lookup_mx("example.com").unwrap(),
+            vec!["mx2.example.com", "mx1.example.com"]
+        );
+        assert_eq!(
+            zone.lookup_ips("mx2.example.com", true).unwrap(),
+            vec!["2001:db8::2".parse::<std::net::IpAddr>().unwrap()]
+        );
+        assert!(zone
+            .lookup_ips("mx2.example.com", false)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            zone.lookup_txt("_dmarc.example.com").unwrap(),
+            vec!["v=DMARC1; p=reject;rua=mailto:d@example.com"]
+        );
+        assert_eq!(zone.lookup_mx("www.example.com").unwrap().len(), 2);
+        assert_eq!(zone.lookup_txt("_spf.example.net").unwrap().len(), 1);
+    }
+}
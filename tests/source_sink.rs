@@ -0,0 +1,49 @@
+use dagger::source::{Raw, RawKind, ReadError};
+use dagger::{ingest, Diagnostics, Feedback, Limits, ReportSink, ReportSource};
+
+/// Yields the reports of an in-memory list.
+struct Reports(Vec<&'static str>);
+
+impl ReportSource for Reports {
+    fn next_raw(&mut self) -> Option<Result<Raw, ReadError>> {
+        let content = self.0.pop()?;
+        Some(Ok(Raw {
+            origin: "memory".to_string(),
+            kind: RawKind::Report,
+            content: content.as_bytes().to_vec(),
+        }))
+    }
+}
+
+/// Collects the report IDs of all consumed reports.
+#[derive(Default)]
+struct ReportIds(Vec<String>);
+
+impl ReportSink<String> for ReportIds {
+    fn consume(&mut self, feedbacks: &[Feedback]) -> Result<(), String> {
+        if feedbacks.is_empty() {
+            return Err("no reports".to_string());
+        }
+        let ids = feedbacks
+            .iter()
+            .map(|f| f.report_metadata.report_id.clone());
+        self.0.extend(ids);
+        Ok(())
+    }
+}
+
+#[test]
+fn ingest_custom_source_into_custom_sink() {
+    let mut source = Reports(vec![include_str!("fixtures/full.xml"), "not a report"]);
+    let mut diagnostics = Diagnostics::default();
+    let feedbacks = ingest(&mut source, &Limits::default(), None, &mut diagnostics);
+    assert_eq!(feedbacks.len(), 1);
+    assert_eq!(diagnostics.messages, 2);
+    assert_eq!(diagnostics.failures.len(), 1);
+    assert_eq!(diagnostics.failures[0].index, 0);
+
+    let mut sink = ReportIds::default();
+    sink.consume(&feedbacks).unwrap();
+    assert_eq!(sink.0, [feedbacks[0].report_metadata.report_id.clone()]);
+    assert_eq!(sink.consume(&[]), Err("no reports".to_string()));
+}